@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::params::MIN_POLLING_INTERVAL_MS;
+
+/// User configuration, read from `config.json` in the app config directory.
+///
+/// Every field is optional in the file; missing ones fall back to their defaults.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub backend: Option<BackendKind>, // Single player backend to use, platform defaults if `None`
+    pub backends: Vec<BackendKind>,   // Player backends to follow in priority order, overrides `backend`
+    pub polling: PollingConfig,       // Intervals of the player status polling
+    pub spotify_web: SpotifyWebConfig, // Spotify Web API backend settings
+    pub mpd: MpdConfig,               // MPD backend settings
+    pub vlc: VlcConfig,               // VLC backend settings
+    pub subsonic: SubsonicConfig,     // Subsonic backend settings
+    pub jellyfin: JellyfinConfig,     // Jellyfin backend settings
+    pub kodi: KodiConfig,             // Kodi backend settings
+    pub browser: BrowserConfig,       // Browser bridge settings
+}
+
+/// Player backends that can be selected in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    Spotify,    // Spotify desktop app through AppleScript (macOS)
+    AppleMusic, // Music.app through AppleScript (macOS)
+    SpotifyWeb, // Spotify Web API
+    Mpris,      // MPRIS2 players over D-Bus (Linux)
+    Mpd,        // Music Player Daemon over its TCP protocol
+    Vlc,        // VLC through its HTTP interface
+    Subsonic,   // Songs played from a Subsonic-compatible server (read only)
+    Jellyfin,   // Music played on the clients of a Jellyfin server
+    Kodi,       // Kodi through JSON-RPC
+    Browser,    // Media sessions of browser tabs, through the companion extension
+}
+
+impl Config {
+    /// Loads the configuration from the given file.
+    ///
+    /// A missing file yields the defaults; an invalid one is logged and ignored.
+    /// Polling intervals are clamped to usable values.
+    pub fn load(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        let mut config: Self = serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid config {}: {}", path.display(), e);
+            Self::default()
+        });
+        config.polling.clamp();
+        config
+    }
+}
+
+/// Intervals (in milliseconds) used by the adaptive status polling.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PollingConfig {
+    pub playing_interval_ms: u64,   // While playing with the notch expanded
+    pub collapsed_interval_ms: u64, // While playing with the notch collapsed, or paused with it expanded
+    pub paused_interval_ms: u64,    // While paused or stopped with the notch collapsed
+    pub absent_initial_interval_ms: u64, // First retry after the player went away
+    pub absent_max_interval_ms: u64, // Upper bound of the exponential backoff while the player is away
+}
+
+impl PollingConfig {
+    /// Raises every interval to `MIN_POLLING_INTERVAL_MS`, so the status thread cannot
+    /// busy-loop nor the backoff stay at zero, and keeps the backoff maximum at or
+    /// above its initial interval.
+    pub fn clamp(&mut self) {
+        for interval in [
+            &mut self.playing_interval_ms,
+            &mut self.collapsed_interval_ms,
+            &mut self.paused_interval_ms,
+            &mut self.absent_initial_interval_ms,
+        ] {
+            *interval = (*interval).max(MIN_POLLING_INTERVAL_MS);
+        }
+        self.absent_max_interval_ms = self
+            .absent_max_interval_ms
+            .max(self.absent_initial_interval_ms);
+    }
+
+    /// Returns the interval while playing with the notch expanded.
+    pub fn playing_interval(&self) -> Duration {
+        Duration::from_millis(self.playing_interval_ms)
+    }
+
+    /// Returns the interval while playing collapsed, or paused expanded.
+    pub fn collapsed_interval(&self) -> Duration {
+        Duration::from_millis(self.collapsed_interval_ms)
+    }
+
+    /// Returns the interval while paused with the notch collapsed.
+    pub fn paused_interval(&self) -> Duration {
+        Duration::from_millis(self.paused_interval_ms)
+    }
+
+    /// Returns the first backoff interval while the player is away.
+    pub fn absent_initial_interval(&self) -> Duration {
+        Duration::from_millis(self.absent_initial_interval_ms)
+    }
+
+    /// Returns the maximum backoff interval while the player is away.
+    pub fn absent_max_interval(&self) -> Duration {
+        Duration::from_millis(self.absent_max_interval_ms)
+    }
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            playing_interval_ms: 800,
+            collapsed_interval_ms: 2_000,
+            paused_interval_ms: 5_000,
+            absent_initial_interval_ms: 2_000,
+            absent_max_interval_ms: 60_000,
+        }
+    }
+}
+
+/// Settings of the Spotify Web API backend.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpotifyWebConfig {
+    pub client_id: Option<String>, // Client id of the Spotify app registered for Noci
+    pub redirect_port: u16,        // Loopback port receiving the authorization redirect
+    pub accounts_url: String,      // Base URL of the accounts service
+    pub api_url: String,           // Base URL of the Web API
+}
+
+impl SpotifyWebConfig {
+    /// Returns the redirect URI registered for the Spotify app.
+    pub fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}/callback", self.redirect_port)
+    }
+}
+
+impl Default for SpotifyWebConfig {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            redirect_port: 8898,
+            accounts_url: "https://accounts.spotify.com".to_string(),
+            api_url: "https://api.spotify.com/v1".to_string(),
+        }
+    }
+}
+
+/// Settings of the MPD backend.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MpdConfig {
+    pub host: String,             // Host MPD listens on
+    pub port: u16,                // Port MPD listens on
+    pub password: Option<String>, // Password sent after connecting, if MPD requires one
+}
+
+impl Default for MpdConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 6600,
+            password: None,
+        }
+    }
+}
+
+/// Settings of the VLC backend, matching VLC's HTTP interface options.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VlcConfig {
+    pub host: String,             // Host the HTTP interface listens on
+    pub port: u16,                // Port of the HTTP interface (`--http-port`)
+    pub password: Option<String>, // Password of the HTTP interface (`--http-password`)
+}
+
+impl Default for VlcConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            password: None,
+        }
+    }
+}
+
+/// Settings of the Subsonic backend (Navidrome, Airsonic, Gonic...).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SubsonicConfig {
+    pub url: Option<String>,      // URL of the server, e.g. `https://music.example.com`
+    pub username: Option<String>, // User whose songs are followed
+    pub password: Option<String>, // Password of the user, only sent as a salted token
+}
+
+/// Settings of the Jellyfin backend.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JellyfinConfig {
+    pub url: Option<String>,      // URL of the server, e.g. `http://127.0.0.1:8096`
+    pub api_key: Option<String>,  // API key created in the server dashboard
+    pub username: Option<String>, // User whose sessions are followed, every user if unset
+}
+
+/// Settings of the Kodi backend, matching Kodi's remote control settings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KodiConfig {
+    pub host: String,             // Host Kodi runs on
+    pub port: u16,                // Port of the web server serving JSON-RPC over HTTP
+    pub tcp_port: u16,            // Port of the TCP interface pushing notifications
+    pub username: String,         // Username of the web server
+    pub password: Option<String>, // Password of the web server, if one is set
+}
+
+impl Default for KodiConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            tcp_port: 9090,
+            username: "kodi".to_string(),
+            password: None,
+        }
+    }
+}
+
+/// Settings of the browser bridge.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BrowserConfig {
+    pub port: u16,             // Loopback port the extension connects to
+    pub token: Option<String>, // Secret the extension sends in `hello`, the bridge is off if unset
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        Self {
+            port: 8974,
+            token: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_or_invalid_file_yields_the_defaults() {
+        let path = std::env::temp_dir().join(format!(
+            "noci-config-invalid-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        assert_eq!(Config::load(&path).polling.playing_interval_ms, 800);
+
+        fs::write(&path, "{ not json").unwrap();
+        let polling = Config::load(&path).polling;
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(polling.paused_interval_ms, 5_000);
+    }
+
+    #[test]
+    fn missing_fields_fall_back_to_their_defaults() {
+        let path = std::env::temp_dir().join(format!(
+            "noci-config-partial-{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, r#"{"polling":{"playing_interval_ms":300}}"#).unwrap();
+
+        let polling = Config::load(&path).polling;
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(polling.playing_interval_ms, 300);
+        assert_eq!(polling.collapsed_interval_ms, 2_000);
+    }
+
+    #[test]
+    fn polling_intervals_are_clamped_on_load() {
+        let path = std::env::temp_dir().join(format!("noci-config-{}.json", std::process::id()));
+        let json = r#"{"polling":{"playing_interval_ms":0,"absent_initial_interval_ms":0,"absent_max_interval_ms":0}}"#;
+        fs::write(&path, json).unwrap();
+
+        let polling = Config::load(&path).polling;
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(polling.playing_interval_ms, MIN_POLLING_INTERVAL_MS);
+        assert_eq!(polling.absent_initial_interval_ms, MIN_POLLING_INTERVAL_MS);
+        assert_eq!(polling.absent_max_interval_ms, MIN_POLLING_INTERVAL_MS);
+        assert_eq!(polling.collapsed_interval_ms, 2_000);
+    }
+
+    #[test]
+    fn backoff_maximum_is_raised_to_the_initial_interval() {
+        let mut polling = PollingConfig {
+            absent_initial_interval_ms: 10_000,
+            absent_max_interval_ms: 3_000,
+            ..PollingConfig::default()
+        };
+
+        polling.clamp();
+
+        assert_eq!(polling.absent_max_interval_ms, 10_000);
+    }
+}
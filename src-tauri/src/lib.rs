@@ -1,9 +1,10 @@
-use std::{thread, time::Duration};
+use std::sync::Arc;
 use tauri::{
-    ActivationPolicy, Builder, Emitter, LogicalSize, PhysicalPosition, PhysicalSize, WebviewUrl,
+    ActivationPolicy, Builder, LogicalSize, Manager, PhysicalPosition, PhysicalSize, WebviewUrl,
     WebviewWindowBuilder,
 };
 
+pub mod config;
 pub mod params;
 pub mod player;
 pub mod window;
@@ -34,14 +35,29 @@ pub fn run() {
 
                 let window = win_builder.build()?;
 
-                // Create a thread that will update the window with the Spotify status every 800ms
-                let window_for_thread = window.clone();
-                thread::spawn(move || loop {
-                    if let Some(status) = player::get_spotify_status() {
-                        // Emit the Spotify status to the window
-                        let _ = window_for_thread.emit("spotify-status-update", status.clone());
-                    }
-                    thread::sleep(Duration::from_millis(800));
+                // Load the user configuration
+                let config = config::Config::load(
+                    &app.path().app_config_dir()?.join(params::CONFIG_FILE_NAME),
+                );
+
+                // Register the players that commands and the status thread dispatch to
+                let auth = Arc::new(player::SpotifyAuth::new(
+                    config.spotify_web.clone(),
+                    Box::new(player::spotify_web::KeyringTokenStore),
+                ));
+                app.manage(player::PlayerManager::new(player::create_players(
+                    &config, &auth,
+                )));
+                app.manage(auth);
+                app.manage(player::StatusService::new(config.polling.clone()));
+
+                // Create a thread that will update the window with player status changes
+                player::service::spawn_status_thread(app.handle().clone(), window.clone());
+
+                // Poll immediately when the notch expands so the UI is up to date
+                let handle = app.handle().clone();
+                window::on_notch_expansion_changed(move |_| {
+                    handle.state::<player::StatusService>().scheduler().wake()
                 });
 
                 // Get the primary monitor
@@ -81,9 +97,31 @@ pub fn run() {
         // Define the invoke handlers for the app
         .invoke_handler(tauri::generate_handler![
             player::set_track_position,
+            player::play_uri,
+            player::save_current_track,
+            player::remove_current_track,
+            player::get_queue,
+            player::add_to_queue,
+            player::list_devices,
+            player::transfer_playback,
+            player::seek_relative,
+            player::restart_track,
+            player::smart_previous_track,
             player::toggle_playback,
             player::next_track,
             player::previous_track,
+            player::set_volume,
+            player::adjust_volume,
+            player::toggle_mute,
+            player::set_shuffle,
+            player::cycle_repeat,
+            player::player_capabilities,
+            player::get_interpolated_position,
+            player::list_players,
+            player::pin_player,
+            player::spotify_login,
+            player::spotify_logout,
+            player::spotify_logged_in,
             window::exit_app
         ])
         // Run the app
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
 // Initial window dimensions and constants for window size management
@@ -7,6 +9,59 @@ pub const NOTCH_WINDOW_LEVEL: i64 = 40; // Window level used for notched display
 pub const RESIZED_WINDOW_WIDTH: f64 = 600.0; // Width of the window after resizing
 pub const RESIZED_WINDOW_HEIGHT: f64 = 250.0; // Height of the window after resizing
 
+// User configuration constants
+pub const CONFIG_FILE_NAME: &str = "config.json"; // Name of the user configuration file in the app config directory
+pub const MIN_POLLING_INTERVAL_MS: u64 = 100; // Shortest polling interval accepted from the configuration
+
+// Player status event constants
+pub const SEEK_DRIFT_TOLERANCE: f64 = 2.0; // Position drift (in seconds) beyond which a jump is reported as a seek
+pub const EMIT_STATUS_SNAPSHOT: bool = true; // Whether the full status is emitted alongside the granular events
+pub const POSITION_RESYNC_INTERVAL: Duration = Duration::from_secs(5); // Interval after which the interpolated position is resynced
+
+// Player manager constants
+pub const BACKGROUND_POLL_INTERVAL: Duration = Duration::from_secs(3); // Interval at which the inactive players are polled to notice one starting
+
+// Player script constants
+pub const STATUS_SCRIPT_TIMEOUT: Duration = Duration::from_secs(3); // Time after which a status script is killed
+pub const COMMAND_SCRIPT_TIMEOUT: Duration = Duration::from_secs(5); // Time after which a command script is killed
+pub const ARTWORK_DIR_NAME: &str = "noci-artwork"; // Directory in the temp dir that exported artwork is written to
+
+// MPD constants
+pub const MPD_TIMEOUT: Duration = Duration::from_secs(3); // Time after which an unanswered MPD request fails
+pub const MPD_RECONNECT_INTERVAL: Duration = Duration::from_secs(5); // Delay before the change watcher reconnects to MPD
+pub const MPD_IDLE_TIMEOUT: Duration = Duration::from_secs(60); // Time after which the change watcher checks that MPD still answers
+pub const MPD_BINARY_LIMIT: usize = 1 << 20; // Size of the artwork chunks requested from MPD (in bytes)
+
+// VLC constants
+pub const VLC_TIMEOUT: Duration = Duration::from_secs(3); // Time after which an unanswered VLC request fails
+
+// Kodi constants
+pub const KODI_TIMEOUT: Duration = Duration::from_secs(3); // Time after which an unanswered Kodi request fails
+pub const KODI_RECONNECT_INTERVAL: Duration = Duration::from_secs(5); // Delay before the notification watcher reconnects to Kodi
+pub const KODI_ARTWORK_MAX_SIZE: u64 = 16 * 1024 * 1024; // Largest thumbnail downloaded from Kodi (in bytes)
+pub const KODI_NOTIFICATION_IDLE_TIMEOUT: Duration = Duration::from_secs(60); // Silence after which the notification watcher pings Kodi
+
+// Browser bridge constants
+pub const BROWSER_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5); // Time after which a connection that did not complete the WebSocket handshake is dropped
+pub const BROWSER_COMMAND_INTERVAL: Duration = Duration::from_millis(50); // How often a connection forwards the queued commands to the extension
+
+// Media server constants
+pub const MEDIA_SERVER_TIMEOUT: Duration = Duration::from_secs(5); // Time after which an unanswered Subsonic or Jellyfin request, or a Kodi connection attempt, fails
+pub const COVER_ART_SIZE: u32 = 300; // Size (in pixels) of the cover art requested from media servers
+pub const COVER_ART_MAX_SIZE: u64 = 16 * 1024 * 1024; // Largest cover art downloaded from a media server (in bytes)
+pub const SUBSONIC_STALE_MARGIN_MINUTES: u64 = 2; // Minutes past the end of its song after which a Subsonic now-playing entry counts as stopped
+
+// Queue constants
+pub const QUEUE_REFRESH_INTERVAL: Duration = Duration::from_secs(15); // Interval at which the queue is refetched while the notch is expanded
+pub const QUEUE_MAX_LENGTH: usize = 20; // Maximum number of upcoming tracks reported
+
+// Seek command constants
+pub const SMART_PREVIOUS_THRESHOLD: f64 = 3.0; // Position (in seconds) beyond which "previous" restarts the track instead
+
+// Volume control constants
+pub const VOLUME_SETTLE_WINDOW: Duration = Duration::from_millis(1500); // Time during which a requested volume is assumed to be settling
+pub const UNMUTE_FALLBACK_VOLUME: u32 = 50; // Level restored when unmuting a player muted outside Noci
+
 // Enum representing various tracking area options for macOS applications
 #[repr(u64)]
 #[allow(non_upper_case_globals)]
@@ -24,15 +79,73 @@ pub enum NSTrackingAreaOptions {
 }
 
 // Struct representing the status of Spotify, including track and player details
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct SpotifyStatus {
-    track_name: Option<String>, // Name of the currently playing track
-    artist_name: Option<String>, // Name of the artist
-    track_volume: Option<u32>, // Volume level of the track
-    position: Option<f64>, // Current position in the track (in seconds)
-    track_duration: Option<f64>, // Duration of the track (in seconds)
-    album_cover: Option<String>, // URL of the album cover image
-    player_state: Option<String>, // Current state of the player (e.g., playing, paused)
-    error: Option<String>, // Error message, if any
+    pub track_name: Option<String>, // Name of the currently playing track
+    pub artist_name: Option<String>, // Name of the artist
+    pub metadata: TrackMetadata, // Additional metadata of the track
+    pub track_volume: Option<u32>, // Volume level of the track
+    pub position: Option<f64>, // Current position in the track (in seconds)
+    pub track_duration: Option<f64>, // Duration of the track (in seconds)
+    pub album_cover: Option<String>, // URL of the album cover image
+    pub player_state: Option<String>, // Current state of the player (e.g., playing, paused)
+    pub shuffle: Option<bool>, // Whether shuffle is enabled, if the player reports it
+    pub repeat_mode: Option<RepeatMode>, // Repeat mode, if the player reports it
+    pub is_saved: Option<bool>, // Whether the track is saved in the user's library, if known
+    pub device: Option<PlaybackDevice>, // Device the playback happens on, for players controlling several
+    pub error: Option<String>, // Error message, if any
+}
+
+// Struct representing backend-neutral track metadata, each field set when the player provides it
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TrackMetadata {
+    pub track_id: Option<String>, // Stable identifier of the track within its player
+    pub uri: Option<String>, // URI the track can be played or shared with
+    pub album_name: Option<String>, // Name of the album
+    pub album_artist: Option<String>, // Artist credited for the whole album
+    pub disc_number: Option<u32>, // Disc of the album the track is on
+    pub track_number: Option<u32>, // Position of the track on its disc
+    pub explicit: Option<bool>, // Whether the track has explicit lyrics
+    pub popularity: Option<u32>, // Popularity of the track (0-100)
+}
+
+// Struct representing a track waiting in the queue of a player
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct QueueTrack {
+    pub track_name: Option<String>, // Name of the track
+    pub artist_name: Option<String>, // Name of the artist
+    pub track_duration: Option<f64>, // Duration of the track (in seconds)
+    pub album_cover: Option<String>, // URL of the album cover image
+    pub metadata: TrackMetadata, // Additional metadata of the track
+}
+
+// Struct representing a device playback can be transferred to (e.g. a Spotify Connect speaker)
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PlaybackDevice {
+    pub id: Option<String>, // Identifier of the device, absent for restricted devices
+    pub name: String, // Human readable name of the device
+    pub device_type: String, // Kind of device (e.g., Computer, Smartphone, Speaker)
+    pub is_active: bool, // Whether playback currently happens on this device
+    pub volume: Option<u32>, // Volume level of the device, if it can be controlled
+}
+
+// Enum representing the repeat modes of a player
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    Off, // Playback stops at the end of the context
+    Track, // The current track is repeated
+    Context, // The album, playlist or queue is repeated
+}
+
+impl RepeatMode {
+    // Returns the mode following this one, in the order the players cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Context,
+            Self::Context => Self::Track,
+            Self::Track => Self::Off,
+        }
+    }
 }
 
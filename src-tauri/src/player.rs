@@ -1,177 +1,858 @@
-use std::process::Command;
-
-/// Fetches the current status of Spotify, returning a `SpotifyStatus` if successful.
-/// Utilizes AppleScript to interact with the Spotify application.
-pub fn get_spotify_status() -> Option<crate::params::SpotifyStatus> {
-    // Define AppleScript to extract Spotify track information
-    let script = r#"
-        on escape_json(s)
-            set s to my replace_text(s, "\\", "\\\\")
-            set s to my replace_text(s, "\"", "\\\"")
-            return s
-        end escape_json
-
-        on replace_text(t, r, w)
-            set AppleScript's text item delimiters to r
-            set t_items to every text item of t
-            set AppleScript's text item delimiters to w
-            set t to t_items as string
-            set AppleScript's text item delimiters to ""
-            return t
-        end replace_text
-
-        on fix_number_string(num)
-            set num_str to num as string
-            set num_str to my replace_text(num_str, ",", ".")
-            return num_str
-        end fix_number_string
-
-        tell application "Spotify"
-            if it is running then
-                set trackNameRaw to name of current track
-                set artistNameRaw to artist of current track
-                set trackName to my escape_json(trackNameRaw)
-                set artistName to my escape_json(artistNameRaw)
-                set trackVolume to sound volume as integer
-                set position to my fix_number_string(player position)
-                set trackDuration to my fix_number_string(duration of current track / 1000)
-                set albumCover to artwork url of current track
-                set playerState to player state
-
-                return "{\"track_name\":\"" & trackName & "\",\"artist_name\":\"" & artistName & "\",\"track_volume\":" & trackVolume & ",\"position\":" & position & ",\"track_duration\":" & trackDuration & ",\"album_cover\":\"" & albumCover & "\",\"player_state\":\"" & playerState & "\"}"
-            else
-                return "{\"error\":\"Spotify not running\"}"
-            end if
-        end tell
-    "#;
-
-    // Execute the AppleScript using osascript command
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .ok()?;
-    
-    // Convert the script output to a JSON string and parse it
-    let json_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    serde_json::from_str(&json_str).ok()
-}
-
-/// Sets the track position in Spotify to the specified value.
-#[tauri::command]
-pub fn set_track_position(position: f64) -> Result<(), String> {
-    // Define AppleScript to set the track position
-    let script = format!(
-        r#"
-        tell application "Spotify"
-            if it is running then
-                set player position to {}
-            end if
-        end tell
-        "#,
-        position
-    );
-
-    // Execute the AppleScript using osascript command
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .map_err(|e| format!("Failed to run AppleScript: {}", e))?;
-
-    // Check for errors in the AppleScript execution
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("AppleScript error: {}", stderr));
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::config::{BackendKind, Config};
+use crate::params::{self, PlaybackDevice, QueueTrack, RepeatMode, SpotifyStatus};
+
+pub mod apple_music;
+pub mod artwork;
+pub mod browser;
+pub mod error;
+pub mod events;
+pub mod jellyfin;
+pub mod kodi;
+pub mod manager;
+pub mod mpd;
+#[cfg(target_os = "linux")]
+pub mod mpris;
+pub mod queue;
+pub mod record;
+pub mod scheduler;
+pub mod script;
+pub mod service;
+pub mod spotify;
+pub mod spotify_web;
+pub mod subsonic;
+#[cfg(test)]
+mod testing;
+pub mod uri;
+pub mod vlc;
+pub mod volume;
+
+pub use apple_music::AppleMusicPlayer;
+pub use browser::BrowserPlayer;
+pub use error::PlayerError;
+pub use events::{PlayerEvent, StatusDiffer};
+pub use jellyfin::JellyfinPlayer;
+pub use kodi::KodiPlayer;
+pub use manager::{PlayerInfo, PlayerManager};
+pub use mpd::MpdPlayer;
+#[cfg(target_os = "linux")]
+pub use mpris::MprisPlayer;
+pub use queue::QueueWatcher;
+pub use scheduler::{PlayerActivity, PollScheduler};
+pub use service::StatusService;
+pub use spotify::SpotifyPlayer;
+pub use spotify_web::{SpotifyAuth, SpotifyWebPlayer};
+pub use subsonic::SubsonicPlayer;
+pub use uri::{SpotifyUri, SpotifyUriKind};
+pub use vlc::VlcPlayer;
+pub use volume::VolumeControl;
+
+/// Describes which controls a backend supports, so the UI can hide the rest.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerCapabilities {
+    pub can_play_pause: bool,  // Whether play/pause can be toggled
+    pub can_go_next: bool,     // Whether skipping to the next track is supported
+    pub can_go_previous: bool, // Whether returning to the previous track is supported
+    pub can_seek: bool,        // Whether the track position can be changed
+    pub can_set_volume: bool,  // Whether the volume can be changed
+    pub can_shuffle: bool,     // Whether shuffle can be toggled
+    pub can_repeat: bool,      // Whether the repeat mode can be changed
+    pub can_play_uri: bool,    // Whether Spotify URIs can be played
+    pub can_save: bool,        // Whether tracks can be saved to the library
+    pub can_queue: bool,       // Whether the queue can be read and added to
+    pub can_transfer: bool,    // Whether playback can be moved to another device
+}
+
+/// A media player backend that Noci can read the status of and control.
+///
+/// Every player integration (Spotify via AppleScript, and any future ones)
+/// implements this trait, and the Tauri commands and the status polling thread
+/// only ever talk to the currently active backend through it. Controls default
+/// to `Unsupported`, so backends only implement what their player offers.
+pub trait MediaPlayer: Send + Sync {
+    /// Human readable name of the player behind this backend.
+    fn name(&self) -> &str;
+
+    /// Returns the controls supported by this backend.
+    fn capabilities(&self) -> PlayerCapabilities;
+
+    /// Fetches the current playback status.
+    fn status(&self) -> Result<SpotifyStatus, PlayerError>;
+
+    /// Toggles playback state (play/pause).
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("play/pause"))
+    }
+
+    /// Skips to the next track.
+    fn next_track(&self) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("next track"))
+    }
+
+    /// Returns to the previous track.
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("previous track"))
+    }
+
+    /// Sets the position in the current track (in seconds).
+    fn set_position(&self, _position: f64) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("seek"))
+    }
+
+    /// Sets the volume level (0–100).
+    fn set_volume(&self, _level: u32) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("set volume"))
+    }
+
+    /// Enables or disables shuffle.
+    fn set_shuffle(&self, _enabled: bool) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("shuffle"))
+    }
+
+    /// Sets the repeat mode, `Unsupported` if the player lacks that mode.
+    fn set_repeat(&self, _mode: RepeatMode) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("repeat"))
     }
 
+    /// Starts playing a track, or a context such as an album or playlist.
+    fn play_uri(&self, _uri: &SpotifyUri) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("play Spotify URI"))
+    }
+
+    /// Saves the current track to the user's library, or removes it from there.
+    fn set_current_track_saved(&self, _saved: bool) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("save track"))
+    }
+
+    /// Returns the upcoming tracks, at most `QUEUE_MAX_LENGTH` of them.
+    fn queue(&self) -> Result<Vec<QueueTrack>, PlayerError> {
+        Err(PlayerError::unsupported("queue"))
+    }
+
+    /// Adds a track or episode to the end of the queue.
+    fn add_to_queue(&self, _uri: &SpotifyUri) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("queue"))
+    }
+
+    /// Lists the devices playback can be transferred to.
+    fn devices(&self) -> Result<Vec<PlaybackDevice>, PlayerError> {
+        Err(PlayerError::unsupported("devices"))
+    }
+
+    /// Moves playback to the given device, starting it there if `play` is set.
+    fn transfer_playback(&self, _device_id: &str, _play: bool) -> Result<(), PlayerError> {
+        Err(PlayerError::unsupported("devices"))
+    }
+
+    /// Starts calling `listener` whenever the player reports a change by itself, and
+    /// returns whether it does; players that don't are only polled.
+    fn watch(&self, _listener: ChangeListener) -> bool {
+        false
+    }
+}
+
+/// Callback a player calls when its state changed, so it is polled right away.
+pub type ChangeListener = Arc<dyn Fn() + Send + Sync>;
+
+/// Creates the players enabled in the configuration, in priority order.
+///
+/// `backends` lists them explicitly; otherwise `backend` alone, or the platform
+/// defaults, are used. Backends unavailable on this platform are skipped.
+pub fn create_players(
+    config: &Config,
+    auth: &Arc<SpotifyAuth>,
+) -> Vec<(BackendKind, Arc<dyn MediaPlayer>)> {
+    let kinds = if !config.backends.is_empty() {
+        config.backends.clone()
+    } else if let Some(kind) = config.backend {
+        vec![kind]
+    } else {
+        default_backend_kinds()
+    };
+
+    let mut players: Vec<(BackendKind, Arc<dyn MediaPlayer>)> = Vec::new();
+    for kind in kinds {
+        if players.iter().any(|(enabled, _)| *enabled == kind) {
+            continue;
+        }
+        if let Some(backend) = create_backend(kind, config, auth) {
+            players.push((kind, backend));
+        }
+    }
+
+    if players.is_empty() {
+        log::warn!("No configured backend is available, using the platform defaults");
+        players = default_backend_kinds()
+            .into_iter()
+            .filter_map(|kind| Some((kind, create_backend(kind, config, auth)?)))
+            .collect();
+    }
+    players
+}
+
+/// Creates the backend of the given kind, `None` if it is unavailable on this platform,
+/// not configured or cannot start.
+pub fn create_backend(
+    kind: BackendKind,
+    config: &Config,
+    auth: &Arc<SpotifyAuth>,
+) -> Option<Arc<dyn MediaPlayer>> {
+    match kind {
+        BackendKind::Spotify => Some(Arc::new(SpotifyPlayer::new())),
+        BackendKind::AppleMusic => Some(Arc::new(AppleMusicPlayer::new())),
+        BackendKind::SpotifyWeb => Some(Arc::new(SpotifyWebPlayer::new(
+            auth.clone(),
+            config.spotify_web.api_url.clone(),
+        ))),
+        #[cfg(target_os = "linux")]
+        BackendKind::Mpris => Some(Arc::new(MprisPlayer::new())),
+        #[cfg(not(target_os = "linux"))]
+        BackendKind::Mpris => {
+            log::warn!("The MPRIS backend is only available on Linux");
+            None
+        }
+        BackendKind::Mpd => Some(Arc::new(MpdPlayer::new(config.mpd.clone()))),
+        BackendKind::Vlc => Some(Arc::new(VlcPlayer::new(&config.vlc))),
+        BackendKind::Subsonic => match SubsonicPlayer::new(&config.subsonic) {
+            Some(player) => Some(Arc::new(player)),
+            None => {
+                log::warn!("The Subsonic backend needs a url, username and password");
+                None
+            }
+        },
+        BackendKind::Browser => Some(Arc::new(BrowserPlayer::new(&config.browser)?)),
+        BackendKind::Kodi => Some(Arc::new(KodiPlayer::new(&config.kodi))),
+        BackendKind::Jellyfin => match JellyfinPlayer::new(&config.jellyfin) {
+            Some(player) => Some(Arc::new(player)),
+            None => {
+                log::warn!("The Jellyfin backend needs a url and an API key");
+                None
+            }
+        },
+    }
+}
+
+/// Returns the backends used when none is configured, in priority order.
+fn default_backend_kinds() -> Vec<BackendKind> {
+    if cfg!(target_os = "linux") {
+        vec![BackendKind::Mpris]
+    } else {
+        vec![BackendKind::Spotify, BackendKind::AppleMusic]
+    }
+}
+
+/// Sets the track position in the active player to the specified value, clamped to the track.
+#[tauri::command(async)]
+pub fn set_track_position(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    position: f64,
+) -> Result<(), PlayerError> {
+    seek_to(&*player.get(), &service, position, service.track_duration())?;
     Ok(())
 }
 
-/// Toggles playback state in Spotify (play/pause).
-#[tauri::command]
-pub fn toggle_playback() -> Result<(), String> {
-    // Define AppleScript to toggle playback
-    let script = r#"
-        tell application "Spotify"
-            if it is running then
-                if player state is playing then
-                    pause
-                else
-                    play
-                end if
-            end if
-        end tell
-    "#;
-
-    // Execute the AppleScript using osascript command
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .map_err(|e| format!("Failed to run AppleScript: {}", e))?;
-
-    // Check for errors in the AppleScript execution
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("AppleScript error: {}", stderr));
+/// Starts playing a Spotify track, album, playlist or artist in the active player.
+///
+/// Accepts `spotify:` URIs and `open.spotify.com` links; anything else is rejected
+/// before reaching the player.
+#[tauri::command(async)]
+pub fn play_uri(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    uri: String,
+) -> Result<(), PlayerError> {
+    let uri = SpotifyUri::parse(&uri)?;
+    player.get().play_uri(&uri)?;
+    service.request_resync();
+    Ok(())
+}
+
+/// Saves the current track of the active player to the user's library.
+#[tauri::command(async)]
+pub fn save_current_track(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    player.get().set_current_track_saved(true)?;
+    service.scheduler().wake();
+    Ok(())
+}
+
+/// Removes the current track of the active player from the user's library.
+#[tauri::command(async)]
+pub fn remove_current_track(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    player.get().set_current_track_saved(false)?;
+    service.scheduler().wake();
+    Ok(())
+}
+
+/// Returns the upcoming tracks of the active player.
+#[tauri::command(async)]
+pub fn get_queue(player: State<'_, PlayerManager>) -> Result<Vec<QueueTrack>, PlayerError> {
+    player.get().queue()
+}
+
+/// Adds a Spotify track or episode to the queue of the active player.
+#[tauri::command(async)]
+pub fn add_to_queue(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    uri: String,
+) -> Result<(), PlayerError> {
+    let uri = SpotifyUri::parse(&uri)?;
+    if uri.kind().is_context() {
+        return Err(PlayerError::invalid_argument(
+            "only tracks and episodes can be queued",
+        ));
     }
+    player.get().add_to_queue(&uri)?;
+    service.queue().request_refresh();
+    service.scheduler().wake();
+    Ok(())
+}
+
+/// Lists the devices the active player can move playback to.
+#[tauri::command(async)]
+pub fn list_devices(player: State<'_, PlayerManager>) -> Result<Vec<PlaybackDevice>, PlayerError> {
+    player.get().devices()
+}
 
+/// Moves playback of the active player to another device.
+#[tauri::command(async)]
+pub fn transfer_playback(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    device_id: String,
+    play: bool,
+) -> Result<(), PlayerError> {
+    if device_id.trim().is_empty() {
+        return Err(PlayerError::invalid_argument("device id is empty"));
+    }
+    player.get().transfer_playback(&device_id, play)?;
+    // The new device has its own volume, so the level known for the old one is stale
+    service.volume().reset();
+    service.scheduler().wake();
     Ok(())
 }
 
-/// Skips to the next track in Spotify.
-#[tauri::command]
-pub fn next_track() -> Result<(), String> {
-    // Define AppleScript to skip to the next track
-    let script = r#"
-        tell application "Spotify"
-            if it is running then
-                next track
-            end if
-        end tell
-    "#;
-
-    // Execute the AppleScript using osascript command
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .map_err(|e| format!("Failed to run AppleScript: {}", e))?;
-
-    // Check for errors in the AppleScript execution
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("AppleScript error: {}", stderr));
+/// Moves the position in the active player by `seconds` (negative to go back), clamped to
+/// the track, and returns the new position.
+#[tauri::command(async)]
+pub fn seek_relative(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    seconds: f64,
+) -> Result<f64, PlayerError> {
+    let backend = player.get();
+    let (position, duration) = playback_position(&*backend, &service)?;
+    seek_to(&*backend, &service, position + seconds, duration)
+}
+
+/// Restarts the current track in the active player.
+#[tauri::command(async)]
+pub fn restart_track(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    seek_to(&*player.get(), &service, 0.0, None)?;
+    Ok(())
+}
+
+/// Restarts the current track if it played for more than `SMART_PREVIOUS_THRESHOLD`,
+/// otherwise returns to the previous track, like the previous button of most players.
+#[tauri::command(async)]
+pub fn smart_previous_track(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    let backend = player.get();
+    let (position, _) = playback_position(&*backend, &service)?;
+    if previous_restarts_track(position) {
+        seek_to(&*backend, &service, 0.0, None)?;
+    } else {
+        backend.previous_track()?;
+        service.request_resync();
+    }
+    Ok(())
+}
+
+/// Moves `backend` to `position` clamped to the track and moves the service clock along,
+/// returning the position sent.
+fn seek_to(
+    backend: &dyn MediaPlayer,
+    service: &StatusService,
+    position: f64,
+    duration: Option<f64>,
+) -> Result<f64, PlayerError> {
+    let target = clamp_position(position, duration);
+    backend.set_position(target)?;
+    service.seeked_to(target);
+    Ok(target)
+}
+
+/// Clamps a position to the track; a track of unknown duration is only bounded by its start.
+fn clamp_position(position: f64, duration: Option<f64>) -> f64 {
+    match duration {
+        Some(duration) => position.clamp(0.0, duration.max(0.0)),
+        None => position.max(0.0),
     }
+}
+
+/// Returns whether "previous" at `position` restarts the track rather than going back.
+fn previous_restarts_track(position: f64) -> bool {
+    position > params::SMART_PREVIOUS_THRESHOLD
+}
 
+/// Returns the current position and track duration (in seconds), extrapolated by the
+/// status service when it knows them and queried from the player otherwise.
+fn playback_position(
+    backend: &dyn MediaPlayer,
+    service: &StatusService,
+) -> Result<(f64, Option<f64>), PlayerError> {
+    if let Some(position) = service.interpolated_position() {
+        return Ok((position, service.track_duration()));
+    }
+
+    let status = backend.status()?;
+    let position = status
+        .position
+        .ok_or_else(|| PlayerError::unsupported("position"))?;
+    Ok((position, status.track_duration))
+}
+
+/// Toggles playback state in the active player (play/pause).
+#[tauri::command(async)]
+pub fn toggle_playback(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    player.get().toggle_playback()?;
+    service.request_resync();
     Ok(())
 }
 
-/// Returns to the previous track in Spotify.
-#[tauri::command]
-pub fn previous_track() -> Result<(), String> {
-    // Define AppleScript to return to the previous track
-    let script = r#"
-        tell application "Spotify"
-            if it is running then
-                previous track
-            end if
-        end tell
-    "#;
-
-    // Execute the AppleScript using osascript command
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .map_err(|e| format!("Failed to run AppleScript: {}", e))?;
-
-    // Check for errors in the AppleScript execution
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("AppleScript error: {}", stderr));
+/// Skips to the next track in the active player.
+#[tauri::command(async)]
+pub fn next_track(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    player.get().next_track()?;
+    service.request_resync();
+    Ok(())
+}
+
+/// Returns to the previous track in the active player.
+#[tauri::command(async)]
+pub fn previous_track(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<(), PlayerError> {
+    player.get().previous_track()?;
+    service.request_resync();
+    Ok(())
+}
+
+/// Sets the volume of the active player (clamped to 0–100) and returns the new level.
+#[tauri::command(async)]
+pub fn set_volume(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    level: i32,
+) -> Result<u32, PlayerError> {
+    service.volume().set(&*player.get(), level)
+}
+
+/// Changes the volume of the active player by `delta` (e.g. one scroll-wheel step)
+/// and returns the new level.
+#[tauri::command(async)]
+pub fn adjust_volume(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    delta: i32,
+) -> Result<u32, PlayerError> {
+    service.volume().adjust(&*player.get(), delta)
+}
+
+/// Mutes the active player, or restores its level from before muting, and returns the new level.
+#[tauri::command(async)]
+pub fn toggle_mute(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<u32, PlayerError> {
+    service.volume().toggle_mute(&*player.get())
+}
+
+/// Enables or disables shuffle in the active player.
+#[tauri::command(async)]
+pub fn set_shuffle(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    enabled: bool,
+) -> Result<(), PlayerError> {
+    player.get().set_shuffle(enabled)?;
+    service.scheduler().wake();
+    Ok(())
+}
+
+/// Switches the active player to the next repeat mode it supports and returns it.
+#[tauri::command(async)]
+pub fn cycle_repeat(
+    player: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+) -> Result<RepeatMode, PlayerError> {
+    let mode = next_repeat_mode(&*player.get())?;
+    service.scheduler().wake();
+    Ok(mode)
+}
+
+/// Switches `backend` to the repeat mode following its current one, skipping the
+/// modes it lacks (e.g. track repeat over AppleScript in Spotify), and returns it.
+fn next_repeat_mode(backend: &dyn MediaPlayer) -> Result<RepeatMode, PlayerError> {
+    let current = backend
+        .status()?
+        .repeat_mode
+        .ok_or_else(|| PlayerError::unsupported("repeat"))?;
+
+    let mut mode = current.next();
+    while mode != current {
+        match backend.set_repeat(mode) {
+            Ok(()) => return Ok(mode),
+            Err(PlayerError::Unsupported { .. }) => mode = mode.next(),
+            Err(error) => return Err(error),
+        }
     }
+    Err(PlayerError::unsupported("repeat"))
+}
 
+/// Returns the controls supported by the active player.
+#[tauri::command]
+pub fn player_capabilities(player: State<'_, PlayerManager>) -> PlayerCapabilities {
+    player.get().capabilities()
+}
+
+/// Returns the playback position extrapolated to now (in seconds), if a track is known.
+#[tauri::command]
+pub fn get_interpolated_position(service: State<'_, StatusService>) -> Option<f64> {
+    service.interpolated_position()
+}
+
+/// Lists the enabled players in priority order.
+#[tauri::command]
+pub fn list_players(manager: State<'_, PlayerManager>) -> Vec<PlayerInfo> {
+    manager.list()
+}
+
+/// Pins a player so commands keep going to it while it runs, or unpins with `None`.
+#[tauri::command]
+pub fn pin_player(
+    manager: State<'_, PlayerManager>,
+    service: State<'_, StatusService>,
+    id: Option<BackendKind>,
+) -> Result<(), PlayerError> {
+    manager.pin(id)?;
+    service.scheduler().wake();
     Ok(())
 }
+
+/// Logs in to the Spotify Web API through the browser.
+#[tauri::command(async)]
+pub fn spotify_login(auth: State<'_, Arc<SpotifyAuth>>) -> Result<(), PlayerError> {
+    auth.login(spotify_web::auth::open_in_browser)
+}
+
+/// Logs out of the Spotify Web API, forgetting the stored tokens.
+#[tauri::command]
+pub fn spotify_logout(auth: State<'_, Arc<SpotifyAuth>>) {
+    auth.logout()
+}
+
+/// Returns whether the user is logged in to the Spotify Web API.
+#[tauri::command]
+pub fn spotify_logged_in(auth: State<'_, Arc<SpotifyAuth>>) -> bool {
+    auth.is_logged_in()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::config::PollingConfig;
+    use crate::player::spotify_web::auth::MemoryTokenStore;
+
+    /// Returns the kinds and names of the players created for the configuration.
+    fn players(config: &Config) -> Vec<(BackendKind, String)> {
+        let auth = Arc::new(SpotifyAuth::new(
+            config.spotify_web.clone(),
+            Box::new(MemoryTokenStore::default()),
+        ));
+        create_players(config, &auth)
+            .into_iter()
+            .map(|(kind, backend)| (kind, backend.name().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn default_players_fit_the_platform() {
+        let players = players(&Config::default());
+
+        if cfg!(target_os = "linux") {
+            assert_eq!(players, [(BackendKind::Mpris, "MPRIS".to_string())]);
+        } else {
+            assert_eq!(
+                players,
+                [
+                    (BackendKind::Spotify, "Spotify".to_string()),
+                    (BackendKind::AppleMusic, "Apple Music".to_string()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn configured_backend_is_created() {
+        let config = Config {
+            backend: Some(BackendKind::SpotifyWeb),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            players(&config),
+            [(BackendKind::SpotifyWeb, "Spotify Web API".to_string())]
+        );
+    }
+
+    #[test]
+    fn backends_list_is_kept_in_order_without_duplicates() {
+        let config = Config {
+            backend: Some(BackendKind::Spotify),
+            backends: vec![
+                BackendKind::SpotifyWeb,
+                BackendKind::AppleMusic,
+                BackendKind::SpotifyWeb,
+            ],
+            ..Config::default()
+        };
+
+        assert_eq!(
+            players(&config),
+            [
+                (BackendKind::SpotifyWeb, "Spotify Web API".to_string()),
+                (BackendKind::AppleMusic, "Apple Music".to_string()),
+            ]
+        );
+    }
+
+    /// Player supporting a subset of the repeat modes and recording the positions it is sent.
+    struct FakePlayer {
+        mode: Mutex<Option<RepeatMode>>, // Repeat mode reported in the status
+        supported: Vec<RepeatMode>,      // Modes `set_repeat` accepts
+        requested: Mutex<Vec<RepeatMode>>, // Modes passed to `set_repeat`, in order
+        positions: Mutex<Vec<f64>>,      // Positions passed to `set_position`, in order
+    }
+
+    impl FakePlayer {
+        fn new(mode: Option<RepeatMode>, supported: &[RepeatMode]) -> Self {
+            Self {
+                mode: Mutex::new(mode),
+                supported: supported.to_vec(),
+                requested: Mutex::new(Vec::new()),
+                positions: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn requested(&self) -> Vec<RepeatMode> {
+            self.requested.lock().unwrap().clone()
+        }
+    }
+
+    impl MediaPlayer for FakePlayer {
+        fn name(&self) -> &str {
+            "Repeat"
+        }
+
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                can_play_pause: false,
+                can_go_next: false,
+                can_go_previous: false,
+                can_seek: false,
+                can_set_volume: false,
+                can_shuffle: false,
+                can_repeat: true,
+                can_play_uri: false,
+                can_save: false,
+                can_queue: false,
+                can_transfer: false,
+            }
+        }
+
+        fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+            Ok(SpotifyStatus {
+                repeat_mode: *self.mode.lock().unwrap(),
+                ..SpotifyStatus::default()
+            })
+        }
+
+        fn toggle_playback(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn next_track(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn previous_track(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+            self.positions.lock().unwrap().push(position);
+            Ok(())
+        }
+
+        fn set_volume(&self, _level: u32) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_shuffle(&self, _enabled: bool) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+            self.requested.lock().unwrap().push(mode);
+            if !self.supported.contains(&mode) {
+                return Err(PlayerError::unsupported("repeat"));
+            }
+            *self.mode.lock().unwrap() = Some(mode);
+            Ok(())
+        }
+    }
+
+    const ALL_MODES: [RepeatMode; 3] = [RepeatMode::Off, RepeatMode::Context, RepeatMode::Track];
+
+    #[test]
+    fn repeat_cycles_through_every_supported_mode() {
+        let player = FakePlayer::new(Some(RepeatMode::Off), &ALL_MODES);
+
+        let modes: Vec<_> = (0..3).map(|_| next_repeat_mode(&player).unwrap()).collect();
+
+        assert_eq!(
+            modes,
+            [RepeatMode::Context, RepeatMode::Track, RepeatMode::Off]
+        );
+    }
+
+    #[test]
+    fn unsupported_repeat_modes_are_skipped() {
+        let player = FakePlayer::new(
+            Some(RepeatMode::Context),
+            &[RepeatMode::Off, RepeatMode::Context],
+        );
+
+        assert_eq!(next_repeat_mode(&player).unwrap(), RepeatMode::Off);
+        assert_eq!(player.requested(), [RepeatMode::Track, RepeatMode::Off]);
+        assert_eq!(next_repeat_mode(&player).unwrap(), RepeatMode::Context);
+    }
+
+    #[test]
+    fn last_repeat_mode_wraps_to_off() {
+        let player = FakePlayer::new(Some(RepeatMode::Track), &ALL_MODES);
+
+        assert_eq!(next_repeat_mode(&player).unwrap(), RepeatMode::Off);
+        assert_eq!(player.requested(), [RepeatMode::Off]);
+    }
+
+    #[test]
+    fn repeat_without_another_mode_is_unsupported() {
+        let player = FakePlayer::new(Some(RepeatMode::Off), &[RepeatMode::Off]);
+
+        assert_eq!(
+            next_repeat_mode(&player).unwrap_err(),
+            PlayerError::unsupported("repeat")
+        );
+        assert_eq!(player.requested(), [RepeatMode::Context, RepeatMode::Track]);
+    }
+
+    #[test]
+    fn unknown_repeat_mode_is_unsupported() {
+        let player = FakePlayer::new(None, &ALL_MODES);
+
+        assert_eq!(
+            next_repeat_mode(&player).unwrap_err(),
+            PlayerError::unsupported("repeat")
+        );
+        assert!(player.requested().is_empty());
+    }
+
+    #[test]
+    fn seek_targets_are_clamped_to_the_track() {
+        // (position, seconds, duration, target)
+        let cases = [
+            (10.0, 5.0, Some(200.0), 15.0),
+            (10.0, -5.0, Some(200.0), 5.0),
+            (10.0, -10.0, Some(200.0), 0.0),
+            (10.0, -10.1, Some(200.0), 0.0),
+            (190.0, 10.0, Some(200.0), 200.0),
+            (190.0, 10.1, Some(200.0), 200.0),
+            (190.0, 9.9, Some(200.0), 199.9),
+            // Streams have no duration, so only the start bounds the target
+            (10.0, 500.0, None, 510.0),
+            (10.0, -10.1, None, 0.0),
+        ];
+
+        for (position, seconds, duration, target) in cases {
+            assert!(
+                (clamp_position(position + seconds, duration) - target).abs() < 1e-9,
+                "{} {:+} within {:?}",
+                position,
+                seconds,
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn previous_restarts_the_track_past_the_threshold() {
+        let threshold = params::SMART_PREVIOUS_THRESHOLD;
+
+        assert!(!previous_restarts_track(0.0));
+        assert!(!previous_restarts_track(threshold - 0.01));
+        assert!(!previous_restarts_track(threshold));
+        assert!(previous_restarts_track(threshold + 0.01));
+    }
+
+    #[test]
+    fn seeks_outside_the_track_are_clamped() {
+        let player = FakePlayer::new(None, &[]);
+        let service = StatusService::new(PollingConfig::default());
+        service.process(
+            &SpotifyStatus {
+                position: Some(10.0),
+                track_duration: Some(200.0),
+                player_state: Some("paused".to_string()),
+                ..SpotifyStatus::default()
+            },
+            &[],
+        );
+
+        assert_eq!(seek_to(&player, &service, -5.0, Some(200.0)).unwrap(), 0.0);
+        assert_eq!(service.interpolated_position(), Some(0.0));
+        assert_eq!(
+            seek_to(&player, &service, 250.0, Some(200.0)).unwrap(),
+            200.0
+        );
+        assert_eq!(service.interpolated_position(), Some(200.0));
+        assert_eq!(seek_to(&player, &service, 250.0, None).unwrap(), 250.0);
+        assert_eq!(*player.positions.lock().unwrap(), [0.0, 200.0, 250.0]);
+    }
+}
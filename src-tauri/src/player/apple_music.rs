@@ -0,0 +1,656 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::artwork::{artwork_dir, asset_url};
+use super::record::{applescript_string, Record, APPLESCRIPT_HELPERS};
+use super::script::{no_track_status, AppleScriptClient, OsaScriptRunner, ScriptRunner};
+use super::{MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::params::{RepeatMode, SpotifyStatus, TrackMetadata};
+
+/// AppleScript handler writing the artwork of a track to a file.
+///
+/// Music.app has no artwork URL, so the raw image data is exported instead; the
+/// handler returns the written path, or an empty string if the track has no artwork.
+const EXPORT_ARTWORK_HANDLER: &str = r#"
+        on export_artwork(theTrack, artPath)
+            try
+                tell application "Music"
+                    if (count of artworks of theTrack) is 0 then return ""
+                    set artData to raw data of artwork 1 of theTrack
+                end tell
+                set fileRef to open for access (POSIX file artPath) with write permission
+                set eof fileRef to 0
+                write artData to fileRef
+                close access fileRef
+                return artPath
+            on error
+                try
+                    close access (POSIX file artPath)
+                end try
+                return ""
+            end try
+        end export_artwork
+"#;
+
+/// AppleScript printing Music.app track information as a record (see `record::Record`).
+///
+/// The fields are, in order: marker, persistent ID, track name, artist, album,
+/// volume, position, duration, artwork path, player state, shuffle, repeat
+/// (`off`, `one` or `all`), album artist, disc number and track number. Times are printed
+/// as integer milliseconds. The artwork is only exported when the track differs
+/// from `knownId`, the track whose artwork was exported last.
+const STATUS_SCRIPT_BODY: &str = r#"
+        tell application "Music"
+            if it is running then
+                set US to character id 31
+                set playerState to player state as text
+                set trackVolume to sound volume as integer
+                set shuffleState to shuffle enabled as text
+                set repeatState to song repeat as text
+                if playerState is "stopped" then
+                    return "ok" & US & US & US & US & US & trackVolume & US & US & US & US & playerState & US & shuffleState & US & repeatState & US & US & US
+                end if
+
+                set theTrack to current track
+                set trackId to my clean_field(persistent ID of theTrack)
+                set trackName to my clean_field(name of theTrack)
+                set artistName to my clean_field(artist of theTrack)
+                set albumName to my clean_field(album of theTrack)
+                set albumArtist to my clean_field(album artist of theTrack)
+                set discNumber to my clean_field(disc number of theTrack)
+                set trackNumber to my clean_field(track number of theTrack)
+                set positionMs to ""
+                try
+                    set positionMs to (player position * 1000) as integer
+                end try
+                set durationMs to ""
+                try
+                    set durationMs to ((duration of theTrack) * 1000) as integer
+                end try
+                set artworkPath to ""
+                if trackId is not "" and trackId is not knownId then
+                    set artworkPath to my export_artwork(theTrack, artworkDir & "/" & trackId)
+                end if
+
+                return "ok" & US & trackId & US & trackName & US & artistName & US & albumName & US & trackVolume & US & positionMs & US & durationMs & US & artworkPath & US & playerState & US & shuffleState & US & repeatState & US & albumArtist & US & discNumber & US & trackNumber
+            else
+                return "not_running"
+            end if
+        end tell
+"#;
+
+/// Number of fields in the record printed by the status script, including the marker.
+const STATUS_FIELDS: usize = 15;
+
+/// Builds the AppleScript printing the Music.app status record.
+///
+/// `artwork_dir` is where artwork gets exported, `known_id` the persistent ID of
+/// the track whose artwork is already there.
+fn status_script(artwork_dir: &Path, known_id: &str) -> String {
+    format!(
+        "{}{}\n        set artworkDir to {}\n        set knownId to {}\n{}",
+        APPLESCRIPT_HELPERS,
+        EXPORT_ARTWORK_HANDLER,
+        applescript_string(&artwork_dir.to_string_lossy()),
+        applescript_string(known_id),
+        STATUS_SCRIPT_BODY
+    )
+}
+
+/// AppleScript toggling playback (play/pause).
+const TOGGLE_PLAYBACK_SCRIPT: &str = r#"
+        tell application "Music"
+            if it is running then
+                playpause
+            end if
+        end tell
+"#;
+
+/// AppleScript skipping to the next track.
+const NEXT_TRACK_SCRIPT: &str = r#"
+        tell application "Music"
+            if it is running then
+                next track
+            end if
+        end tell
+"#;
+
+/// AppleScript returning to the previous track.
+const PREVIOUS_TRACK_SCRIPT: &str = r#"
+        tell application "Music"
+            if it is running then
+                previous track
+            end if
+        end tell
+"#;
+
+/// Builds the AppleScript setting the track position (in seconds).
+fn set_position_script(position: f64) -> String {
+    format!(
+        r#"
+        tell application "Music"
+            if it is running then
+                set player position to {}
+            end if
+        end tell
+        "#,
+        position
+    )
+}
+
+/// Builds the AppleScript setting the volume level (0–100).
+fn set_volume_script(level: u32) -> String {
+    format!(
+        r#"
+        tell application "Music"
+            if it is running then
+                set sound volume to {}
+            end if
+        end tell
+        "#,
+        level
+    )
+}
+
+/// Builds the AppleScript enabling or disabling shuffle.
+fn set_shuffle_script(enabled: bool) -> String {
+    format!(
+        r#"
+        tell application "Music"
+            if it is running then
+                set shuffle enabled to {}
+            end if
+        end tell
+        "#,
+        enabled
+    )
+}
+
+/// Builds the AppleScript setting the repeat mode (`off`, `one` or `all`).
+fn set_repeat_script(mode: &str) -> String {
+    format!(
+        r#"
+        tell application "Music"
+            if it is running then
+                set song repeat to {}
+            end if
+        end tell
+        "#,
+        mode
+    )
+}
+
+/// Status parsed from the Music.app record, before the artwork is resolved.
+#[derive(Debug, Clone)]
+pub struct MusicStatus {
+    pub track_id: Option<String>,     // Persistent ID of the current track
+    pub artwork_path: Option<String>, // Path the artwork was exported to, if it was
+    pub status: SpotifyStatus,        // Status without the album cover
+}
+
+/// Artwork exported for a track.
+#[derive(Debug, Clone)]
+struct ExportedArtwork {
+    track_id: String,      // Persistent ID of the track
+    path: Option<PathBuf>, // Exported file, `None` if the track has no artwork
+}
+
+/// Backend controlling Music.app (Apple Music) through AppleScript (macOS only).
+pub struct AppleMusicPlayer {
+    client: AppleScriptClient,               // Runs the AppleScript snippets
+    artwork_dir: PathBuf,                    // Directory the artwork is exported to
+    artwork: Mutex<Option<ExportedArtwork>>, // Artwork of the last seen track
+}
+
+impl AppleMusicPlayer {
+    /// Creates a new Apple Music backend running scripts through `osascript`.
+    pub fn new() -> Self {
+        Self::with_runner(Arc::new(OsaScriptRunner))
+    }
+
+    /// Creates a new Apple Music backend running scripts through the given runner.
+    pub fn with_runner(runner: Arc<dyn ScriptRunner>) -> Self {
+        Self {
+            client: AppleScriptClient::new(runner),
+            artwork_dir: artwork_dir(),
+            artwork: Mutex::new(None),
+        }
+    }
+
+    /// Returns the album cover URL of the track, reusing the artwork exported earlier
+    /// for the same track and deleting the one of the previous track.
+    fn resolve_artwork(
+        &self,
+        track_id: Option<String>,
+        exported: Option<String>,
+    ) -> Option<String> {
+        let mut artwork = self.artwork.lock().unwrap_or_else(|e| e.into_inner());
+        let track_id = track_id?;
+
+        match artwork.as_ref() {
+            Some(known) if known.track_id == track_id => {}
+            _ => {
+                let path = exported.map(PathBuf::from);
+                if let Some(previous) = artwork.take().and_then(|known| known.path) {
+                    if Some(&previous) != path.as_ref() {
+                        let _ = fs::remove_file(previous);
+                    }
+                }
+                *artwork = Some(ExportedArtwork { track_id, path });
+            }
+        }
+
+        artwork
+            .as_ref()
+            .and_then(|known| known.path.as_deref())
+            .map(asset_url)
+    }
+}
+
+impl Default for AppleMusicPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaPlayer for AppleMusicPlayer {
+    fn name(&self) -> &str {
+        "Apple Music"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: true,
+            can_shuffle: true,
+            can_repeat: true,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        if let Err(e) = fs::create_dir_all(&self.artwork_dir) {
+            log::warn!("Failed to create {}: {}", self.artwork_dir.display(), e);
+        }
+        let known_id = self
+            .artwork
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|known| known.track_id.clone())
+            .unwrap_or_default();
+
+        let stdout = match self
+            .client
+            .query(&status_script(&self.artwork_dir, &known_id))?
+        {
+            Some(stdout) => stdout,
+            None => return Ok(no_track_status()),
+        };
+        let MusicStatus {
+            track_id,
+            artwork_path,
+            mut status,
+        } = parse_status(&stdout)?;
+
+        status.album_cover = self.resolve_artwork(track_id, artwork_path);
+        Ok(status)
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        self.client.command(TOGGLE_PLAYBACK_SCRIPT)
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.client.command(NEXT_TRACK_SCRIPT)
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.client.command(PREVIOUS_TRACK_SCRIPT)
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        self.client.command(&set_position_script(position))
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        self.client.command(&set_volume_script(level))
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        self.client.command(&set_shuffle_script(enabled))
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        let mode = match mode {
+            RepeatMode::Off => "off",
+            RepeatMode::Track => "one",
+            RepeatMode::Context => "all",
+        };
+        self.client.command(&set_repeat_script(mode))
+    }
+}
+
+/// Parses the record printed by the status script.
+///
+/// A `not_running` record is reported as `PlayerNotRunning` rather than as a status.
+/// Fast forwarding and rewinding are reported as `playing`.
+pub fn parse_status(stdout: &str) -> Result<MusicStatus, PlayerError> {
+    let record = Record::parse(stdout);
+    match record.marker() {
+        "ok" => record.expect_fields(STATUS_FIELDS)?,
+        "not_running" => return Err(PlayerError::PlayerNotRunning),
+        marker => {
+            return Err(PlayerError::parse(format!(
+                "unexpected marker {:?}",
+                marker
+            )))
+        }
+    }
+
+    let player_state = record.text(9).map(|state| match state.as_str() {
+        "fast forwarding" | "rewinding" => "playing".to_string(),
+        _ => state,
+    });
+
+    let track_id = record.text(1);
+    Ok(MusicStatus {
+        track_id: track_id.clone(),
+        artwork_path: record.text(8),
+        status: SpotifyStatus {
+            track_name: record.text(2),
+            artist_name: record.text(3),
+            metadata: TrackMetadata {
+                track_id,
+                uri: None,
+                album_name: record.text(4),
+                album_artist: record.text(12),
+                disc_number: record.count(13),
+                track_number: record.count(14),
+                explicit: None,
+                popularity: None,
+            },
+            track_volume: record
+                .number(5)
+                .map(|volume| volume.round().clamp(0.0, 100.0) as u32),
+            position: record.number(6).map(|ms| ms / 1000.0),
+            track_duration: record.number(7).map(|ms| ms / 1000.0),
+            album_cover: None,
+            player_state,
+            shuffle: record.boolean(10),
+            repeat_mode: record.text(11).and_then(|mode| match mode.as_str() {
+                "off" => Some(RepeatMode::Off),
+                "one" => Some(RepeatMode::Track),
+                "all" => Some(RepeatMode::Context),
+                _ => None,
+            }),
+            is_saved: None,
+            device: None,
+            error: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::script::{FakeScriptRunner, ScriptOutput};
+    use crate::player::SpotifyUri;
+
+    /// Record printed by Music.app for a library track with exported artwork
+    /// (`artwork_path` is filled in by `record`).
+    const LIBRARY_TRACK: [&str; STATUS_FIELDS] = [
+        "ok",
+        "A1B2C3D4E5F60718",
+        "Blue in Green",
+        "Miles Davis",
+        "Kind of Blue",
+        "55",
+        "12500",
+        "337000",
+        "",
+        "fast forwarding",
+        "true",
+        "one",
+        "Miles Davis",
+        "1",
+        "3",
+    ];
+
+    /// Record printed by Music.app for a radio stream: no duration, album or artwork.
+    const STREAM: [&str; STATUS_FIELDS] = [
+        "ok",
+        "0F1E2D3C4B5A6978",
+        "Jazz Radio",
+        "",
+        "",
+        "70",
+        "61000",
+        "",
+        "",
+        "playing",
+        "false",
+        "off",
+        "",
+        "0",
+        "0",
+    ];
+
+    /// Record printed by Music.app while it is stopped.
+    const STOPPED: [&str; STATUS_FIELDS] = [
+        "ok", "", "", "", "", "40", "", "", "", "stopped", "false", "all", "", "", "",
+    ];
+
+    /// Joins the fields with the unit separator, as the status script prints them.
+    fn record(fields: &[&str]) -> String {
+        fields.join("\u{1f}") + "\n"
+    }
+
+    /// Returns the record with the artwork path field replaced.
+    fn with_artwork(fields: [&str; STATUS_FIELDS], path: &Path) -> String {
+        let path = path.to_string_lossy();
+        let mut fields = fields;
+        fields[8] = &path;
+        record(&fields)
+    }
+
+    /// Returns a backend exporting artwork to a directory of its own.
+    fn player(name: &str) -> (Arc<FakeScriptRunner>, AppleMusicPlayer) {
+        let runner = Arc::new(FakeScriptRunner::new());
+        let artwork_dir =
+            std::env::temp_dir().join(format!("noci-music-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&artwork_dir);
+        let player = AppleMusicPlayer {
+            client: AppleScriptClient::new(runner.clone()),
+            artwork_dir,
+            artwork: Mutex::new(None),
+        };
+        (runner, player)
+    }
+
+    #[test]
+    fn library_track_is_parsed() {
+        let status = parse_status(&record(&LIBRARY_TRACK)).unwrap();
+
+        assert_eq!(status.track_id.as_deref(), Some("A1B2C3D4E5F60718"));
+        assert_eq!(status.artwork_path, None);
+        let status = status.status;
+        assert_eq!(status.track_name.as_deref(), Some("Blue in Green"));
+        assert_eq!(status.artist_name.as_deref(), Some("Miles Davis"));
+        assert_eq!(
+            status.metadata,
+            TrackMetadata {
+                track_id: Some("A1B2C3D4E5F60718".to_string()),
+                uri: None,
+                album_name: Some("Kind of Blue".to_string()),
+                album_artist: Some("Miles Davis".to_string()),
+                disc_number: Some(1),
+                track_number: Some(3),
+                explicit: None,
+                popularity: None,
+            }
+        );
+        assert_eq!(status.track_volume, Some(55));
+        assert_eq!(status.position, Some(12.5));
+        assert_eq!(status.track_duration, Some(337.0));
+        // Fast forwarding is reported as playing
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(true));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Track));
+    }
+
+    #[test]
+    fn stream_has_no_duration() {
+        let status = parse_status(&record(&STREAM)).unwrap().status;
+
+        assert_eq!(status.track_name.as_deref(), Some("Jazz Radio"));
+        assert_eq!(status.artist_name, None);
+        assert_eq!(status.metadata.album_name, None);
+        // Music.app prints 0 for unknown disc and track numbers
+        assert_eq!(status.metadata.disc_number, None);
+        assert_eq!(status.metadata.track_number, None);
+        assert_eq!(status.track_duration, None);
+        assert_eq!(status.position, Some(61.0));
+        assert_eq!(status.shuffle, Some(false));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Off));
+    }
+
+    #[test]
+    fn stopped_player_has_no_track() {
+        let status = parse_status(&record(&STOPPED)).unwrap();
+
+        assert_eq!(status.track_id, None);
+        assert_eq!(status.status.track_name, None);
+        assert_eq!(status.status.metadata, TrackMetadata::default());
+        assert_eq!(status.status.track_volume, Some(40));
+        assert_eq!(status.status.player_state.as_deref(), Some("stopped"));
+        assert_eq!(status.status.repeat_mode, Some(RepeatMode::Context));
+    }
+
+    #[test]
+    fn unexpected_records_are_errors() {
+        assert_eq!(
+            parse_status("not_running\n").unwrap_err(),
+            PlayerError::PlayerNotRunning
+        );
+        assert!(matches!(
+            parse_status(&record(&LIBRARY_TRACK[..12])),
+            Err(PlayerError::ParseError { .. })
+        ));
+        assert!(matches!(
+            parse_status("error\n"),
+            Err(PlayerError::ParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn artwork_is_exported_once_per_track() {
+        let (runner, player) = player("artwork");
+        fs::create_dir_all(&player.artwork_dir).unwrap();
+        let first = player.artwork_dir.join("A1B2C3D4E5F60718");
+        fs::write(&first, b"jpeg").unwrap();
+
+        runner.push_output(ScriptOutput::success(with_artwork(LIBRARY_TRACK, &first)));
+        let cover = player.status().unwrap().album_cover;
+        assert_eq!(cover, Some(asset_url(&first)));
+        assert!(runner.scripts()[0].contains(r#"set knownId to """#));
+
+        // The same track is not exported again, the known artwork is reused
+        runner.push_output(ScriptOutput::success(record(&LIBRARY_TRACK)));
+        assert_eq!(player.status().unwrap().album_cover, cover);
+        assert!(runner.scripts()[1].contains(r#"set knownId to "A1B2C3D4E5F60718""#));
+
+        // The next track has no artwork: the previous file is deleted
+        runner.push_output(ScriptOutput::success(record(&STREAM)));
+        assert_eq!(player.status().unwrap().album_cover, None);
+        assert!(!first.exists());
+
+        // While it plays, no artwork is looked for again
+        runner.push_output(ScriptOutput::success(record(&STREAM)));
+        assert_eq!(player.status().unwrap().album_cover, None);
+        assert!(runner.scripts()[3].contains(r#"set knownId to "0F1E2D3C4B5A6978""#));
+
+        let _ = fs::remove_dir_all(&player.artwork_dir);
+    }
+
+    #[test]
+    fn stopping_keeps_the_known_artwork() {
+        let (runner, player) = player("stopped");
+        fs::create_dir_all(&player.artwork_dir).unwrap();
+        let path = player.artwork_dir.join("A1B2C3D4E5F60718");
+        fs::write(&path, b"jpeg").unwrap();
+
+        runner.push_output(ScriptOutput::success(with_artwork(LIBRARY_TRACK, &path)));
+        player.status().unwrap();
+        runner.push_output(ScriptOutput::success(record(&STOPPED)));
+        assert_eq!(player.status().unwrap().album_cover, None);
+
+        // Resuming the same track reuses the exported file
+        runner.push_output(ScriptOutput::success(record(&LIBRARY_TRACK)));
+        assert_eq!(player.status().unwrap().album_cover, Some(asset_url(&path)));
+        assert!(path.exists());
+
+        let _ = fs::remove_dir_all(&player.artwork_dir);
+    }
+
+    #[test]
+    fn no_current_track_is_an_empty_status() {
+        let (runner, player) = player("no-track");
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Music got an error: Can’t get name of current track. (-1728)\n",
+        ));
+
+        let status = player.status().unwrap();
+        assert_eq!(status.track_name, None);
+        assert_eq!(status.player_state.as_deref(), Some("stopped"));
+    }
+
+    #[test]
+    fn commands_send_the_expected_scripts() {
+        let (runner, player) = player("commands");
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.previous_track().unwrap();
+        player.set_position(42.5).unwrap();
+        player.set_volume(30).unwrap();
+        player.set_shuffle(true).unwrap();
+        player.set_repeat(RepeatMode::Track).unwrap();
+        player.set_repeat(RepeatMode::Context).unwrap();
+
+        assert_eq!(
+            runner.scripts(),
+            vec![
+                TOGGLE_PLAYBACK_SCRIPT.to_string(),
+                NEXT_TRACK_SCRIPT.to_string(),
+                PREVIOUS_TRACK_SCRIPT.to_string(),
+                set_position_script(42.5),
+                set_volume_script(30),
+                set_shuffle_script(true),
+                set_repeat_script("one"),
+                set_repeat_script("all"),
+            ]
+        );
+    }
+
+    #[test]
+    fn spotify_uris_are_unsupported() {
+        let (runner, player) = player("uri");
+        let uri = SpotifyUri::parse("spotify:track:4uLU6hMCjMI75M1A2tKUQC").unwrap();
+
+        assert!(matches!(
+            player.play_uri(&uri),
+            Err(PlayerError::Unsupported { .. })
+        ));
+        assert!(!player.capabilities().can_play_uri);
+        assert!(runner.scripts().is_empty());
+    }
+}
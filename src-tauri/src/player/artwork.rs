@@ -0,0 +1,212 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use sha2::{Digest, Sha256};
+
+use super::PlayerError;
+use crate::params::ARTWORK_DIR_NAME;
+
+/// Returns the directory artwork exported by the local backends is written to.
+pub fn artwork_dir() -> PathBuf {
+    std::env::temp_dir().join(ARTWORK_DIR_NAME)
+}
+
+/// Returns the URL the webview loads a local file through (Tauri asset protocol).
+pub fn asset_url(path: &Path) -> String {
+    let encoded: String = path
+        .to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("asset://localhost/{}", encoded)
+}
+
+/// Artwork written for a track.
+#[derive(Debug, Clone)]
+struct CachedArtwork {
+    key: String,           // Identifies the track the artwork belongs to
+    path: Option<PathBuf>, // Written file, `None` if the track has no artwork
+}
+
+/// Artwork of the current track, fetched from players whose artwork the webview
+/// cannot load directly (e.g. behind authentication) and written to the artwork
+/// directory.
+///
+/// The artwork is fetched once per track, and the file of the previous track is
+/// deleted when the track changes.
+#[derive(Debug)]
+pub struct ArtworkCache {
+    prefix: &'static str, // Prefix of the written files, naming the backend
+    dir: PathBuf,         // Directory the files are written to
+    current: Mutex<Option<CachedArtwork>>, // Artwork of the last seen track
+}
+
+impl ArtworkCache {
+    /// Creates an empty cache writing files named after the given prefix to the
+    /// artwork directory.
+    pub fn new(prefix: &'static str) -> Self {
+        Self::with_dir(prefix, artwork_dir())
+    }
+
+    /// Creates an empty cache writing files named after the given prefix to `dir`.
+    pub fn with_dir(prefix: &'static str, dir: PathBuf) -> Self {
+        Self {
+            prefix,
+            dir,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Returns the URL of the artwork of the track identified by `key`, calling
+    /// `fetch` for its data only when the track changed.
+    ///
+    /// A failed fetch is logged once and remembered as no artwork for the track,
+    /// rather than retried at every poll while it plays.
+    pub fn resolve(
+        &self,
+        key: &str,
+        fetch: impl FnOnce() -> Result<Option<Vec<u8>>, PlayerError>,
+    ) -> Option<String> {
+        let mut current = self.current();
+        if current.as_ref().map_or(true, |known| known.key != key) {
+            let data = fetch().unwrap_or_else(|e| {
+                log::warn!("Failed to fetch the artwork of {}: {}", key, e);
+                None
+            });
+            let path = data.and_then(|data| self.write(key, &data));
+            if let Some(previous) = current.take().and_then(|known| known.path) {
+                if Some(&previous) != path.as_ref() {
+                    let _ = fs::remove_file(previous);
+                }
+            }
+            *current = Some(CachedArtwork {
+                key: key.to_string(),
+                path,
+            });
+        }
+
+        current
+            .as_ref()
+            .and_then(|known| known.path.as_deref())
+            .map(asset_url)
+    }
+
+    /// Writes artwork data to a file named after the track, returning its path.
+    fn write(&self, key: &str, data: &[u8]) -> Option<PathBuf> {
+        let digest = Sha256::digest(key.as_bytes());
+        let name: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        let path = self.dir.join(format!("{}-{}", self.prefix, name));
+
+        let written = fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, data));
+        match written {
+            Ok(()) => Some(path),
+            Err(e) => {
+                log::warn!("Failed to write {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Locks the cached artwork, recovering from a poisoned lock.
+    fn current(&self) -> MutexGuard<'_, Option<CachedArtwork>> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::player::testing::TempDir;
+
+    /// Returns the path of the file an asset URL points to.
+    fn path_of(url: &str) -> PathBuf {
+        let encoded = url.strip_prefix("asset://localhost/").unwrap();
+        let bytes: Vec<u8> = encoded
+            .split('%')
+            .enumerate()
+            .flat_map(|(index, part)| {
+                if index == 0 {
+                    part.as_bytes().to_vec()
+                } else {
+                    let mut bytes = vec![u8::from_str_radix(&part[..2], 16).unwrap()];
+                    bytes.extend_from_slice(&part.as_bytes()[2..]);
+                    bytes
+                }
+            })
+            .collect();
+        PathBuf::from(String::from_utf8(bytes).unwrap())
+    }
+
+    #[test]
+    fn asset_urls_encode_the_path() {
+        assert_eq!(
+            asset_url(Path::new("/tmp/a b/c-d_e.~f")),
+            "asset://localhost/%2Ftmp%2Fa%20b%2Fc-d_e.~f"
+        );
+    }
+
+    #[test]
+    fn artwork_is_fetched_once_per_track() {
+        let dir = TempDir::new("artwork-once");
+        let cache = ArtworkCache::with_dir("test", dir.path.clone());
+        let fetches = Cell::new(0);
+        let fetch = || {
+            fetches.set(fetches.get() + 1);
+            Ok(Some(b"jpeg".to_vec()))
+        };
+
+        let url = cache.resolve("song", fetch).unwrap();
+        assert_eq!(cache.resolve("song", fetch), Some(url.clone()));
+        assert_eq!(fetches.get(), 1);
+
+        let path = path_of(&url);
+        assert!(path.starts_with(&dir.path));
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("test-"));
+        assert_eq!(fs::read(path).unwrap(), b"jpeg");
+    }
+
+    #[test]
+    fn previous_artwork_is_deleted_when_the_track_changes() {
+        let dir = TempDir::new("artwork-change");
+        let cache = ArtworkCache::with_dir("test", dir.path.clone());
+
+        let first = path_of(&cache.resolve("first", || Ok(Some(b"1".to_vec()))).unwrap());
+        let second = path_of(&cache.resolve("second", || Ok(Some(b"2".to_vec()))).unwrap());
+        assert!(!first.exists());
+        assert_eq!(fs::read(&second).unwrap(), b"2");
+
+        // A track without artwork has no cover and removes the previous file
+        assert_eq!(cache.resolve("third", || Ok(None)), None);
+        assert!(!second.exists());
+    }
+
+    #[test]
+    fn failed_fetch_is_not_retried_for_the_same_track() {
+        let dir = TempDir::new("artwork-failure");
+        let cache = ArtworkCache::with_dir("test", dir.path.clone());
+        let fetches = Cell::new(0);
+        let fetch = || {
+            fetches.set(fetches.get() + 1);
+            Err(PlayerError::Timeout)
+        };
+
+        assert_eq!(cache.resolve("song", fetch), None);
+        assert_eq!(cache.resolve("song", fetch), None);
+        assert_eq!(fetches.get(), 1);
+
+        // The next track is fetched again
+        assert!(cache.resolve("next", || Ok(Some(b"2".to_vec()))).is_some());
+    }
+}
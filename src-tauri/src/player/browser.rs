@@ -0,0 +1,954 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+
+use super::{ChangeListener, MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::config::BrowserConfig;
+use crate::params::{self, PlaybackDevice, SpotifyStatus, TrackMetadata};
+
+/// Version of the protocol spoken with the extension, bumped on breaking changes.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Origin schemes of browser extensions; web pages must not drive the bridge.
+const EXTENSION_SCHEMES: [&str; 3] = ["chrome-extension", "moz-extension", "safari-web-extension"];
+
+/// Failure of a connection to the extension, boxed as tungstenite errors are large.
+type ConnectionError = Box<tungstenite::Error>;
+
+/// Message sent by the extension, tagged by its `type`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// First message of a connection, answered by `welcome`, or by `error` and a
+    /// close if the version is not supported or the token is wrong.
+    Hello {
+        version: u32,
+        token: Option<String>,   // Secret of the `browser.token` setting
+        browser: Option<String>, // e.g. `Firefox`, shown with the tabs
+    },
+    /// Media session of a tab, sent whenever it changes.
+    Session(Box<TabSession>),
+    /// The tab closed or dropped its media session.
+    Closed { tab_id: String },
+}
+
+/// Message sent to the extension, tagged by its `type`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    /// The connection is accepted.
+    Welcome { version: u32 },
+    /// Runs a Media Session action in a tab.
+    Command {
+        tab_id: String,
+        action: &'static str, // Media Session action
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seek_time: Option<f64>, // Seconds, for `seekto`
+    },
+    /// A message was rejected.
+    Error { message: String },
+}
+
+/// Media Session of a tab, as reported by the extension.
+///
+/// Tab ids are chosen by the extension and only need to be unique per connection.
+#[derive(Debug, Clone, Deserialize)]
+struct TabSession {
+    tab_id: String,
+    origin: Option<String>, // Origin of the page, e.g. `https://www.youtube.com`
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    artwork: Option<String>, // URL of the largest artwork
+    playback_state: String,  // `playing`, `paused` or `none`
+    position: Option<f64>,   // Seconds, when the session reported it
+    duration: Option<f64>,   // Seconds, absent or infinite for live streams
+    playback_rate: Option<f64>,
+    #[serde(default)]
+    actions: Vec<String>, // Media Session actions handled by the page
+}
+
+/// Tab known to the bridge.
+#[derive(Debug)]
+struct Tab {
+    connection: u64,                // Connection of the extension reporting the tab
+    session: TabSession,            // Last reported media session
+    reported_at: Instant,           // When the session was reported, to advance its position
+    playing_since: Option<Instant>, // When the tab started playing, `None` unless playing
+}
+
+impl Tab {
+    /// Returns the identifier of the tab across connections.
+    fn key(&self) -> String {
+        format!("{}:{}", self.connection, self.session.tab_id)
+    }
+
+    /// Returns whether the tab is playing.
+    fn is_playing(&self) -> bool {
+        self.playing_since.is_some()
+    }
+}
+
+/// Extension connected to the bridge.
+#[derive(Debug)]
+struct Connection {
+    browser: Option<String>,         // Browser name announced in `hello`
+    commands: Sender<ServerMessage>, // Messages to send to the extension
+}
+
+/// State shared between the connections and the player.
+#[derive(Default)]
+struct BridgeState {
+    connections: HashMap<u64, Connection>, // Extensions that completed the handshake
+    tabs: Vec<Tab>,                        // Tabs with a media session, in report order
+    selected: Option<String>,              // Key of the tab chosen by the user
+    listener: Option<ChangeListener>,      // Called when a tab changes
+}
+
+impl BridgeState {
+    /// Returns the tab to follow: the one chosen by the user while it exists, then
+    /// the one that most recently started playing, then the most recently reported.
+    fn current(&self) -> Option<&Tab> {
+        let selected = self
+            .selected
+            .as_ref()
+            .and_then(|key| self.tabs.iter().find(|tab| tab.key() == *key));
+        selected
+            .or_else(|| {
+                self.tabs
+                    .iter()
+                    .filter_map(|tab| tab.playing_since.map(|since| (since, tab)))
+                    .max_by_key(|(since, _)| *since)
+                    .map(|(_, tab)| tab)
+            })
+            .or_else(|| self.tabs.iter().max_by_key(|tab| tab.reported_at))
+    }
+
+    /// Queues a Media Session action for a tab.
+    fn send(
+        &self,
+        tab: &Tab,
+        action: &'static str,
+        seek_time: Option<f64>,
+    ) -> Result<(), PlayerError> {
+        let connection = self
+            .connections
+            .get(&tab.connection)
+            .ok_or(PlayerError::PlayerNotRunning)?;
+        connection
+            .commands
+            .send(ServerMessage::Command {
+                tab_id: tab.session.tab_id.clone(),
+                action,
+                seek_time,
+            })
+            .map_err(|_| PlayerError::PlayerNotRunning)
+    }
+}
+
+/// Shared bridge state, with the lock helper used by every thread.
+struct Bridge {
+    state: Mutex<BridgeState>,
+    token: String, // Secret the extension must send in `hello`
+}
+
+impl Bridge {
+    /// Creates the state of a bridge accepting extensions that send `token`.
+    fn new(token: String) -> Self {
+        Self {
+            state: Mutex::default(),
+            token,
+        }
+    }
+
+    /// Returns whether `token` is the configured secret, comparing in constant time.
+    fn accepts(&self, token: Option<&str>) -> bool {
+        let token = token.unwrap_or_default().as_bytes();
+        let expected = self.token.as_bytes();
+        token.len() == expected.len()
+            && token
+                .iter()
+                .zip(expected)
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+
+    /// Locks the bridge state, recovering from a poisoned lock.
+    fn state(&self) -> MutexGuard<'_, BridgeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Calls the change listener, if the status thread registered one.
+    fn notify(&self) {
+        let listener = self.state().listener.clone();
+        if let Some(listener) = listener {
+            listener();
+        }
+    }
+}
+
+/// Backend following the media sessions of browser tabs (YouTube, SoundCloud,
+/// Bandcamp...) through the companion extension.
+///
+/// The bridge listens on the loopback interface only and refuses web pages; extensions
+/// and local clients must send the configured token in `hello`, as any of them can
+/// reach the port. Each tab is listed as a device, so transferring playback picks the
+/// tab to follow. The protocol is described in the README.
+pub struct BrowserPlayer {
+    bridge: Arc<Bridge>, // State shared with the connections
+    port: u16,           // Port the bridge listens on
+}
+
+impl BrowserPlayer {
+    /// Starts the bridge on the configured port, `None` if no token is configured or
+    /// the port cannot be bound.
+    pub fn new(config: &BrowserConfig) -> Option<Self> {
+        let token = match config.token.as_deref().filter(|token| !token.is_empty()) {
+            Some(token) => token.to_string(),
+            None => {
+                log::warn!("The browser bridge needs a token shared with the extension");
+                return None;
+            }
+        };
+        let listener = match TcpListener::bind(("127.0.0.1", config.port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::warn!(
+                    "Failed to listen on port {} for the browser bridge: {}",
+                    config.port,
+                    e
+                );
+                return None;
+            }
+        };
+        let port = listener
+            .local_addr()
+            .map(|addr| addr.port())
+            .unwrap_or(config.port);
+        log::info!("Browser bridge listening on ws://127.0.0.1:{}", port);
+
+        let bridge = Arc::new(Bridge::new(token));
+        let accepting = bridge.clone();
+        thread::spawn(move || {
+            let mut next_id = 1;
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::debug!("Failed to accept a browser bridge connection: {}", e);
+                        continue;
+                    }
+                };
+                let id = next_id;
+                next_id += 1;
+                let bridge = accepting.clone();
+                thread::spawn(move || {
+                    if let Err(e) = serve(&bridge, id, stream) {
+                        log::debug!("Browser bridge connection {} failed: {}", id, e);
+                    }
+                    disconnect(&bridge, id);
+                });
+            }
+        });
+
+        Some(Self { bridge, port })
+    }
+
+    /// Returns the port the bridge listens on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Runs a Media Session action in the followed tab, if the page handles it.
+    fn action(&self, action: &'static str, seek_time: Option<f64>) -> Result<(), PlayerError> {
+        let state = self.bridge.state();
+        let tab = state.current().ok_or(PlayerError::PlayerNotRunning)?;
+        // The extension plays, pauses and seeks the media element itself when the page
+        // does not handle these actions, but only the page knows its next track
+        let needs_page = matches!(action, "nexttrack" | "previoustrack");
+        if needs_page && !tab.session.actions.iter().any(|handled| handled == action) {
+            return Err(PlayerError::unsupported(format!("{} on this page", action)));
+        }
+        state.send(tab, action, seek_time)
+    }
+}
+
+impl MediaPlayer for BrowserPlayer {
+    fn name(&self) -> &str {
+        "Browser"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: false,
+            can_shuffle: false,
+            can_repeat: false,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: true,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        let state = self.bridge.state();
+        let tab = state.current().ok_or(PlayerError::PlayerNotRunning)?;
+        let session = &tab.session;
+        let browser = state
+            .connections
+            .get(&tab.connection)
+            .and_then(|connection| connection.browser.as_deref());
+
+        let duration = session
+            .duration
+            .filter(|duration| duration.is_finite() && *duration > 0.0);
+        // Sessions only report their position when it jumps, so it is advanced here
+        let position = session.position.map(|position| {
+            let elapsed = match tab.is_playing() {
+                true => {
+                    tab.reported_at.elapsed().as_secs_f64() * session.playback_rate.unwrap_or(1.0)
+                }
+                false => 0.0,
+            };
+            let position = (position + elapsed).max(0.0);
+            duration.map_or(position, |duration| position.min(duration))
+        });
+        let player_state = match session.playback_state.as_str() {
+            "playing" => "playing",
+            "paused" => "paused",
+            _ => "stopped",
+        };
+
+        Ok(SpotifyStatus {
+            track_name: session.title.clone(),
+            artist_name: session.artist.clone(),
+            metadata: TrackMetadata {
+                track_id: Some(format!(
+                    "{}/{}",
+                    tab.key(),
+                    session.title.as_deref().unwrap_or_default()
+                )),
+                uri: None,
+                album_name: session.album.clone(),
+                ..TrackMetadata::default()
+            },
+            track_volume: None,
+            position,
+            track_duration: duration,
+            album_cover: session.artwork.clone(),
+            player_state: Some(player_state.to_string()),
+            shuffle: None,
+            repeat_mode: None,
+            is_saved: None,
+            device: Some(tab_device(tab, browser, true)),
+            error: None,
+        })
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        let playing = self.bridge.state().current().is_some_and(Tab::is_playing);
+        self.action(if playing { "pause" } else { "play" }, None)
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.action("nexttrack", None)
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.action("previoustrack", None)
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        self.action("seekto", Some(position.max(0.0)))
+    }
+
+    fn devices(&self) -> Result<Vec<PlaybackDevice>, PlayerError> {
+        let state = self.bridge.state();
+        let current = state.current().map(Tab::key);
+        Ok(state
+            .tabs
+            .iter()
+            .map(|tab| {
+                let browser = state
+                    .connections
+                    .get(&tab.connection)
+                    .and_then(|connection| connection.browser.as_deref());
+                tab_device(tab, browser, current.as_ref() == Some(&tab.key()))
+            })
+            .collect())
+    }
+
+    fn transfer_playback(&self, device_id: &str, play: bool) -> Result<(), PlayerError> {
+        let mut state = self.bridge.state();
+        let target = state
+            .tabs
+            .iter()
+            .position(|tab| tab.key() == device_id)
+            .ok_or_else(|| PlayerError::invalid_argument(format!("unknown tab {}", device_id)))?;
+
+        if play {
+            // Like a transfer between devices, the music moves from one tab to the other
+            if let Some(current) = state.current().filter(|tab| tab.is_playing()) {
+                if current.key() != device_id {
+                    state.send(current, "pause", None)?;
+                }
+            }
+            state.send(&state.tabs[target], "play", None)?;
+        }
+        state.selected = Some(device_id.to_string());
+        Ok(())
+    }
+
+    fn watch(&self, listener: ChangeListener) -> bool {
+        self.bridge.state().listener = Some(listener);
+        true
+    }
+}
+
+/// Describes a tab as a playback device.
+fn tab_device(tab: &Tab, browser: Option<&str>, is_active: bool) -> PlaybackDevice {
+    let site = tab
+        .session
+        .origin
+        .as_deref()
+        .and_then(|origin| url::Url::parse(origin).ok())
+        .and_then(|origin| origin.host_str().map(str::to_string))
+        .unwrap_or_else(|| format!("Tab {}", tab.session.tab_id));
+    PlaybackDevice {
+        id: Some(tab.key()),
+        name: site,
+        device_type: browser.map_or_else(
+            || "Browser tab".to_string(),
+            |browser| format!("{} tab", browser),
+        ),
+        is_active,
+        volume: None,
+    }
+}
+
+/// Accepts the WebSocket handshake if it comes from an extension or a local client,
+/// which then authenticate with the token.
+#[allow(clippy::result_large_err)] // Signature of the tungstenite handshake callback
+fn check_origin(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    let origin = match request.headers().get("Origin") {
+        Some(origin) => origin.to_str().unwrap_or_default(),
+        // Browsers always send an origin, so this is a local application
+        None => return Ok(response),
+    };
+    let scheme = origin.split("://").next().unwrap_or_default();
+    if EXTENSION_SCHEMES.contains(&scheme) {
+        return Ok(response);
+    }
+
+    log::warn!("Rejected a browser bridge connection from {}", origin);
+    let mut rejection = ErrorResponse::new(Some("only browser extensions may connect".into()));
+    *rejection.status_mut() = StatusCode::FORBIDDEN;
+    Err(rejection)
+}
+
+/// Talks to a connected extension until it disconnects.
+fn serve(bridge: &Bridge, id: u64, stream: TcpStream) -> Result<(), ConnectionError> {
+    stream
+        .set_read_timeout(Some(params::BROWSER_HANDSHAKE_TIMEOUT))
+        .map_err(tungstenite::Error::Io)?;
+    let mut socket = tungstenite::accept_hdr(stream, check_origin).map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e,
+        tungstenite::HandshakeError::Interrupted(_) => {
+            io::Error::new(io::ErrorKind::TimedOut, "handshake timed out").into()
+        }
+    })?;
+
+    // Reads wake up regularly to forward the queued commands
+    socket
+        .get_ref()
+        .set_read_timeout(Some(params::BROWSER_COMMAND_INTERVAL))
+        .map_err(tungstenite::Error::Io)?;
+    let (sender, commands) = mpsc::channel();
+    let mut sender = Some(sender);
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if !receive(bridge, id, &mut socket, &mut sender, &text)? {
+                    socket.close(None)?;
+                }
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+
+        while let Ok(message) = commands.try_recv() {
+            send(&mut socket, &message)?;
+        }
+        // Also sends the pongs answering the extension's pings
+        match socket.flush() {
+            Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {}
+            result => result?,
+        }
+    }
+}
+
+/// Handles a message from the extension; returns `false` if the connection must close.
+///
+/// `sender` is taken when the handshake completes and the connection gets registered.
+fn receive(
+    bridge: &Bridge,
+    id: u64,
+    socket: &mut WebSocket<TcpStream>,
+    sender: &mut Option<Sender<ServerMessage>>,
+    text: &str,
+) -> Result<bool, ConnectionError> {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => {
+            let message = format!("invalid message: {}", e);
+            send(socket, &ServerMessage::Error { message })?;
+            return Ok(true);
+        }
+    };
+
+    let registered = sender.is_none();
+    match message {
+        ClientMessage::Hello {
+            version,
+            token,
+            browser,
+        } => {
+            if version != PROTOCOL_VERSION {
+                let message = format!(
+                    "unsupported protocol version {}, expected {}",
+                    version, PROTOCOL_VERSION
+                );
+                send(socket, &ServerMessage::Error { message })?;
+                return Ok(false);
+            }
+            if !bridge.accepts(token.as_deref()) {
+                log::warn!("Rejected a browser bridge connection with a wrong token");
+                let message = "invalid token".to_string();
+                send(socket, &ServerMessage::Error { message })?;
+                return Ok(false);
+            }
+            if let Some(commands) = sender.take() {
+                let connection = Connection { browser, commands };
+                bridge.state().connections.insert(id, connection);
+            }
+            send(
+                socket,
+                &ServerMessage::Welcome {
+                    version: PROTOCOL_VERSION,
+                },
+            )?;
+        }
+        _ if !registered => {
+            let message = "expected hello first".to_string();
+            send(socket, &ServerMessage::Error { message })?;
+            return Ok(false);
+        }
+        ClientMessage::Session(session) => {
+            update_tab(bridge, id, *session);
+            bridge.notify();
+        }
+        ClientMessage::Closed { tab_id } => {
+            let mut state = bridge.state();
+            state
+                .tabs
+                .retain(|tab| tab.connection != id || tab.session.tab_id != tab_id);
+            drop(state);
+            bridge.notify();
+        }
+    }
+    Ok(true)
+}
+
+/// Records the media session reported for a tab.
+fn update_tab(bridge: &Bridge, id: u64, session: TabSession) {
+    let now = Instant::now();
+    let playing = session.playback_state == "playing";
+    let mut state = bridge.state();
+    let existing = state
+        .tabs
+        .iter()
+        .position(|tab| tab.connection == id && tab.session.tab_id == session.tab_id);
+    match existing {
+        Some(index) => {
+            let tab = &mut state.tabs[index];
+            tab.playing_since = match (playing, tab.playing_since) {
+                (true, Some(since)) => Some(since),
+                (true, None) => Some(now),
+                (false, _) => None,
+            };
+            tab.session = session;
+            tab.reported_at = now;
+        }
+        None => state.tabs.push(Tab {
+            connection: id,
+            session,
+            reported_at: now,
+            playing_since: playing.then_some(now),
+        }),
+    }
+}
+
+/// Forgets a disconnected extension and its tabs.
+fn disconnect(bridge: &Bridge, id: u64) {
+    let mut state = bridge.state();
+    let known = state.connections.remove(&id).is_some();
+    state.tabs.retain(|tab| tab.connection != id);
+    drop(state);
+    if known {
+        bridge.notify();
+    }
+}
+
+/// Sends a message to the extension.
+fn send(socket: &mut WebSocket<TcpStream>, message: &ServerMessage) -> Result<(), ConnectionError> {
+    let text = serde_json::to_string(message)
+        .map_err(|e| tungstenite::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    Ok(socket.send(Message::Text(text))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use serde_json::{json, Value};
+    use tungstenite::client::IntoClientRequest;
+
+    use super::*;
+
+    const TOKEN: &str = "s3cret";
+
+    /// Starts a bridge on a free port.
+    fn bridge() -> BrowserPlayer {
+        BrowserPlayer::new(&BrowserConfig {
+            port: 0,
+            token: Some(TOKEN.to_string()),
+        })
+        .unwrap()
+    }
+
+    /// Opens a WebSocket to the bridge, sending `origin` if given.
+    fn connect(
+        player: &BrowserPlayer,
+        origin: Option<&str>,
+    ) -> Result<WebSocket<TcpStream>, ConnectionError> {
+        let stream = TcpStream::connect(("127.0.0.1", player.port())).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut request = format!("ws://127.0.0.1:{}/", player.port())
+            .into_client_request()
+            .unwrap();
+        if let Some(origin) = origin {
+            request
+                .headers_mut()
+                .insert("Origin", origin.parse().unwrap());
+        }
+        tungstenite::client(request, stream)
+            .map(|(socket, _)| socket)
+            .map_err(|e| match e {
+                tungstenite::HandshakeError::Failure(e) => Box::new(e),
+                tungstenite::HandshakeError::Interrupted(_) => panic!("handshake interrupted"),
+            })
+    }
+
+    /// Sends a JSON message to the bridge.
+    fn write(socket: &mut WebSocket<TcpStream>, message: Value) {
+        socket.send(Message::Text(message.to_string())).unwrap();
+    }
+
+    /// Reads the next JSON message from the bridge.
+    fn read(socket: &mut WebSocket<TcpStream>) -> Value {
+        loop {
+            if let Message::Text(text) = socket.read().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    /// Returns whether the bridge closed the connection.
+    fn is_closed(socket: &mut WebSocket<TcpStream>) -> bool {
+        matches!(socket.read(), Ok(Message::Close(_)) | Err(_))
+    }
+
+    /// Connects as an extension that completed the handshake.
+    fn extension(player: &BrowserPlayer) -> WebSocket<TcpStream> {
+        let mut socket = connect(player, Some("moz-extension://4f1c2d3e")).unwrap();
+        write(
+            &mut socket,
+            json!({"type": "hello", "version": PROTOCOL_VERSION, "token": TOKEN, "browser": "Firefox"}),
+        );
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "welcome", "version": PROTOCOL_VERSION})
+        );
+        socket
+    }
+
+    /// Returns a `session` message for a tab of `origin`.
+    fn session(tab_id: &str, origin: &str, state: &str, actions: &[&str]) -> Value {
+        json!({
+            "type": "session", "tab_id": tab_id, "origin": origin, "title": format!("Title {}", tab_id),
+            "artist": "Artist", "artwork": "https://example.com/cover.jpg", "playback_state": state,
+            "position": 10.0, "duration": 200.0, "playback_rate": 1.0, "actions": actions,
+        })
+    }
+
+    /// Waits until the bridge handled the messages sent so far.
+    fn settle() {
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    #[test]
+    fn bridge_needs_a_token() {
+        assert!(BrowserPlayer::new(&BrowserConfig {
+            port: 0,
+            token: None
+        })
+        .is_none());
+        assert!(BrowserPlayer::new(&BrowserConfig {
+            port: 0,
+            token: Some(String::new())
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn web_pages_are_refused() {
+        let player = bridge();
+
+        let error = connect(&player, Some("https://example.com")).unwrap_err();
+
+        assert!(
+            matches!(*error, tungstenite::Error::Http(ref response) if response.status() == StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn handshake_answers_the_version() {
+        let player = bridge();
+
+        extension(&player);
+    }
+
+    #[test]
+    fn wrong_token_is_refused() {
+        let player = bridge();
+
+        for token in [json!("guess"), json!(null)] {
+            // Local clients send no origin and authenticate the same way
+            let mut socket = connect(&player, None).unwrap();
+            write(
+                &mut socket,
+                json!({"type": "hello", "version": PROTOCOL_VERSION, "token": token}),
+            );
+            assert_eq!(
+                read(&mut socket),
+                json!({"type": "error", "message": "invalid token"})
+            );
+            assert!(is_closed(&mut socket));
+        }
+        assert!(player.bridge.state().connections.is_empty());
+    }
+
+    #[test]
+    fn unsupported_version_is_refused() {
+        let player = bridge();
+        let mut socket = connect(&player, Some("chrome-extension://abcdef")).unwrap();
+
+        write(
+            &mut socket,
+            json!({"type": "hello", "version": 1, "token": TOKEN}),
+        );
+
+        assert_eq!(
+            read(&mut socket)["message"],
+            "unsupported protocol version 1, expected 2"
+        );
+        assert!(is_closed(&mut socket));
+    }
+
+    #[test]
+    fn messages_before_hello_are_refused() {
+        let player = bridge();
+        let mut socket = connect(&player, Some("chrome-extension://abcdef")).unwrap();
+
+        write(
+            &mut socket,
+            session("1", "https://example.com", "playing", &[]),
+        );
+
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "error", "message": "expected hello first"})
+        );
+        assert!(is_closed(&mut socket));
+        assert!(player.bridge.state().tabs.is_empty());
+    }
+
+    #[test]
+    fn invalid_message_is_answered_with_an_error() {
+        let player = bridge();
+        let mut socket = extension(&player);
+
+        write(&mut socket, json!({"type": "bogus"}));
+
+        assert_eq!(read(&mut socket)["type"], "error");
+        // The connection stays open
+        write(
+            &mut socket,
+            session("1", "https://example.com", "paused", &[]),
+        );
+        settle();
+        assert!(player.status().is_ok());
+    }
+
+    #[test]
+    fn sessions_are_reported_as_status() {
+        let player = bridge();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        player.watch(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let mut socket = extension(&player);
+
+        write(
+            &mut socket,
+            session(
+                "1",
+                "https://www.youtube.com",
+                "playing",
+                &["play", "pause"],
+            ),
+        );
+        write(
+            &mut socket,
+            session("2", "https://x.bandcamp.com", "paused", &[]),
+        );
+        settle();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let status = player.status().unwrap();
+        assert_eq!(status.track_name.as_deref(), Some("Title 1"));
+        assert_eq!(status.artist_name.as_deref(), Some("Artist"));
+        assert_eq!(
+            status.album_cover.as_deref(),
+            Some("https://example.com/cover.jpg")
+        );
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.track_duration, Some(200.0));
+        // Advanced while playing
+        assert!(status.position.unwrap() > 10.0);
+        let device = status.device.unwrap();
+        assert_eq!(device.name, "www.youtube.com");
+        assert_eq!(device.device_type, "Firefox tab");
+        assert_eq!(player.devices().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn closed_tabs_and_connections_are_forgotten() {
+        let player = bridge();
+        let mut socket = extension(&player);
+        write(
+            &mut socket,
+            session("1", "https://www.youtube.com", "paused", &[]),
+        );
+        write(
+            &mut socket,
+            session("2", "https://x.bandcamp.com", "playing", &[]),
+        );
+
+        write(&mut socket, json!({"type": "closed", "tab_id": "2"}));
+        settle();
+        assert_eq!(
+            player.status().unwrap().track_name.as_deref(),
+            Some("Title 1")
+        );
+
+        socket.close(None).unwrap();
+        while !is_closed(&mut socket) {}
+        settle();
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn commands_are_sent_to_the_followed_tab() {
+        let player = bridge();
+        let mut socket = extension(&player);
+        write(
+            &mut socket,
+            session(
+                "1",
+                "https://www.youtube.com",
+                "playing",
+                &["play", "pause"],
+            ),
+        );
+        write(
+            &mut socket,
+            session(
+                "2",
+                "https://x.bandcamp.com",
+                "paused",
+                &["nexttrack", "previoustrack"],
+            ),
+        );
+        settle();
+
+        player.toggle_playback().unwrap();
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "command", "tab_id": "1", "action": "pause"})
+        );
+        player.set_position(30.0).unwrap();
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "command", "tab_id": "1", "action": "seekto", "seek_time": 30.0})
+        );
+        assert!(matches!(
+            player.next_track(),
+            Err(PlayerError::Unsupported { .. })
+        ));
+
+        // Moving to the other tab pauses the playing one
+        let target = player.devices().unwrap()[1].id.clone().unwrap();
+        player.transfer_playback(&target, true).unwrap();
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "command", "tab_id": "1", "action": "pause"})
+        );
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "command", "tab_id": "2", "action": "play"})
+        );
+        player.next_track().unwrap();
+        assert_eq!(
+            read(&mut socket),
+            json!({"type": "command", "tab_id": "2", "action": "nexttrack"})
+        );
+        assert!(matches!(
+            player.transfer_playback("unknown", false),
+            Err(PlayerError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// Error returned by player backends and the player commands.
+///
+/// Serialized with a `kind` tag (e.g. `{"kind":"script_failed","stderr":"..."}`)
+/// so the frontend can discriminate on it.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlayerError {
+    /// The player application is installed but not running.
+    PlayerNotRunning,
+    /// The player application could not be found on this machine.
+    PlayerNotInstalled,
+    /// The script controlling the player exited with an error.
+    ScriptFailed { stderr: String },
+    /// The player did not answer in time.
+    Timeout,
+    /// The player answered with something that could not be understood.
+    ParseError { message: String },
+    /// The backend needs the user to log in (again) before it can be used.
+    NotAuthenticated,
+    /// The command was given an argument it cannot accept.
+    InvalidArgument { message: String },
+    /// The backend does not support the requested operation.
+    Unsupported { operation: String },
+    /// Communication with the player failed for another reason.
+    Backend { message: String },
+}
+
+impl PlayerError {
+    /// Creates an `Unsupported` error for the given operation.
+    pub fn unsupported(operation: impl Into<String>) -> Self {
+        Self::Unsupported {
+            operation: operation.into(),
+        }
+    }
+
+    /// Creates a `ParseError` with the given message.
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::ParseError {
+            message: message.into(),
+        }
+    }
+
+    /// Creates an `InvalidArgument` error with the given message.
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::InvalidArgument {
+            message: message.into(),
+        }
+    }
+
+    /// Creates a `Backend` error with the given message.
+    pub fn backend(message: impl fmt::Display) -> Self {
+        Self::Backend {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PlayerNotRunning => write!(f, "Player is not running"),
+            Self::PlayerNotInstalled => write!(f, "Player is not installed"),
+            Self::ScriptFailed { stderr } => write!(f, "Script error: {}", stderr),
+            Self::Timeout => write!(f, "Player did not respond in time"),
+            Self::NotAuthenticated => write!(f, "Not logged in to the player"),
+            Self::ParseError { message } => write!(f, "Failed to parse player output: {}", message),
+            Self::InvalidArgument { message } => write!(f, "Invalid argument: {}", message),
+            Self::Unsupported { operation } => {
+                write!(f, "Operation not supported by this player: {}", operation)
+            }
+            Self::Backend { message } => write!(f, "Player error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_tagged_with_their_kind() {
+        assert_eq!(
+            serde_json::to_value(PlayerError::PlayerNotRunning).unwrap(),
+            serde_json::json!({ "kind": "player_not_running" })
+        );
+        assert_eq!(
+            serde_json::to_value(PlayerError::ScriptFailed {
+                stderr: "boom".to_string()
+            })
+            .unwrap(),
+            serde_json::json!({ "kind": "script_failed", "stderr": "boom" })
+        );
+    }
+}
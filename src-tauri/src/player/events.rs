@@ -0,0 +1,437 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::params::{RepeatMode, SpotifyStatus, TrackMetadata, SEEK_DRIFT_TOLERANCE};
+
+/// A transition detected between two consecutive player statuses.
+///
+/// Each variant is emitted to the window as its own event (see `PlayerEvent::name`),
+/// with the variant fields as payload.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PlayerEvent {
+    /// A different track started (`track-changed`).
+    TrackChanged {
+        track_name: Option<String>,
+        artist_name: Option<String>,
+        track_duration: Option<f64>,
+        album_cover: Option<String>,
+        metadata: TrackMetadata,
+    },
+    /// The player started, paused or stopped (`playback-state-changed`).
+    PlaybackStateChanged { player_state: Option<String> },
+    /// The volume level changed (`volume-changed`).
+    VolumeChanged { volume: Option<u32> },
+    /// The position jumped further than playback alone explains (`seeked`).
+    Seeked { position: f64 },
+    /// Shuffle was turned on or off (`shuffle-changed`).
+    ShuffleChanged { shuffle: Option<bool> },
+    /// The repeat mode changed (`repeat-changed`).
+    RepeatChanged { repeat_mode: Option<RepeatMode> },
+}
+
+impl PlayerEvent {
+    /// Returns the name of the Tauri event this transition is emitted as.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TrackChanged { .. } => "track-changed",
+            Self::PlaybackStateChanged { .. } => "playback-state-changed",
+            Self::VolumeChanged { .. } => "volume-changed",
+            Self::Seeked { .. } => "seeked",
+            Self::ShuffleChanged { .. } => "shuffle-changed",
+            Self::RepeatChanged { .. } => "repeat-changed",
+        }
+    }
+}
+
+/// Remembers the previous status so consecutive statuses can be diffed into events.
+#[derive(Debug, Default)]
+pub struct StatusDiffer {
+    previous: Option<(SpotifyStatus, Instant)>, // Last status seen and when it was sampled
+}
+
+impl StatusDiffer {
+    /// Creates a differ with no previous status.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compares the status with the previous one and returns the transitions between them.
+    ///
+    /// The first status after creation or `reset` reports every field as changed.
+    pub fn update(&mut self, status: &SpotifyStatus) -> Vec<PlayerEvent> {
+        let now = Instant::now();
+        let events = match &self.previous {
+            Some((previous, sampled_at)) => {
+                diff_statuses(previous, status, now.duration_since(*sampled_at))
+            }
+            None => initial_events(status),
+        };
+
+        self.previous = Some((status.clone(), now));
+        events
+    }
+
+    /// Forgets the previous status, e.g. after the player went away.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+/// Returns the transitions between two statuses sampled `elapsed` apart.
+pub fn diff_statuses(
+    previous: &SpotifyStatus,
+    current: &SpotifyStatus,
+    elapsed: Duration,
+) -> Vec<PlayerEvent> {
+    let mut events = Vec::new();
+    let same_track = is_same_track(previous, current);
+
+    if !same_track {
+        events.push(track_changed(current));
+    }
+    if previous.player_state != current.player_state {
+        events.push(PlayerEvent::PlaybackStateChanged {
+            player_state: current.player_state.clone(),
+        });
+    }
+    if previous.track_volume != current.track_volume {
+        events.push(PlayerEvent::VolumeChanged {
+            volume: current.track_volume,
+        });
+    }
+    if previous.shuffle != current.shuffle {
+        events.push(PlayerEvent::ShuffleChanged {
+            shuffle: current.shuffle,
+        });
+    }
+    if previous.repeat_mode != current.repeat_mode {
+        events.push(PlayerEvent::RepeatChanged {
+            repeat_mode: current.repeat_mode,
+        });
+    }
+
+    // A new track always starts at a different position, so only seeks within a track count
+    if let (true, Some(before), Some(after)) = (same_track, previous.position, current.position) {
+        let expected = if previous.player_state.as_deref() == Some("playing") {
+            before + elapsed.as_secs_f64()
+        } else {
+            before
+        };
+        if (after - expected).abs() > SEEK_DRIFT_TOLERANCE {
+            events.push(PlayerEvent::Seeked { position: after });
+        }
+    }
+
+    events
+}
+
+/// Returns the events describing a status seen for the first time.
+fn initial_events(status: &SpotifyStatus) -> Vec<PlayerEvent> {
+    vec![
+        track_changed(status),
+        PlayerEvent::PlaybackStateChanged {
+            player_state: status.player_state.clone(),
+        },
+        PlayerEvent::VolumeChanged {
+            volume: status.track_volume,
+        },
+        PlayerEvent::ShuffleChanged {
+            shuffle: status.shuffle,
+        },
+        PlayerEvent::RepeatChanged {
+            repeat_mode: status.repeat_mode,
+        },
+    ]
+}
+
+/// Builds the `TrackChanged` event for the track in the status.
+fn track_changed(status: &SpotifyStatus) -> PlayerEvent {
+    PlayerEvent::TrackChanged {
+        track_name: status.track_name.clone(),
+        artist_name: status.artist_name.clone(),
+        track_duration: status.track_duration,
+        album_cover: status.album_cover.clone(),
+        metadata: status.metadata.clone(),
+    }
+}
+
+/// Returns whether both statuses describe the same track, by id when the player
+/// reports one and by name, artist, album and duration otherwise.
+fn is_same_track(previous: &SpotifyStatus, current: &SpotifyStatus) -> bool {
+    if let (Some(before), Some(after)) = (&previous.metadata.track_id, &current.metadata.track_id) {
+        return before == after;
+    }
+    previous.track_name == current.track_name
+        && previous.artist_name == current.artist_name
+        && previous.metadata.album_name == current.metadata.album_name
+        && previous.track_duration == current.track_duration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Status of a track playing at the given position.
+    fn playing(position: f64) -> SpotifyStatus {
+        SpotifyStatus {
+            track_name: Some("Song".to_string()),
+            artist_name: Some("Artist".to_string()),
+            track_volume: Some(50),
+            shuffle: Some(false),
+            repeat_mode: Some(RepeatMode::Off),
+            position: Some(position),
+            track_duration: Some(200.0),
+            player_state: Some("playing".to_string()),
+            ..SpotifyStatus::default()
+        }
+    }
+
+    /// Status of the same track paused at the given position.
+    fn paused(position: f64) -> SpotifyStatus {
+        SpotifyStatus {
+            player_state: Some("paused".to_string()),
+            ..playing(position)
+        }
+    }
+
+    /// Returns the names of the events between two statuses sampled `elapsed` seconds apart.
+    fn names(previous: &SpotifyStatus, current: &SpotifyStatus, elapsed: f64) -> Vec<&'static str> {
+        diff_statuses(previous, current, Duration::from_secs_f64(elapsed))
+            .iter()
+            .map(PlayerEvent::name)
+            .collect()
+    }
+
+    #[test]
+    fn identical_statuses_have_no_events() {
+        assert!(names(&paused(10.0), &paused(10.0), 0.8).is_empty());
+        assert!(names(&SpotifyStatus::default(), &SpotifyStatus::default(), 0.8).is_empty());
+    }
+
+    #[test]
+    fn first_status_reports_everything() {
+        let mut differ = StatusDiffer::new();
+
+        assert_eq!(
+            differ.update(&playing(10.0)),
+            vec![
+                track_changed(&playing(10.0)),
+                PlayerEvent::PlaybackStateChanged {
+                    player_state: Some("playing".to_string())
+                },
+                PlayerEvent::VolumeChanged { volume: Some(50) },
+                PlayerEvent::ShuffleChanged {
+                    shuffle: Some(false)
+                },
+                PlayerEvent::RepeatChanged {
+                    repeat_mode: Some(RepeatMode::Off)
+                },
+            ]
+        );
+        assert!(differ.update(&playing(10.0)).is_empty());
+
+        differ.reset();
+        assert_eq!(differ.update(&playing(10.0)).len(), 5);
+    }
+
+    #[test]
+    fn track_changes_are_detected() {
+        let other_name = SpotifyStatus {
+            track_name: Some("Other".to_string()),
+            ..playing(0.0)
+        };
+        let other_artist = SpotifyStatus {
+            artist_name: Some("Other".to_string()),
+            ..playing(0.0)
+        };
+        let other_album = SpotifyStatus {
+            metadata: TrackMetadata {
+                album_name: Some("Other".to_string()),
+                ..TrackMetadata::default()
+            },
+            ..playing(0.0)
+        };
+        let other_duration = SpotifyStatus {
+            track_duration: Some(100.0),
+            ..playing(0.0)
+        };
+
+        for current in [other_name, other_artist, other_album, other_duration] {
+            let events = diff_statuses(&playing(150.0), &current, Duration::from_millis(800));
+            // The position jump that comes with a new track is not a seek
+            assert_eq!(events, vec![track_changed(&current)]);
+        }
+    }
+
+    /// Status of a track with the given id and title playing at 10 seconds.
+    fn with_id(track_id: Option<&str>, track_name: &str) -> SpotifyStatus {
+        SpotifyStatus {
+            track_name: Some(track_name.to_string()),
+            metadata: TrackMetadata {
+                track_id: track_id.map(str::to_string),
+                ..TrackMetadata::default()
+            },
+            ..playing(10.0)
+        }
+    }
+
+    #[test]
+    fn tracks_with_ids_are_compared_by_id() {
+        // (previous, current, changed)
+        let cases = [
+            // A renamed title, e.g. a live-updated stream title, is the same track
+            (
+                with_id(Some("1"), "Song"),
+                with_id(Some("1"), "Renamed"),
+                false,
+            ),
+            // The same song from another release is another track
+            (with_id(Some("1"), "Song"), with_id(Some("2"), "Song"), true),
+            // Without an id on both sides the fields decide
+            (with_id(None, "Song"), with_id(Some("1"), "Song"), false),
+            (with_id(Some("1"), "Song"), with_id(None, "Other"), true),
+        ];
+
+        for (previous, current, changed) in cases {
+            let events = names(&previous, &current, 0.0);
+            assert_eq!(
+                events.contains(&"track-changed"),
+                changed,
+                "{:?} {:?} -> {:?} {:?}",
+                previous.metadata.track_id,
+                previous.track_name,
+                current.metadata.track_id,
+                current.track_name
+            );
+        }
+    }
+
+    #[test]
+    fn playback_state_changes_are_detected() {
+        let stopped = SpotifyStatus {
+            player_state: Some("stopped".to_string()),
+            ..paused(10.0)
+        };
+        let cases = [
+            (playing(10.0), paused(10.0)),
+            (paused(10.0), playing(10.0)),
+            (paused(10.0), stopped),
+        ];
+
+        for (previous, current) in cases {
+            assert_eq!(
+                diff_statuses(&previous, &current, Duration::ZERO),
+                vec![PlayerEvent::PlaybackStateChanged {
+                    player_state: current.player_state.clone()
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn volume_changes_are_detected() {
+        let louder = SpotifyStatus {
+            track_volume: Some(80),
+            ..paused(10.0)
+        };
+        let unknown = SpotifyStatus {
+            track_volume: None,
+            ..paused(10.0)
+        };
+
+        assert_eq!(
+            diff_statuses(&paused(10.0), &louder, Duration::ZERO),
+            vec![PlayerEvent::VolumeChanged { volume: Some(80) }]
+        );
+        assert_eq!(
+            diff_statuses(&paused(10.0), &unknown, Duration::ZERO),
+            vec![PlayerEvent::VolumeChanged { volume: None }]
+        );
+    }
+
+    #[test]
+    fn shuffle_and_repeat_changes_are_detected() {
+        let shuffled = SpotifyStatus {
+            shuffle: Some(true),
+            ..paused(10.0)
+        };
+        let repeating = SpotifyStatus {
+            repeat_mode: Some(RepeatMode::Track),
+            ..paused(10.0)
+        };
+        let unknown = SpotifyStatus {
+            shuffle: None,
+            repeat_mode: None,
+            ..paused(10.0)
+        };
+
+        assert_eq!(
+            diff_statuses(&paused(10.0), &shuffled, Duration::ZERO),
+            vec![PlayerEvent::ShuffleChanged {
+                shuffle: Some(true)
+            }]
+        );
+        assert_eq!(
+            diff_statuses(&paused(10.0), &repeating, Duration::ZERO),
+            vec![PlayerEvent::RepeatChanged {
+                repeat_mode: Some(RepeatMode::Track)
+            }]
+        );
+        assert_eq!(
+            diff_statuses(&paused(10.0), &unknown, Duration::ZERO),
+            vec![
+                PlayerEvent::ShuffleChanged { shuffle: None },
+                PlayerEvent::RepeatChanged { repeat_mode: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn seeks_are_position_jumps_beyond_the_drift_tolerance() {
+        let within = SEEK_DRIFT_TOLERANCE - 0.1;
+        let beyond = SEEK_DRIFT_TOLERANCE + 0.1;
+        // (previous, current, elapsed seconds, seeked)
+        let cases = [
+            // Playing: the position is expected to advance by the elapsed time
+            (playing(10.0), playing(15.0), 5.0, false),
+            (playing(10.0), playing(15.0 + within), 5.0, false),
+            (playing(10.0), playing(15.0 - within), 5.0, false),
+            (playing(10.0), playing(15.0 + beyond), 5.0, true),
+            (playing(10.0), playing(15.0 - beyond), 5.0, true),
+            (playing(10.0), playing(10.0), 5.0, true),
+            // Paused: the position is expected to stay put
+            (paused(10.0), paused(10.0 + within), 5.0, false),
+            (paused(10.0), paused(10.0 + beyond), 5.0, true),
+            (paused(10.0), paused(15.0), 5.0, true),
+        ];
+
+        for (previous, current, elapsed, seeked) in cases {
+            let events = names(&previous, &current, elapsed);
+            assert_eq!(
+                events.contains(&"seeked"),
+                seeked,
+                "{:?} -> {:?} after {}s",
+                previous.position,
+                current.position,
+                elapsed
+            );
+        }
+        assert_eq!(
+            diff_statuses(&paused(10.0), &paused(60.0), Duration::ZERO),
+            vec![PlayerEvent::Seeked { position: 60.0 }]
+        );
+    }
+
+    #[test]
+    fn unknown_positions_are_not_seeks() {
+        let unknown = SpotifyStatus {
+            position: None,
+            ..paused(0.0)
+        };
+
+        assert!(names(&paused(10.0), &unknown, 0.0).is_empty());
+        assert!(names(&unknown, &paused(10.0), 0.0).is_empty());
+    }
+}
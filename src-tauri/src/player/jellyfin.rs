@@ -0,0 +1,577 @@
+use serde::Deserialize;
+
+use super::{MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::config::JellyfinConfig;
+use crate::params::{self, PlaybackDevice, RepeatMode, SpotifyStatus, TrackMetadata};
+
+/// Number of Jellyfin ticks (100 ns) in a second.
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+
+/// Session of a Jellyfin client, as listed by `GET /Sessions`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Session {
+    id: String,
+    user_name: Option<String>,
+    client: Option<String>,      // Name of the client application
+    device_name: Option<String>, // Name of the device the client runs on
+    #[serde(default)]
+    supports_remote_control: bool,
+    now_playing_item: Option<Item>,
+    play_state: Option<PlayState>,
+}
+
+/// Item played by a session.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Item {
+    id: String,
+    name: Option<String>,
+    media_type: Option<String>, // `Audio` for music
+    #[serde(default)]
+    artists: Vec<String>,
+    album: Option<String>,
+    album_artist: Option<String>,
+    album_id: Option<String>,
+    album_primary_image_tag: Option<String>,
+    run_time_ticks: Option<u64>,
+    index_number: Option<u32>,        // Track number
+    parent_index_number: Option<u32>, // Disc number
+    image_tags: Option<ImageTags>,
+}
+
+/// Image tags of an item, used to address its images.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ImageTags {
+    primary: Option<String>,
+}
+
+/// Playback state of a session.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlayState {
+    position_ticks: Option<u64>,
+    #[serde(default)]
+    can_seek: bool,
+    #[serde(default)]
+    is_paused: bool,
+    #[serde(default)]
+    is_muted: bool,
+    volume_level: Option<u32>,    // 0–100
+    repeat_mode: Option<String>,  // `RepeatNone`, `RepeatAll` or `RepeatOne`
+    shuffle_mode: Option<String>, // `Sorted` or `Shuffle`
+}
+
+/// Backend following what a user plays on any Jellyfin client, and controlling
+/// the clients that accept remote control.
+pub struct JellyfinPlayer {
+    base_url: String,         // URL of the server
+    authorization: String,    // Authorization header carrying the API key
+    username: Option<String>, // User whose sessions are followed, every user if `None`
+    agent: ureq::Agent,       // HTTP client for the server
+}
+
+impl JellyfinPlayer {
+    /// Creates a backend for the configured server, `None` if it is not fully configured.
+    pub fn new(config: &JellyfinConfig) -> Option<Self> {
+        let (url, api_key) = match (&config.url, &config.api_key) {
+            (Some(url), Some(api_key)) => (url, api_key),
+            _ => return None,
+        };
+
+        Some(Self {
+            base_url: url.trim_end_matches('/').to_string(),
+            authorization: format!("MediaBrowser Token=\"{}\"", api_key),
+            username: config.username.clone(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(params::MEDIA_SERVER_TIMEOUT)
+                .build(),
+        })
+    }
+
+    /// Sends a request to the server, with a JSON body if given.
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> Result<ureq::Response, PlayerError> {
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{}", self.base_url, path))
+            .set("Authorization", &self.authorization);
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+
+        let result = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        result.map_err(http_error)
+    }
+
+    /// Returns the session playing music for the user, preferring one that is not
+    /// paused; `PlayerNotRunning` if there is none.
+    fn session(&self) -> Result<Session, PlayerError> {
+        let sessions: Vec<Session> = self
+            .send("GET", "/Sessions", &[], None)?
+            .into_json()
+            .map_err(|e| PlayerError::parse(e.to_string()))?;
+
+        let mut sessions: Vec<Session> = sessions
+            .into_iter()
+            .filter(|session| {
+                session
+                    .now_playing_item
+                    .as_ref()
+                    .is_some_and(|item| item.media_type.as_deref() == Some("Audio"))
+            })
+            .filter(|session| self.username.is_none() || session.user_name == self.username)
+            .collect();
+        let playing = sessions.iter().position(|session| {
+            !session
+                .play_state
+                .as_ref()
+                .is_some_and(|state| state.is_paused)
+        });
+        match playing {
+            Some(index) => Ok(sessions.swap_remove(index)),
+            None => sessions
+                .into_iter()
+                .next()
+                .ok_or(PlayerError::PlayerNotRunning),
+        }
+    }
+
+    /// Returns the session to send a command to, if it accepts remote control.
+    fn controlled_session(&self) -> Result<Session, PlayerError> {
+        let session = self.session()?;
+        if !session.supports_remote_control {
+            return Err(PlayerError::unsupported("remote control"));
+        }
+        Ok(session)
+    }
+
+    /// Sends a playstate command (`PlayPause`, `NextTrack`...) to the session.
+    fn playing_command(&self, command: &str, query: &[(&str, &str)]) -> Result<(), PlayerError> {
+        let session = self.controlled_session()?;
+        let path = format!("/Sessions/{}/Playing/{}", session.id, command);
+        self.send("POST", &path, query, None).map(|_| ())
+    }
+
+    /// Sends a general command (`SetVolume`, `SetRepeatMode`...) to the session.
+    fn general_command(&self, name: &str, argument: (&str, &str)) -> Result<(), PlayerError> {
+        let session = self.controlled_session()?;
+        let path = format!("/Sessions/{}/Command", session.id);
+        let body = serde_json::json!({
+            "Name": name,
+            "Arguments": { argument.0: argument.1 },
+        });
+        self.send("POST", &path, &[], Some(&body)).map(|_| ())
+    }
+
+    /// Returns the URL of the album cover, or of the item image when the album has none.
+    fn cover_url(&self, item: &Item) -> Option<String> {
+        let (id, tag) = match (&item.album_id, &item.album_primary_image_tag) {
+            (Some(album_id), Some(tag)) => (album_id, tag),
+            _ => (&item.id, item.image_tags.as_ref()?.primary.as_ref()?),
+        };
+        Some(format!(
+            "{}/Items/{}/Images/Primary?tag={}&maxHeight={}",
+            self.base_url,
+            id,
+            tag,
+            params::COVER_ART_SIZE
+        ))
+    }
+}
+
+impl MediaPlayer for JellyfinPlayer {
+    fn name(&self) -> &str {
+        "Jellyfin"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        let session = self.session().ok();
+        let remote = session
+            .as_ref()
+            .is_some_and(|session| session.supports_remote_control);
+        let can_seek = session
+            .as_ref()
+            .and_then(|session| session.play_state.as_ref())
+            .is_some_and(|state| state.can_seek);
+        PlayerCapabilities {
+            can_play_pause: remote,
+            can_go_next: remote,
+            can_go_previous: remote,
+            can_seek: remote && can_seek,
+            can_set_volume: remote,
+            can_shuffle: remote,
+            can_repeat: remote,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        let session = self.session()?;
+        let item = session
+            .now_playing_item
+            .ok_or(PlayerError::PlayerNotRunning)?;
+        let state = session.play_state;
+        let volume = state.as_ref().and_then(|state| {
+            if state.is_muted {
+                Some(0)
+            } else {
+                state.volume_level
+            }
+        });
+
+        Ok(SpotifyStatus {
+            album_cover: self.cover_url(&item),
+            track_name: item.name,
+            artist_name: (!item.artists.is_empty()).then(|| item.artists.join(", ")),
+            metadata: TrackMetadata {
+                track_id: Some(item.id),
+                uri: None,
+                album_name: item.album,
+                album_artist: item.album_artist,
+                disc_number: item.parent_index_number,
+                track_number: item.index_number,
+                explicit: None,
+                popularity: None,
+            },
+            track_volume: volume,
+            position: state
+                .as_ref()
+                .and_then(|state| state.position_ticks)
+                .map(|ticks| ticks as f64 / TICKS_PER_SECOND),
+            track_duration: item
+                .run_time_ticks
+                .map(|ticks| ticks as f64 / TICKS_PER_SECOND),
+            player_state: Some(
+                if state.as_ref().is_some_and(|state| state.is_paused) {
+                    "paused"
+                } else {
+                    "playing"
+                }
+                .to_string(),
+            ),
+            shuffle: state
+                .as_ref()
+                .and_then(|state| state.shuffle_mode.as_deref())
+                .map(|mode| mode == "Shuffle"),
+            repeat_mode: state
+                .as_ref()
+                .and_then(|state| state.repeat_mode.as_deref())
+                .and_then(|mode| match mode {
+                    "RepeatNone" => Some(RepeatMode::Off),
+                    "RepeatOne" => Some(RepeatMode::Track),
+                    "RepeatAll" => Some(RepeatMode::Context),
+                    _ => None,
+                }),
+            is_saved: None,
+            // The client playing the music, as several may be connected
+            device: Some(PlaybackDevice {
+                id: Some(session.id),
+                name: session.device_name.unwrap_or_default(),
+                device_type: session.client.unwrap_or_default(),
+                is_active: true,
+                volume,
+            }),
+            error: None,
+        })
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        self.playing_command("PlayPause", &[])
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.playing_command("NextTrack", &[])
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.playing_command("PreviousTrack", &[])
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        let ticks = ((position.max(0.0) * TICKS_PER_SECOND) as u64).to_string();
+        self.playing_command("Seek", &[("seekPositionTicks", &ticks)])
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        self.general_command("SetVolume", ("Volume", &level.min(100).to_string()))
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        let mode = if enabled { "Shuffle" } else { "Sorted" };
+        self.general_command("SetShuffleQueue", ("ShuffleMode", mode))
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        let mode = match mode {
+            RepeatMode::Off => "RepeatNone",
+            RepeatMode::Track => "RepeatOne",
+            RepeatMode::Context => "RepeatAll",
+        };
+        self.general_command("SetRepeatMode", ("RepeatMode", mode))
+    }
+}
+
+/// Maps a failed request to a `PlayerError`, treating a refused connection as a stopped server.
+fn http_error(error: ureq::Error) -> PlayerError {
+    match error {
+        ureq::Error::Status(401 | 403, _) => PlayerError::NotAuthenticated,
+        ureq::Error::Status(code, _) => {
+            PlayerError::backend(format!("Jellyfin answered with status {}", code))
+        }
+        ureq::Error::Transport(transport) => match transport.kind() {
+            ureq::ErrorKind::ConnectionFailed => PlayerError::PlayerNotRunning,
+            _ => PlayerError::backend(transport),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::testing::MockServer;
+
+    /// `GET /Sessions` answer: a video session, alice's paused web client, alice's
+    /// playing phone and bob's desktop client.
+    const SESSIONS: &str = r#"[
+        {"Id": "video", "UserName": "alice", "SupportsRemoteControl": true,
+         "NowPlayingItem": {"Id": "m1", "Name": "Movie", "MediaType": "Video"}},
+        {"Id": "web", "UserName": "alice", "Client": "Jellyfin Web", "DeviceName": "Firefox",
+         "SupportsRemoteControl": true,
+         "NowPlayingItem": {"Id": "t0", "Name": "Paused", "MediaType": "Audio"},
+         "PlayState": {"IsPaused": true}},
+        {"Id": "phone", "UserName": "alice", "Client": "Finamp", "DeviceName": "Phone",
+         "SupportsRemoteControl": true,
+         "NowPlayingItem": {"Id": "t1", "Name": "Song", "MediaType": "Audio",
+            "Artists": ["A", "B"], "Album": "Album", "AlbumArtist": "Album Artist",
+            "AlbumId": "al1", "AlbumPrimaryImageTag": "tag1", "RunTimeTicks": 2450000000,
+            "IndexNumber": 3, "ParentIndexNumber": 1},
+         "PlayState": {"PositionTicks": 615000000, "CanSeek": true, "IsPaused": false,
+            "IsMuted": false, "VolumeLevel": 80, "RepeatMode": "RepeatOne",
+            "ShuffleMode": "Shuffle"}},
+        {"Id": "desktop", "UserName": "bob", "SupportsRemoteControl": false,
+         "NowPlayingItem": {"Id": "t2", "Name": "Other", "MediaType": "Audio",
+            "ImageTags": {"Primary": "tag2"}},
+         "PlayState": {"IsMuted": true, "VolumeLevel": 50}}
+    ]"#;
+
+    /// Returns a backend following `username` on the mock server.
+    fn player(server: &MockServer, username: Option<&str>) -> JellyfinPlayer {
+        JellyfinPlayer::new(&JellyfinConfig {
+            url: Some(format!("{}/", server.url)),
+            api_key: Some("key".to_string()),
+            username: username.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    /// Answers `GET /Sessions` with `sessions` and commands with 204.
+    fn jellyfin(sessions: &'static str) -> MockServer {
+        MockServer::start(move |request| match request.method.as_str() {
+            "GET" if request.path == "/Sessions" => (200, sessions.to_string()),
+            "POST" => (204, String::new()),
+            _ => (404, String::new()),
+        })
+    }
+
+    #[test]
+    fn requests_carry_the_api_key() {
+        let server = jellyfin(SESSIONS);
+        player(&server, Some("alice")).status().unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(
+            request.header("authorization"),
+            Some("MediaBrowser Token=\"key\"")
+        );
+    }
+
+    #[test]
+    fn playing_session_of_the_user_is_reported() {
+        let server = jellyfin(SESSIONS);
+        let status = player(&server, Some("alice")).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("A, B"));
+        assert_eq!(status.metadata.track_id.as_deref(), Some("t1"));
+        assert_eq!(status.metadata.album_name.as_deref(), Some("Album"));
+        assert_eq!(
+            status.metadata.album_artist.as_deref(),
+            Some("Album Artist")
+        );
+        assert_eq!(status.metadata.track_number, Some(3));
+        assert_eq!(status.metadata.disc_number, Some(1));
+        assert_eq!(status.position, Some(61.5));
+        assert_eq!(status.track_duration, Some(245.0));
+        assert_eq!(status.track_volume, Some(80));
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(true));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Track));
+        assert_eq!(
+            status.album_cover,
+            Some(format!(
+                "{}/Items/al1/Images/Primary?tag=tag1&maxHeight={}",
+                server.url,
+                params::COVER_ART_SIZE
+            ))
+        );
+        let device = status.device.unwrap();
+        assert_eq!(device.id.as_deref(), Some("phone"));
+        assert_eq!(device.name, "Phone");
+        assert_eq!(device.device_type, "Finamp");
+    }
+
+    #[test]
+    fn paused_session_is_reported_when_none_plays() {
+        let server = jellyfin(
+            r#"[{"Id": "web", "UserName": "alice",
+                 "NowPlayingItem": {"Id": "t0", "Name": "Paused", "MediaType": "Audio"},
+                 "PlayState": {"IsPaused": true}}]"#,
+        );
+        let status = player(&server, Some("alice")).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Paused"));
+        assert_eq!(status.player_state.as_deref(), Some("paused"));
+    }
+
+    #[test]
+    fn muted_session_has_no_volume() {
+        let server = jellyfin(SESSIONS);
+        let status = player(&server, Some("bob")).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Other"));
+        assert_eq!(status.track_volume, Some(0));
+        // The item image stands in for a missing album image
+        assert!(status
+            .album_cover
+            .unwrap()
+            .ends_with("/Items/t2/Images/Primary?tag=tag2&maxHeight=300"));
+    }
+
+    #[test]
+    fn no_music_session_is_not_running() {
+        let server = jellyfin(SESSIONS);
+        assert_eq!(
+            player(&server, Some("carol")).status().unwrap_err(),
+            PlayerError::PlayerNotRunning
+        );
+
+        let server = jellyfin(
+            r#"[{"Id": "video", "UserName": "alice",
+                 "NowPlayingItem": {"Id": "m1", "MediaType": "Video"}}]"#,
+        );
+        assert_eq!(
+            player(&server, None).status().unwrap_err(),
+            PlayerError::PlayerNotRunning
+        );
+    }
+
+    #[test]
+    fn playstate_commands_target_the_session() {
+        let server = jellyfin(SESSIONS);
+        let player = player(&server, Some("alice"));
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.previous_track().unwrap();
+        player.set_position(61.5).unwrap();
+
+        let commands: Vec<String> = server
+            .request_lines()
+            .into_iter()
+            .filter(|line| line.starts_with("POST"))
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                "POST /Sessions/phone/Playing/PlayPause",
+                "POST /Sessions/phone/Playing/NextTrack",
+                "POST /Sessions/phone/Playing/PreviousTrack",
+                "POST /Sessions/phone/Playing/Seek?seekPositionTicks=615000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn general_commands_carry_their_arguments() {
+        let server = jellyfin(SESSIONS);
+        let player = player(&server, Some("alice"));
+
+        player.set_volume(150).unwrap();
+        player.set_shuffle(false).unwrap();
+        player.set_repeat(RepeatMode::Context).unwrap();
+
+        let bodies: Vec<serde_json::Value> = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "POST")
+            .map(|request| {
+                assert_eq!(request.path, "/Sessions/phone/Command");
+                serde_json::from_str(&request.body).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            bodies,
+            [
+                serde_json::json!({"Name": "SetVolume", "Arguments": {"Volume": "100"}}),
+                serde_json::json!({"Name": "SetShuffleQueue", "Arguments": {"ShuffleMode": "Sorted"}}),
+                serde_json::json!({"Name": "SetRepeatMode", "Arguments": {"RepeatMode": "RepeatAll"}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn session_without_remote_control_is_not_controlled() {
+        let server = jellyfin(SESSIONS);
+        let player = player(&server, Some("bob"));
+
+        assert_eq!(
+            player.toggle_playback().unwrap_err(),
+            PlayerError::unsupported("remote control")
+        );
+        assert!(!player.capabilities().can_play_pause);
+        assert!(server
+            .request_lines()
+            .iter()
+            .all(|line| line.starts_with("GET")));
+    }
+
+    #[test]
+    fn rejected_api_key_is_not_authenticated() {
+        let server = MockServer::start(|_| (401, String::new()));
+
+        assert_eq!(
+            player(&server, None).status().unwrap_err(),
+            PlayerError::NotAuthenticated
+        );
+    }
+
+    #[test]
+    fn stopped_server_is_not_running() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let player = JellyfinPlayer::new(&JellyfinConfig {
+            url: Some(format!("http://127.0.0.1:{}", port)),
+            api_key: Some("key".to_string()),
+            username: None,
+        })
+        .unwrap();
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+}
@@ -0,0 +1,780 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::artwork::ArtworkCache;
+use super::{ChangeListener, MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::config::KodiConfig;
+use crate::params::{self, RepeatMode, SpotifyStatus, TrackMetadata};
+
+/// Properties of the active player read for the status.
+const PLAYER_PROPERTIES: [&str; 5] = ["time", "totaltime", "speed", "shuffled", "repeat"];
+/// Properties of the playing item read for the status.
+const ITEM_PROPERTIES: [&str; 8] = [
+    "title",
+    "artist",
+    "album",
+    "albumartist",
+    "thumbnail",
+    "track",
+    "disc",
+    "file",
+];
+
+/// Response to a JSON-RPC request.
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+/// Error answered to a JSON-RPC request.
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Request written to the TCP interface to check that Kodi still answers.
+const PING: &[u8] = br#"{"jsonrpc": "2.0", "id": "ping", "method": "JSONRPC.Ping"}"#;
+
+/// Notification pushed by Kodi over its TCP interface.
+#[derive(Debug, Deserialize)]
+struct Notification {
+    method: Option<String>, // e.g. `Player.OnPlay`, absent in responses
+}
+
+/// Player listed by `Player.GetActivePlayers`.
+#[derive(Debug, Deserialize)]
+struct ActivePlayer {
+    playerid: i64,
+    #[serde(rename = "type")]
+    kind: String, // `audio`, `video` or `picture`
+}
+
+/// Properties returned by `Player.GetProperties`.
+#[derive(Debug, Deserialize)]
+struct PlayerProperties {
+    time: Option<KodiTime>,
+    totaltime: Option<KodiTime>,
+    speed: Option<f64>, // 0 while paused
+    shuffled: Option<bool>,
+    repeat: Option<String>, // `off`, `one` or `all`
+}
+
+/// Time as represented by Kodi.
+#[derive(Debug, Deserialize)]
+struct KodiTime {
+    hours: u64,
+    minutes: u64,
+    seconds: u64,
+    milliseconds: u64,
+}
+
+impl KodiTime {
+    /// Converts the time to seconds.
+    fn as_secs(&self) -> f64 {
+        (self.hours * 3600 + self.minutes * 60 + self.seconds) as f64
+            + self.milliseconds as f64 / 1000.0
+    }
+}
+
+/// Result of `Player.GetItem`.
+#[derive(Debug, Deserialize)]
+struct ItemResult {
+    item: Item,
+}
+
+/// Item being played.
+#[derive(Debug, Deserialize)]
+struct Item {
+    id: Option<i64>, // Library id, absent for files played outside the library
+    #[serde(rename = "type")]
+    kind: Option<String>, // `song`, `movie`, `unknown`...
+    label: Option<String>,
+    title: Option<String>,
+    #[serde(default)]
+    artist: Vec<String>,
+    album: Option<String>,
+    #[serde(default)]
+    albumartist: Vec<String>,
+    thumbnail: Option<String>, // `image://` URL, served by the web server
+    track: Option<i64>,        // 0 or -1 when unknown
+    disc: Option<i64>,
+    file: Option<String>,
+}
+
+/// Properties returned by `Application.GetProperties`.
+#[derive(Debug, Deserialize)]
+struct ApplicationProperties {
+    volume: Option<u32>,
+    muted: Option<bool>,
+}
+
+/// Backend controlling Kodi through JSON-RPC over HTTP, with changes pushed through
+/// its TCP interface so the status thread polls right away.
+pub struct KodiPlayer {
+    config: KodiConfig,            // Where and how to connect
+    base_url: String,              // URL of the web server
+    authorization: Option<String>, // Basic auth header value, if a password is set
+    agent: ureq::Agent,            // HTTP client for the web server
+    artwork: ArtworkCache,         // Thumbnail of the last seen item
+}
+
+impl KodiPlayer {
+    /// Creates a backend talking to the Kodi web server configured in `config`.
+    pub fn new(config: &KodiConfig) -> Self {
+        let authorization = config.password.as_ref().map(|password| {
+            let credentials = format!("{}:{}", config.username, password);
+            format!("Basic {}", STANDARD.encode(credentials))
+        });
+        Self {
+            config: config.clone(),
+            base_url: format!("http://{}:{}", config.host, config.port),
+            authorization,
+            agent: ureq::AgentBuilder::new()
+                .timeout(params::KODI_TIMEOUT)
+                .build(),
+            artwork: ArtworkCache::new("kodi"),
+        }
+    }
+
+    /// Sends a GET request to the web server.
+    fn get(&self, path: &str) -> Result<ureq::Response, PlayerError> {
+        let mut request = self.agent.get(&format!("{}{}", self.base_url, path));
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        request.call().map_err(http_error)
+    }
+
+    /// Calls a JSON-RPC method and returns its result.
+    fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, PlayerError> {
+        let mut request = self.agent.post(&format!("{}/jsonrpc", self.base_url));
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+
+        let response: RpcResponse<T> = request
+            .send_json(body)
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| PlayerError::parse(e.to_string()))?;
+        match (response.result, response.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(error)) => Err(PlayerError::backend(format!(
+                "{} failed ({}): {}",
+                method, error.code, error.message
+            ))),
+            (None, None) => Err(PlayerError::parse(format!("{} returned nothing", method))),
+        }
+    }
+
+    /// Calls a JSON-RPC method whose result is not needed.
+    fn command(&self, method: &str, params: serde_json::Value) -> Result<(), PlayerError> {
+        self.call::<serde_json::Value>(method, params).map(|_| ())
+    }
+
+    /// Returns the id of the player to follow, preferring audio over video;
+    /// `PlayerNotRunning` if nothing plays.
+    fn active_player(&self) -> Result<i64, PlayerError> {
+        let players: Vec<ActivePlayer> = self.call("Player.GetActivePlayers", json!({}))?;
+        players
+            .iter()
+            .find(|player| player.kind == "audio")
+            .or_else(|| players.first())
+            .map(|player| player.playerid)
+            .ok_or(PlayerError::PlayerNotRunning)
+    }
+
+    /// Downloads a thumbnail through the web server, which authenticates the request.
+    fn fetch_thumbnail(&self, thumbnail: &str) -> Result<Option<Vec<u8>>, PlayerError> {
+        let encoded: String = url::form_urlencoded::byte_serialize(thumbnail.as_bytes()).collect();
+        let mut data = Vec::new();
+        self.get(&format!("/image/{}", encoded))?
+            .into_reader()
+            .take(params::KODI_ARTWORK_MAX_SIZE)
+            .read_to_end(&mut data)
+            .map_err(PlayerError::backend)?;
+        Ok((!data.is_empty()).then_some(data))
+    }
+}
+
+impl MediaPlayer for KodiPlayer {
+    fn name(&self) -> &str {
+        "Kodi"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: true,
+            can_shuffle: true,
+            can_repeat: true,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        let playerid = self.active_player()?;
+        let properties: PlayerProperties = self.call(
+            "Player.GetProperties",
+            json!({ "playerid": playerid, "properties": PLAYER_PROPERTIES }),
+        )?;
+        let item = self
+            .call::<ItemResult>(
+                "Player.GetItem",
+                json!({ "playerid": playerid, "properties": ITEM_PROPERTIES }),
+            )?
+            .item;
+        let application: ApplicationProperties = self.call(
+            "Application.GetProperties",
+            json!({ "properties": ["volume", "muted"] }),
+        )?;
+
+        let album_cover = item
+            .thumbnail
+            .as_deref()
+            .filter(|thumbnail| !thumbnail.is_empty())
+            .and_then(|thumbnail| {
+                self.artwork
+                    .resolve(thumbnail, || self.fetch_thumbnail(thumbnail))
+            });
+        // Library items have an id per type, files played directly only their path
+        let track_id = match (&item.kind, item.id) {
+            (Some(kind), Some(id)) => Some(format!("{}:{}", kind, id)),
+            _ => item.file.clone(),
+        };
+        let player_state = if properties.speed.unwrap_or(0.0) == 0.0 {
+            "paused"
+        } else {
+            "playing"
+        };
+
+        Ok(SpotifyStatus {
+            track_name: item.title.filter(|title| !title.is_empty()).or(item.label),
+            artist_name: (!item.artist.is_empty()).then(|| item.artist.join(", ")),
+            metadata: TrackMetadata {
+                track_id,
+                uri: item.file,
+                album_name: item.album.filter(|album| !album.is_empty()),
+                album_artist: (!item.albumartist.is_empty()).then(|| item.albumartist.join(", ")),
+                disc_number: item
+                    .disc
+                    .and_then(|disc| u32::try_from(disc).ok())
+                    .filter(|&disc| disc > 0),
+                track_number: item
+                    .track
+                    .and_then(|track| u32::try_from(track).ok())
+                    .filter(|&track| track > 0),
+                explicit: None,
+                popularity: None,
+            },
+            track_volume: match application.muted {
+                Some(true) => Some(0),
+                _ => application.volume,
+            },
+            position: properties.time.as_ref().map(KodiTime::as_secs),
+            track_duration: properties
+                .totaltime
+                .as_ref()
+                .map(KodiTime::as_secs)
+                .filter(|duration| *duration > 0.0),
+            album_cover,
+            player_state: Some(player_state.to_string()),
+            shuffle: properties.shuffled,
+            repeat_mode: properties
+                .repeat
+                .as_deref()
+                .and_then(|repeat| match repeat {
+                    "off" => Some(RepeatMode::Off),
+                    "one" => Some(RepeatMode::Track),
+                    "all" => Some(RepeatMode::Context),
+                    _ => None,
+                }),
+            is_saved: None,
+            device: None,
+            error: None,
+        })
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        let playerid = self.active_player()?;
+        self.command("Player.PlayPause", json!({ "playerid": playerid }))
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        let playerid = self.active_player()?;
+        self.command("Player.GoTo", json!({ "playerid": playerid, "to": "next" }))
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        let playerid = self.active_player()?;
+        self.command(
+            "Player.GoTo",
+            json!({ "playerid": playerid, "to": "previous" }),
+        )
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        let playerid = self.active_player()?;
+        let millis = (position.max(0.0) * 1000.0) as u64;
+        let time = json!({
+            "hours": millis / 3_600_000,
+            "minutes": millis / 60_000 % 60,
+            "seconds": millis / 1000 % 60,
+            "milliseconds": millis % 1000,
+        });
+        self.command(
+            "Player.Seek",
+            json!({ "playerid": playerid, "value": { "time": time } }),
+        )
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        self.command("Application.SetVolume", json!({ "volume": level.min(100) }))
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        let playerid = self.active_player()?;
+        self.command(
+            "Player.SetShuffle",
+            json!({ "playerid": playerid, "shuffle": enabled }),
+        )
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        let playerid = self.active_player()?;
+        let repeat = match mode {
+            RepeatMode::Off => "off",
+            RepeatMode::Track => "one",
+            RepeatMode::Context => "all",
+        };
+        self.command(
+            "Player.SetRepeat",
+            json!({ "playerid": playerid, "repeat": repeat }),
+        )
+    }
+
+    fn watch(&self, listener: ChangeListener) -> bool {
+        let config = self.config.clone();
+        thread::spawn(move || loop {
+            if let Err(e) = watch_notifications(&config, &listener) {
+                log::debug!("Kodi notification watcher disconnected: {}", e);
+            }
+            thread::sleep(params::KODI_RECONNECT_INTERVAL);
+        });
+        true
+    }
+}
+
+/// Reads the notifications Kodi pushes over its TCP interface and calls the listener
+/// for each playback or volume change, until the connection fails.
+fn watch_notifications(config: &KodiConfig, listener: &ChangeListener) -> io::Result<()> {
+    let stream = connect(config)?;
+    stream.set_read_timeout(Some(params::KODI_NOTIFICATION_IDLE_TIMEOUT))?;
+    // Kodi may just have come up, so report it right away
+    listener();
+
+    // The interface streams JSON objects back to back, without separators; the
+    // answers to pings have no method and are skipped
+    let reader = BufReader::new(PingingReader::new(stream));
+    let notifications = serde_json::Deserializer::from_reader(reader).into_iter::<Notification>();
+    for notification in notifications {
+        let method = notification?.method.unwrap_or_default();
+        if method.starts_with("Player.") || method == "Application.OnVolumeChanged" {
+            listener();
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed by Kodi",
+    ))
+}
+
+/// Connects to the TCP interface, trying each address the host resolves to.
+fn connect(config: &KodiConfig) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host resolves to no address");
+    for address in (config.host.as_str(), config.tcp_port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, params::MEDIA_SERVER_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Reader over the TCP interface that pings Kodi when a read times out, so an idle
+/// connection is kept while one to a vanished host fails.
+struct PingingReader {
+    stream: TcpStream, // Connection with a read timeout set
+    pinged: bool,      // Whether a ping is unanswered
+}
+
+impl PingingReader {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            pinged: false,
+        }
+    }
+}
+
+impl Read for PingingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.stream.read(buf) {
+                Ok(read) => {
+                    self.pinged = false;
+                    return Ok(read);
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    if self.pinged {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "Kodi stopped answering",
+                        ));
+                    }
+                    self.stream.write_all(PING)?;
+                    self.pinged = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Maps a failed request to a `PlayerError`, treating a refused connection as a closed Kodi.
+fn http_error(error: ureq::Error) -> PlayerError {
+    match error {
+        ureq::Error::Status(401 | 403, _) => PlayerError::NotAuthenticated,
+        ureq::Error::Status(code, _) => {
+            PlayerError::backend(format!("Kodi answered with status {}", code))
+        }
+        ureq::Error::Transport(transport) => match transport.kind() {
+            ureq::ErrorKind::ConnectionFailed => PlayerError::PlayerNotRunning,
+            _ => PlayerError::backend(transport),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::player::testing::{MockRequest, MockServer, TempDir};
+
+    /// Answers the JSON-RPC methods the backend calls as Kodi playing a song, with a
+    /// failing `Player.SetShuffle`; requires the `kodi:secret` credentials.
+    fn answer(request: &MockRequest) -> (u16, String) {
+        if request.header("authorization") != Some("Basic a29kaTpzZWNyZXQ=") {
+            return (401, String::new());
+        }
+        if request.path.starts_with("/image/") {
+            return (200, "cover".to_string());
+        }
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        let result = match body["method"].as_str().unwrap() {
+            "Player.GetActivePlayers" => {
+                r#"[{"playerid": 1, "playertype": "internal", "type": "video"},
+                    {"playerid": 0, "playertype": "internal", "type": "audio"}]"#
+            }
+            "Player.GetProperties" => {
+                r#"{"time": {"hours": 0, "minutes": 1, "seconds": 2, "milliseconds": 500},
+                    "totaltime": {"hours": 0, "minutes": 4, "seconds": 0, "milliseconds": 0},
+                    "speed": 0, "shuffled": true, "repeat": "all"}"#
+            }
+            "Player.GetItem" => {
+                r#"{"item": {"id": 12, "type": "song", "label": "Song", "title": "Song",
+                    "artist": ["A", "B"], "album": "Album", "albumartist": [],
+                    "thumbnail": "image://music@smb%3a%2f%2fcover.jpg/", "track": 3,
+                    "disc": 0, "file": "/music/song.flac"}}"#
+            }
+            "Application.GetProperties" => r#"{"volume": 80, "muted": false}"#,
+            "Player.SetShuffle" => {
+                return (
+                    200,
+                    r#"{"id": 1, "jsonrpc": "2.0", "error": {"code": -32100, "message": "Failed to execute method."}}"#
+                        .to_string(),
+                )
+            }
+            _ => r#""OK""#,
+        };
+        (
+            200,
+            format!(r#"{{"id": 1, "jsonrpc": "2.0", "result": {}}}"#, result),
+        )
+    }
+
+    /// Returns the settings reaching the mock server.
+    fn config(server: &MockServer) -> KodiConfig {
+        KodiConfig {
+            host: "127.0.0.1".to_string(),
+            port: server.port,
+            tcp_port: 9090,
+            username: "kodi".to_string(),
+            password: Some("secret".to_string()),
+        }
+    }
+
+    /// Returns `player` storing the thumbnails it fetches in `dir`.
+    fn storing_thumbnails_in(player: KodiPlayer, dir: &TempDir) -> KodiPlayer {
+        KodiPlayer {
+            artwork: ArtworkCache::with_dir("kodi", dir.path.clone()),
+            ..player
+        }
+    }
+
+    /// Returns the method and parameters of the JSON-RPC calls received by the server,
+    /// leaving out the status reads.
+    fn commands(server: &MockServer) -> Vec<serde_json::Value> {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.path == "/jsonrpc")
+            .map(|request| serde_json::from_str::<serde_json::Value>(&request.body).unwrap())
+            .filter(|body| !body["method"].as_str().unwrap().contains(".Get"))
+            .map(|body| json!([body["method"], body["params"]]))
+            .collect()
+    }
+
+    /// Returns a port nothing listens on.
+    fn closed_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[test]
+    fn status_follows_the_audio_player() {
+        let server = MockServer::start(answer);
+        let dir = TempDir::new("kodi-status");
+        let player = storing_thumbnails_in(KodiPlayer::new(&config(&server)), &dir);
+        let status = player.status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("A, B"));
+        assert_eq!(status.metadata.track_id.as_deref(), Some("song:12"));
+        assert_eq!(status.metadata.uri.as_deref(), Some("/music/song.flac"));
+        assert_eq!(status.metadata.album_artist, None);
+        assert_eq!(status.metadata.track_number, Some(3));
+        assert_eq!(status.metadata.disc_number, None);
+        assert_eq!(status.position, Some(62.5));
+        assert_eq!(status.track_duration, Some(240.0));
+        assert_eq!(status.player_state.as_deref(), Some("paused"));
+        assert_eq!(status.shuffle, Some(true));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Context));
+        assert_eq!(status.track_volume, Some(80));
+
+        let request = &server.requests()[1];
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["params"]["playerid"], 0);
+    }
+
+    #[test]
+    fn thumbnail_is_fetched_through_the_web_server() {
+        let server = MockServer::start(answer);
+        let dir = TempDir::new("kodi-thumbnail");
+        let player = storing_thumbnails_in(KodiPlayer::new(&config(&server)), &dir);
+        let cover = player.status().unwrap().album_cover.unwrap();
+
+        assert!(cover.starts_with("asset://localhost/"));
+        let name = cover.rsplit("%2F").next().unwrap();
+        assert_eq!(std::fs::read(dir.path.join(name)).unwrap(), b"cover");
+        assert!(server.request_lines().contains(
+            &"GET /image/image%3A%2F%2Fmusic%40smb%253a%252f%252fcover.jpg%2F".to_string()
+        ));
+    }
+
+    #[test]
+    fn commands_target_the_audio_player() {
+        let server = MockServer::start(answer);
+        let player = KodiPlayer::new(&config(&server));
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.previous_track().unwrap();
+        player.set_position(3725.25).unwrap();
+        player.set_volume(120).unwrap();
+        player.set_repeat(RepeatMode::Track).unwrap();
+
+        assert_eq!(
+            commands(&server),
+            [
+                json!(["Player.PlayPause", {"playerid": 0}]),
+                json!(["Player.GoTo", {"playerid": 0, "to": "next"}]),
+                json!(["Player.GoTo", {"playerid": 0, "to": "previous"}]),
+                json!(["Player.Seek", {"playerid": 0, "value": {"time":
+                    {"hours": 1, "minutes": 2, "seconds": 5, "milliseconds": 250}}}]),
+                json!(["Application.SetVolume", {"volume": 100}]),
+                json!(["Player.SetRepeat", {"playerid": 0, "repeat": "one"}]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rpc_error_is_a_backend_error() {
+        let server = MockServer::start(answer);
+
+        assert_eq!(
+            KodiPlayer::new(&config(&server))
+                .set_shuffle(true)
+                .unwrap_err(),
+            PlayerError::backend("Player.SetShuffle failed (-32100): Failed to execute method.")
+        );
+    }
+
+    #[test]
+    fn nothing_playing_is_not_running() {
+        let server = MockServer::start(|_| {
+            (
+                200,
+                r#"{"id": 1, "jsonrpc": "2.0", "result": []}"#.to_string(),
+            )
+        });
+        let player = KodiPlayer::new(&config(&server));
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn missing_password_is_not_authenticated() {
+        let server = MockServer::start(answer);
+        let player = KodiPlayer::new(&KodiConfig {
+            password: None,
+            ..config(&server)
+        });
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::NotAuthenticated);
+    }
+
+    #[test]
+    fn closed_kodi_is_not_running() {
+        let server = MockServer::start(answer);
+        let player = KodiPlayer::new(&KodiConfig {
+            port: closed_port(),
+            ..config(&server)
+        });
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn notifications_call_the_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(
+                    br#"{"jsonrpc": "2.0", "method": "Player.OnPlay", "params": {"data": {}, "sender": "xbmc"}}{"jsonrpc": "2.0", "method": "GUI.OnScreensaverActivated", "params": {}}"#,
+                )
+                .unwrap();
+            stream
+                .write_all(
+                    br#"{"jsonrpc": "2.0", "method": "Application.OnVolumeChanged", "params": {"data": {"muted": false, "volume": 50}, "sender": "xbmc"}}"#,
+                )
+                .unwrap();
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let listener: ChangeListener = Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let config = KodiConfig {
+            tcp_port,
+            ..KodiConfig::default()
+        };
+
+        let error = watch_notifications(&config, &listener).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        // Once on connection, then for the play and volume changes
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn closed_interface_fails_to_connect() {
+        let listener: ChangeListener = Arc::new(|| panic!("no connection to report"));
+        let config = KodiConfig {
+            tcp_port: closed_port(),
+            ..KodiConfig::default()
+        };
+
+        assert!(watch_notifications(&config, &listener).is_err());
+    }
+
+    #[test]
+    fn idle_connection_is_pinged() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut ping = vec![0; PING.len()];
+            stream.read_exact(&mut ping).unwrap();
+            sender.send(ping).unwrap();
+            stream
+                .write_all(br#"{"id": "ping", "jsonrpc": "2.0", "result": "pong"}"#)
+                .unwrap();
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+
+        let mut answer = String::new();
+        PingingReader::new(stream)
+            .read_to_string(&mut answer)
+            .unwrap();
+
+        assert_eq!(received.recv().unwrap(), PING);
+        assert_eq!(
+            answer,
+            r#"{"id": "ping", "jsonrpc": "2.0", "result": "pong"}"#
+        );
+    }
+
+    #[test]
+    fn unanswered_ping_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let _peer = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+
+        let error = PingingReader::new(stream).read(&mut [0; 16]).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,748 @@
+use std::cmp::Reverse;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::{ChangeListener, MediaPlayer, PlayerError};
+use crate::config::BackendKind;
+use crate::params::{self, SpotifyStatus};
+
+/// Description of a player known to the manager, as listed to the frontend.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct PlayerInfo {
+    pub id: BackendKind, // Backend identifier, used to pin the player
+    pub name: String,    // Human readable name of the player
+    pub running: bool,   // Whether the player answered the last poll
+    pub playing: bool,   // Whether the player was playing at the last poll
+    pub active: bool,    // Whether commands are routed to this player
+    pub pinned: bool,    // Whether the user pinned this player
+}
+
+/// Result of polling the players.
+#[derive(Debug)]
+pub struct ManagerPoll {
+    pub status: Result<SpotifyStatus, PlayerError>, // Status of the active player
+    pub switched: bool,                             // Whether the active player changed
+}
+
+/// Poll results remembered for a player.
+#[derive(Debug, Clone, Copy, Default)]
+struct PlayerSlot {
+    running: bool,              // Whether the player answered the last poll
+    playing: bool,              // Whether the player was playing at the last poll
+    playing_since: u64,         // Poll at which the player last started playing
+    polled_at: Option<Instant>, // When the player was last polled
+    watched: bool,              // Whether the player pushes its changes
+}
+
+/// Arbitration state shared between the status thread and the commands.
+#[derive(Debug)]
+struct ManagerState {
+    slots: Vec<PlayerSlot>, // Poll results, in the order of the players
+    active: usize,          // Index of the active player
+    pinned: Option<usize>,  // Index of the player pinned by the user
+    polls: u64,             // Number of polls so far
+}
+
+/// Tracks every enabled player and decides which one is active.
+///
+/// The active player is the pinned one while it runs, otherwise the one that
+/// most recently started playing; when nothing plays the current one is kept
+/// while it runs, then the first running one in priority order is picked.
+/// Registered as Tauri managed state in `lib.rs::run`.
+///
+/// Only the active player is polled at the status thread's pace; the others are
+/// polled every `BACKGROUND_POLL_INTERVAL`, or right away when they report a change
+/// themselves. The players due are polled in parallel, so a poll takes as long as
+/// the slowest of them rather than the sum of all.
+pub struct PlayerManager {
+    players: Vec<(BackendKind, Arc<dyn MediaPlayer>)>, // Enabled players, in priority order
+    state: Mutex<ManagerState>,                        // Arbitration state
+    changed: Arc<Vec<AtomicBool>>, // Players that reported a change since their last poll
+    background_interval: Duration, // Interval at which the inactive players are polled
+}
+
+impl PlayerManager {
+    /// Creates a manager for the given players, listed in priority order.
+    ///
+    /// Panics if no player is given.
+    pub fn new(players: Vec<(BackendKind, Arc<dyn MediaPlayer>)>) -> Self {
+        assert!(!players.is_empty(), "at least one player is required");
+        Self {
+            state: Mutex::new(ManagerState {
+                slots: vec![PlayerSlot::default(); players.len()],
+                active: 0,
+                pinned: None,
+                polls: 0,
+            }),
+            changed: Arc::new(players.iter().map(|_| AtomicBool::new(false)).collect()),
+            background_interval: params::BACKGROUND_POLL_INTERVAL,
+            players,
+        }
+    }
+
+    /// Returns the active player, which commands are routed to.
+    pub fn get(&self) -> Arc<dyn MediaPlayer> {
+        self.players[self.state().active].1.clone()
+    }
+
+    /// Polls the active player and the inactive ones that are due, updates the active
+    /// one and returns its status.
+    pub fn poll(&self) -> ManagerPoll {
+        let due: Vec<bool> = {
+            let state = self.state();
+            state
+                .slots
+                .iter()
+                .enumerate()
+                .map(|(index, slot)| {
+                    let changed = self.changed[index].swap(false, Ordering::SeqCst);
+                    let stale = slot
+                        .polled_at
+                        .map_or(true, |at| at.elapsed() >= self.background_interval);
+                    index == state.active || changed || stale
+                })
+                .collect()
+        };
+        let mut results = self.poll_players(&due);
+
+        let mut state = self.state();
+        state.polls += 1;
+        let polls = state.polls;
+        for (index, result) in results.iter().enumerate() {
+            if let Some(result) = result {
+                record(&mut state.slots[index], result, polls);
+            }
+        }
+
+        let previous = state.active;
+        state.active = select_active(&state);
+        // Picked from its last background poll, so it is polled for a fresh status
+        let active = state.active;
+        let status = match results[active].take() {
+            Some(status) => status,
+            None => {
+                drop(state);
+                let status = self.players[active].1.status();
+                record(&mut self.state().slots[active], &status, polls);
+                status
+            }
+        };
+        ManagerPoll {
+            status,
+            switched: active != previous,
+        }
+    }
+
+    /// Asks every player to report its changes to `listener`, flagging the reporting
+    /// player so the next poll includes it.
+    pub fn watch(&self, listener: ChangeListener) {
+        let watched: Vec<bool> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(index, (_, backend))| {
+                let changed = self.changed.clone();
+                let listener = listener.clone();
+                backend.watch(Arc::new(move || {
+                    changed[index].store(true, Ordering::SeqCst);
+                    listener();
+                }))
+            })
+            .collect();
+        for (slot, watched) in self.state().slots.iter_mut().zip(watched) {
+            slot.watched = watched;
+        }
+    }
+
+    /// Returns whether every player pushes its changes, so polls only catch up on missed ones.
+    pub fn is_watched(&self) -> bool {
+        self.state().slots.iter().all(|slot| slot.watched)
+    }
+
+    /// Polls the flagged players in parallel, `None` for the others.
+    fn poll_players(&self, due: &[bool]) -> Vec<Option<Result<SpotifyStatus, PlayerError>>> {
+        thread::scope(|scope| {
+            let polls: Vec<_> = self
+                .players
+                .iter()
+                .zip(due)
+                .map(|((_, backend), &due)| due.then(|| scope.spawn(|| backend.status())))
+                .collect();
+            polls
+                .into_iter()
+                .map(|poll| {
+                    poll.map(|poll| poll.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                })
+                .collect()
+        })
+    }
+
+    /// Pins the given player so it stays active while it runs, or unpins with `None`.
+    pub fn pin(&self, id: Option<BackendKind>) -> Result<(), PlayerError> {
+        let pinned = match id {
+            Some(id) => Some(
+                self.players
+                    .iter()
+                    .position(|(kind, _)| *kind == id)
+                    .ok_or_else(|| PlayerError::backend(format!("{:?} is not enabled", id)))?,
+            ),
+            None => None,
+        };
+        self.state().pinned = pinned;
+        Ok(())
+    }
+
+    /// Lists the players in priority order.
+    pub fn list(&self) -> Vec<PlayerInfo> {
+        let state = self.state();
+        (0..self.players.len())
+            .map(|index| self.info(&state, index))
+            .collect()
+    }
+
+    /// Describes the active player.
+    pub fn active_info(&self) -> PlayerInfo {
+        let state = self.state();
+        self.info(&state, state.active)
+    }
+
+    /// Describes the player at the given index.
+    fn info(&self, state: &ManagerState, index: usize) -> PlayerInfo {
+        let (kind, backend) = &self.players[index];
+        let slot = state.slots[index];
+        PlayerInfo {
+            id: *kind,
+            name: backend.name().to_string(),
+            running: slot.running,
+            playing: slot.playing,
+            active: state.active == index,
+            pinned: state.pinned == Some(index),
+        }
+    }
+
+    /// Locks the arbitration state, recovering from a poisoned lock.
+    fn state(&self) -> MutexGuard<'_, ManagerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Records the result of polling a player in its slot.
+fn record(slot: &mut PlayerSlot, result: &Result<SpotifyStatus, PlayerError>, polls: u64) {
+    let playing = result
+        .as_ref()
+        .is_ok_and(|status| status.player_state.as_deref() == Some("playing"));
+    if playing && !slot.playing {
+        slot.playing_since = polls;
+    }
+    slot.running = result.is_ok();
+    slot.playing = playing;
+    slot.polled_at = Some(Instant::now());
+}
+
+/// Picks the index of the active player from the latest poll results.
+fn select_active(state: &ManagerState) -> usize {
+    let slots = &state.slots;
+    if let Some(pinned) = state.pinned.filter(|&index| slots[index].running) {
+        return pinned;
+    }
+
+    // Among players starting at the same poll, the first in priority order wins
+    let latest_playing = slots
+        .iter()
+        .enumerate()
+        .filter(|(_, slot)| slot.playing)
+        .max_by_key(|(index, slot)| (slot.playing_since, Reverse(*index)))
+        .map(|(index, _)| index);
+    if let Some(index) = latest_playing {
+        return index;
+    }
+
+    if slots[state.active].running {
+        return state.active;
+    }
+    slots
+        .iter()
+        .position(|slot| slot.running)
+        .unwrap_or(state.active)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Condvar;
+
+    use super::*;
+    use crate::params::RepeatMode;
+    use crate::player::PlayerCapabilities;
+
+    /// Meeting point of concurrent polls: each poll waits for the others to arrive.
+    struct Gate {
+        expected: usize,       // Polls that have to meet
+        arrived: Mutex<usize>, // Polls that arrived so far
+        all_arrived: Condvar,  // Notified on every arrival
+        met: AtomicUsize,      // Polls that saw all the others arrive
+    }
+
+    impl Gate {
+        fn new(expected: usize) -> Arc<Self> {
+            Arc::new(Self {
+                expected,
+                arrived: Mutex::new(0),
+                all_arrived: Condvar::new(),
+                met: AtomicUsize::new(0),
+            })
+        }
+
+        /// Waits a bounded time for the other polls, so sequential polls fail
+        /// the test instead of hanging it.
+        fn pass(&self) {
+            let mut arrived = self.arrived.lock().unwrap();
+            *arrived += 1;
+            self.all_arrived.notify_all();
+            let (_arrived, wait) = self
+                .all_arrived
+                .wait_timeout_while(arrived, Duration::from_secs(5), |arrived| {
+                    *arrived < self.expected
+                })
+                .unwrap();
+            if !wait.timed_out() {
+                self.met.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Player answering a settable state, counting its polls and recording its commands.
+    struct FakePlayer {
+        name: &'static str,
+        state: Mutex<Result<&'static str, PlayerError>>, // `player_state` of the status
+        gate: Option<Arc<Gate>>,                         // Gate each poll passes
+        polls: AtomicUsize,
+        commands: Mutex<Vec<&'static str>>,
+        pushes: bool,                            // Whether `watch` is supported
+        listener: Mutex<Option<ChangeListener>>, // Listener given to `watch`
+    }
+
+    impl FakePlayer {
+        fn new(name: &'static str, state: &'static str) -> Arc<Self> {
+            Self::gated(name, state, None)
+        }
+
+        fn gated(name: &'static str, state: &'static str, gate: Option<Arc<Gate>>) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                state: Mutex::new(Ok(state)),
+                gate,
+                polls: AtomicUsize::new(0),
+                commands: Mutex::new(Vec::new()),
+                pushes: true,
+                listener: Mutex::new(None),
+            })
+        }
+
+        /// Returns a player that does not push its changes.
+        fn polled_only(name: &'static str, state: &'static str) -> Arc<Self> {
+            let mut player = Arc::into_inner(Self::new(name, state)).unwrap();
+            player.pushes = false;
+            Arc::new(player)
+        }
+
+        /// Reports a change the way a watched backend does.
+        fn report_change(&self) {
+            let listener = self.listener.lock().unwrap().clone();
+            listener.unwrap()();
+        }
+
+        fn polls(&self) -> usize {
+            self.polls.load(Ordering::SeqCst)
+        }
+
+        fn set(&self, state: Result<&'static str, PlayerError>) {
+            *self.state.lock().unwrap() = state;
+        }
+
+        fn record(&self, command: &'static str) -> Result<(), PlayerError> {
+            self.commands.lock().unwrap().push(command);
+            Ok(())
+        }
+
+        fn commands(&self) -> Vec<&'static str> {
+            self.commands.lock().unwrap().clone()
+        }
+    }
+
+    impl MediaPlayer for FakePlayer {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                can_play_pause: true,
+                can_go_next: true,
+                can_go_previous: true,
+                can_seek: true,
+                can_set_volume: true,
+                can_shuffle: true,
+                can_repeat: true,
+                can_play_uri: false,
+                can_save: false,
+                can_queue: false,
+                can_transfer: false,
+            }
+        }
+
+        fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+            self.polls.fetch_add(1, Ordering::SeqCst);
+            if let Some(gate) = &self.gate {
+                gate.pass();
+            }
+            self.state
+                .lock()
+                .unwrap()
+                .clone()
+                .map(|state| SpotifyStatus {
+                    track_name: Some(self.name.to_string()),
+                    player_state: Some(state.to_string()),
+                    ..SpotifyStatus::default()
+                })
+        }
+
+        fn toggle_playback(&self) -> Result<(), PlayerError> {
+            self.record("toggle")
+        }
+
+        fn next_track(&self) -> Result<(), PlayerError> {
+            self.record("next")
+        }
+
+        fn previous_track(&self) -> Result<(), PlayerError> {
+            self.record("previous")
+        }
+
+        fn set_position(&self, _position: f64) -> Result<(), PlayerError> {
+            self.record("seek")
+        }
+
+        fn set_volume(&self, _level: u32) -> Result<(), PlayerError> {
+            self.record("volume")
+        }
+
+        fn set_shuffle(&self, _enabled: bool) -> Result<(), PlayerError> {
+            self.record("shuffle")
+        }
+
+        fn set_repeat(&self, _mode: RepeatMode) -> Result<(), PlayerError> {
+            self.record("repeat")
+        }
+
+        fn watch(&self, listener: ChangeListener) -> bool {
+            if self.pushes {
+                *self.listener.lock().unwrap() = Some(listener);
+            }
+            self.pushes
+        }
+    }
+
+    /// Returns a manager polling every player on each poll, as if the background
+    /// interval always elapsed.
+    fn manager(players: &[(BackendKind, &Arc<FakePlayer>)]) -> PlayerManager {
+        let mut manager = PlayerManager::new(
+            players
+                .iter()
+                .map(|(kind, player)| (*kind, (*player).clone() as Arc<dyn MediaPlayer>))
+                .collect(),
+        );
+        manager.background_interval = Duration::ZERO;
+        manager
+    }
+
+    /// Returns the name of the active player.
+    fn active(manager: &PlayerManager) -> String {
+        manager.get().name().to_string()
+    }
+
+    #[test]
+    fn most_recently_playing_player_is_active() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let music = FakePlayer::new("Music", "playing");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+
+        let poll = manager.poll();
+        assert!(poll.switched);
+        assert_eq!(active(&manager), "Music");
+        assert_eq!(poll.status.unwrap().track_name.as_deref(), Some("Music"));
+
+        spotify.set(Ok("playing"));
+        let poll = manager.poll();
+        assert!(poll.switched);
+        assert_eq!(active(&manager), "Spotify");
+        assert_eq!(poll.status.unwrap().track_name.as_deref(), Some("Spotify"));
+
+        // The player still playing takes over again
+        spotify.set(Ok("paused"));
+        assert!(manager.poll().switched);
+        assert_eq!(active(&manager), "Music");
+    }
+
+    #[test]
+    fn first_player_starting_wins_a_tie() {
+        let spotify = FakePlayer::new("Spotify", "playing");
+        let music = FakePlayer::new("Music", "playing");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+
+        manager.poll();
+
+        assert_eq!(active(&manager), "Spotify");
+    }
+
+    #[test]
+    fn running_player_is_kept_when_nothing_plays() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let music = FakePlayer::new("Music", "playing");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.poll();
+
+        music.set(Ok("paused"));
+        assert!(!manager.poll().switched);
+        assert_eq!(active(&manager), "Music");
+    }
+
+    #[test]
+    fn first_running_player_is_the_fallback() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let music = FakePlayer::new("Music", "paused");
+        let web = FakePlayer::new("Web", "playing");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+            (BackendKind::SpotifyWeb, &web),
+        ]);
+        manager.poll();
+
+        web.set(Err(PlayerError::PlayerNotRunning));
+        let poll = manager.poll();
+
+        assert!(poll.switched);
+        assert_eq!(active(&manager), "Spotify");
+        assert_eq!(poll.status.unwrap().track_name.as_deref(), Some("Spotify"));
+    }
+
+    #[test]
+    fn nothing_running_keeps_the_active_player() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let music = FakePlayer::new("Music", "playing");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.poll();
+
+        spotify.set(Err(PlayerError::PlayerNotRunning));
+        music.set(Err(PlayerError::PlayerNotRunning));
+        let poll = manager.poll();
+
+        assert!(!poll.switched);
+        assert_eq!(active(&manager), "Music");
+        assert_eq!(poll.status.unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn pinned_player_stays_active_while_it_runs() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let music = FakePlayer::new("Music", "playing");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.poll();
+
+        manager.pin(Some(BackendKind::Spotify)).unwrap();
+        assert!(manager.poll().switched);
+        assert_eq!(active(&manager), "Spotify");
+        let list = manager.list();
+        assert!(list[0].pinned && list[0].active && list[0].running);
+        assert!(list[1].playing && !list[1].active);
+
+        // A closed pinned player gives way until it comes back
+        spotify.set(Err(PlayerError::PlayerNotRunning));
+        manager.poll();
+        assert_eq!(active(&manager), "Music");
+        spotify.set(Ok("paused"));
+        manager.poll();
+        assert_eq!(active(&manager), "Spotify");
+
+        manager.pin(None).unwrap();
+        manager.poll();
+        assert_eq!(active(&manager), "Music");
+    }
+
+    #[test]
+    fn pinning_a_disabled_player_fails() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let manager = manager(&[(BackendKind::Spotify, &spotify)]);
+
+        assert!(manager.pin(Some(BackendKind::AppleMusic)).is_err());
+        assert!(!manager.list()[0].pinned);
+    }
+
+    #[test]
+    fn commands_reach_the_active_player() {
+        let spotify = FakePlayer::new("Spotify", "paused");
+        let music = FakePlayer::new("Music", "paused");
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.poll();
+        manager.get().toggle_playback().unwrap();
+
+        music.set(Ok("playing"));
+        manager.poll();
+        let active = manager.get();
+        active.next_track().unwrap();
+        active.previous_track().unwrap();
+        active.set_position(42.5).unwrap();
+        active.set_shuffle(true).unwrap();
+        active.set_repeat(RepeatMode::Track).unwrap();
+
+        assert_eq!(spotify.commands(), ["toggle"]);
+        assert_eq!(
+            music.commands(),
+            ["next", "previous", "seek", "shuffle", "repeat"]
+        );
+        assert_eq!(manager.active_info().id, BackendKind::AppleMusic);
+    }
+
+    #[test]
+    fn inactive_players_are_polled_at_the_background_interval() {
+        let spotify = FakePlayer::new("Spotify", "playing");
+        let music = FakePlayer::new("Music", "paused");
+        let mut manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.background_interval = Duration::from_secs(3600);
+
+        for _ in 0..3 {
+            manager.poll();
+        }
+
+        assert_eq!(spotify.polls(), 3);
+        assert_eq!(music.polls(), 1);
+
+        // A player starting in the background is noticed once its interval elapsed
+        music.set(Ok("playing"));
+        manager.poll();
+        assert_eq!(active(&manager), "Spotify");
+        manager.background_interval = Duration::ZERO;
+        manager.poll();
+        assert_eq!(active(&manager), "Music");
+    }
+
+    #[test]
+    fn players_are_watched_only_if_all_push_their_changes() {
+        let spotify = FakePlayer::new("Spotify", "playing");
+        let music = FakePlayer::new("Music", "paused");
+        let pushing = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        let reports = Arc::new(AtomicUsize::new(0));
+        let counter = reports.clone();
+
+        assert!(!pushing.is_watched());
+        pushing.watch(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        assert!(pushing.is_watched());
+        music.report_change();
+        assert_eq!(reports.load(Ordering::SeqCst), 1);
+
+        let polled = FakePlayer::polled_only("Spotify", "playing");
+        let mixed = manager(&[
+            (BackendKind::Spotify, &polled),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        mixed.watch(Arc::new(|| {}));
+        assert!(!mixed.is_watched());
+    }
+
+    #[test]
+    fn reported_change_polls_the_player_right_away() {
+        let spotify = FakePlayer::new("Spotify", "playing");
+        let music = FakePlayer::new("Music", "paused");
+        let mut manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.background_interval = Duration::from_secs(3600);
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let counter = wakes.clone();
+        manager.watch(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        manager.poll();
+
+        music.set(Ok("playing"));
+        music.report_change();
+        let poll = manager.poll();
+
+        assert_eq!(wakes.load(Ordering::SeqCst), 1);
+        assert_eq!(music.polls(), 2);
+        assert!(poll.switched);
+        assert_eq!(active(&manager), "Music");
+    }
+
+    #[test]
+    fn player_picked_from_a_background_poll_is_polled_again() {
+        let spotify = FakePlayer::new("Spotify", "playing");
+        let music = FakePlayer::new("Music", "paused");
+        let mut manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+        ]);
+        manager.poll();
+        manager.background_interval = Duration::from_secs(3600);
+
+        // Music is kept running while Spotify quits, but was not due this poll
+        spotify.set(Err(PlayerError::PlayerNotRunning));
+        let poll = manager.poll();
+
+        assert!(poll.switched);
+        assert_eq!(poll.status.unwrap().track_name.as_deref(), Some("Music"));
+        assert_eq!(music.polls(), 2);
+    }
+
+    #[test]
+    fn players_are_polled_in_parallel() {
+        let gate = Gate::new(3);
+        let spotify = FakePlayer::gated("Spotify", "playing", Some(gate.clone()));
+        let music = FakePlayer::gated("Music", "paused", Some(gate.clone()));
+        let web = FakePlayer::gated("Web", "paused", Some(gate.clone()));
+        let manager = manager(&[
+            (BackendKind::Spotify, &spotify),
+            (BackendKind::AppleMusic, &music),
+            (BackendKind::SpotifyWeb, &web),
+        ]);
+
+        manager.poll();
+
+        // Every poll was in flight while the others were
+        assert_eq!(gate.met.load(Ordering::SeqCst), 3);
+        assert_eq!(web.polls(), 1);
+    }
+}
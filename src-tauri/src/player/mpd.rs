@@ -0,0 +1,872 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use super::artwork::ArtworkCache;
+use super::record::leading_number;
+use super::{ChangeListener, MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::config::MpdConfig;
+use crate::params::{self, RepeatMode, SpotifyStatus, TrackMetadata};
+
+/// Subsystems whose changes are pushed to the change listener.
+const IDLE_COMMAND: &str = "idle player mixer options";
+
+/// MPD error codes for a wrong password or a command the password does not allow.
+const ACK_PASSWORD: u32 = 3;
+const ACK_PERMISSION: u32 = 4;
+
+/// Failure of an exchange with MPD.
+#[derive(Debug)]
+enum MpdError {
+    Io(io::Error),                      // The connection failed and must be reopened
+    Ack { code: u32, message: String }, // MPD rejected the command
+    Argument(String),                   // An argument cannot be sent, e.g. it spans lines
+}
+
+impl From<io::Error> for MpdError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Response to an MPD command: its `key: value` pairs and binary payload, if any.
+#[derive(Debug, Default)]
+struct Response {
+    pairs: Vec<(String, String)>, // Pairs in the order MPD sent them
+    binary: Vec<u8>,              // Payload of `albumart` and `readpicture`
+}
+
+impl Response {
+    /// Returns the first value of the given key.
+    fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Joins every value of a key that may repeat (e.g. `Artist`) with commas.
+    fn joined(&self, key: &str) -> Option<String> {
+        let values: Vec<&str> = self
+            .pairs
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+            .collect();
+        (!values.is_empty()).then(|| values.join(", "))
+    }
+
+    /// Parses the value of the given key.
+    fn parsed<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+}
+
+/// Connection to MPD speaking its line-based text protocol.
+struct MpdConnection {
+    reader: BufReader<TcpStream>, // Buffered socket, written through `get_mut`
+}
+
+impl MpdConnection {
+    /// Connects to MPD and logs in if a password is configured.
+    ///
+    /// `timeout` bounds every read and write.
+    fn open(config: &MpdConfig, timeout: Duration) -> Result<Self, MpdError> {
+        let stream = connect(config)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut connection = Self {
+            reader: BufReader::new(stream),
+        };
+        let greeting = connection.read_line()?;
+        if !greeting.starts_with("OK MPD ") {
+            return Err(invalid_data(format!("unexpected greeting {:?}", greeting)).into());
+        }
+        if let Some(password) = &config.password {
+            connection.command(&format!("password {}", quote(password)?))?;
+        }
+        Ok(connection)
+    }
+
+    /// Sends a command (or a command list) and reads its response.
+    fn command(&mut self, command: &str) -> Result<Response, MpdError> {
+        self.write(command)?;
+        self.read_response()
+    }
+
+    /// Sends a command without waiting for its response.
+    fn write(&mut self, command: &str) -> io::Result<()> {
+        self.reader
+            .get_mut()
+            .write_all(format!("{}\n", command).as_bytes())
+    }
+
+    /// Changes how long reads wait for MPD.
+    fn set_read_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.reader.get_ref().set_read_timeout(Some(timeout))
+    }
+
+    /// Reads the response to the last command sent.
+    fn read_response(&mut self) -> Result<Response, MpdError> {
+        let mut response = Response::default();
+        loop {
+            let line = self.read_line()?;
+            if line == "OK" {
+                return Ok(response);
+            }
+            if let Some(ack) = line.strip_prefix("ACK ") {
+                return Err(parse_ack(ack));
+            }
+
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| invalid_data(format!("unexpected line {:?}", line)))?;
+            if key == "binary" {
+                // The payload is followed by a newline of its own
+                let length = value
+                    .parse()
+                    .map_err(|_| invalid_data(format!("invalid binary length {:?}", value)))?;
+                response.binary = vec![0; length];
+                self.reader.read_exact(&mut response.binary)?;
+                self.reader.read_exact(&mut [0; 1])?;
+            } else {
+                response.pairs.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+
+    /// Reads a line, without its newline.
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed by MPD",
+            ));
+        }
+        line.truncate(line.trim_end_matches('\n').len());
+        Ok(line)
+    }
+}
+
+/// Backend controlling MPD (Music Player Daemon) over its TCP protocol.
+///
+/// Commands share one connection, reopened when MPD drops it. Changes are pushed
+/// through a second connection parked in `idle`, so the status thread polls right
+/// away instead of waiting for its next interval.
+pub struct MpdPlayer {
+    config: MpdConfig,                        // Where and how to connect
+    connection: Mutex<Option<MpdConnection>>, // Connection used by status and commands
+    artwork: ArtworkCache,                    // Artwork of the last seen song
+    idle_timeout: Duration, // Time the change watcher idles before checking on MPD
+}
+
+impl MpdPlayer {
+    /// Creates a backend connecting to MPD as configured; nothing is opened until used.
+    pub fn new(config: MpdConfig) -> Self {
+        Self {
+            config,
+            connection: Mutex::new(None),
+            artwork: ArtworkCache::new("mpd"),
+            idle_timeout: params::MPD_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Runs an exchange on the shared connection, opening it if needed.
+    ///
+    /// MPD closes connections idle for too long, so an exchange failing on a reused
+    /// connection is retried once on a new one.
+    fn run<T>(
+        &self,
+        exchange: impl Fn(&mut MpdConnection) -> Result<T, MpdError>,
+    ) -> Result<T, PlayerError> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(reused) = connection.as_mut() {
+            match exchange(reused) {
+                Err(MpdError::Io(_)) => *connection = None,
+                result => return result.map_err(mpd_error),
+            }
+        }
+
+        let opened = connection.insert(self.connect().map_err(mpd_error)?);
+        let result = exchange(opened);
+        if let Err(MpdError::Io(_)) = result {
+            *connection = None;
+        }
+        result.map_err(mpd_error)
+    }
+
+    /// Opens a command connection, raising the artwork chunk size where MPD allows it.
+    fn connect(&self) -> Result<MpdConnection, MpdError> {
+        let mut connection = MpdConnection::open(&self.config, params::MPD_TIMEOUT)?;
+        // Servers older than 0.22.4 lack `binarylimit` and keep their default chunk size
+        match connection.command(&format!("binarylimit {}", params::MPD_BINARY_LIMIT)) {
+            Err(MpdError::Io(e)) => Err(MpdError::Io(e)),
+            _ => Ok(connection),
+        }
+    }
+
+    /// Runs a command whose response is not needed.
+    fn send(&self, command: &str) -> Result<(), PlayerError> {
+        self.run(|connection| connection.command(command).map(|_| ()))
+    }
+}
+
+impl MediaPlayer for MpdPlayer {
+    fn name(&self) -> &str {
+        "MPD"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: true,
+            can_shuffle: true,
+            can_repeat: true,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        let (status, song) = self.run(|connection| {
+            Ok((
+                connection.command("status")?,
+                connection.command("currentsong")?,
+            ))
+        })?;
+
+        let mut status = parse_status(&status, &song);
+        status.album_cover = song.get("file").and_then(|file| {
+            self.artwork.resolve(file, || {
+                self.run(|connection| read_artwork(connection, file))
+            })
+        });
+        Ok(status)
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        self.run(|connection| {
+            let status = connection.command("status")?;
+            let command = match status.get("state") {
+                Some("play") => "pause 1",
+                Some("pause") => "pause 0",
+                // A stopped player has nothing to resume
+                _ => "play",
+            };
+            connection.command(command).map(|_| ())
+        })
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.send("next")
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.send("previous")
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        self.send(&format!("seekcur {:.3}", position.max(0.0)))
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        self.send(&format!("setvol {}", level.min(100)))
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        self.send(&format!("random {}", u8::from(enabled)))
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        // MPD repeats the current song with both `repeat` and `single` enabled
+        let (repeat, single) = match mode {
+            RepeatMode::Off => (0, 0),
+            RepeatMode::Track => (1, 1),
+            RepeatMode::Context => (1, 0),
+        };
+        self.send(&format!(
+            "command_list_begin\nrepeat {}\nsingle {}\ncommand_list_end",
+            repeat, single
+        ))
+    }
+
+    fn watch(&self, listener: ChangeListener) -> bool {
+        let config = self.config.clone();
+        let idle_timeout = self.idle_timeout;
+        thread::spawn(move || loop {
+            if let Err(e) = watch_changes(&config, &listener, idle_timeout) {
+                log::debug!("MPD change watcher disconnected: {}", mpd_error(e));
+            }
+            thread::sleep(params::MPD_RECONNECT_INTERVAL);
+        });
+        true
+    }
+}
+
+/// Idles on a dedicated connection and calls the listener whenever MPD reports a
+/// change, until the connection fails.
+///
+/// A half-open connection would leave the watcher idling forever, so after
+/// `idle_timeout` without changes the idle is cancelled with `noidle`, which MPD
+/// has to answer within the usual timeout, and then started again.
+fn watch_changes(
+    config: &MpdConfig,
+    listener: &ChangeListener,
+    idle_timeout: Duration,
+) -> Result<(), MpdError> {
+    let mut connection = MpdConnection::open(config, params::MPD_TIMEOUT)?;
+    // MPD may just have come up, so report it right away
+    listener();
+    loop {
+        connection.write(IDLE_COMMAND)?;
+        connection.set_read_timeout(idle_timeout)?;
+        let changes = match connection.read_response() {
+            Err(MpdError::Io(e)) if is_timeout(&e) => {
+                connection.set_read_timeout(params::MPD_TIMEOUT)?;
+                connection.command("noidle")?
+            }
+            result => result?,
+        };
+        if changes.get("changed").is_some() {
+            listener();
+        }
+    }
+}
+
+/// Maps the `status` and `currentsong` responses onto the status model, without
+/// the album cover.
+fn parse_status(status: &Response, song: &Response) -> SpotifyStatus {
+    let player_state = match status.get("state") {
+        Some("play") => "playing",
+        Some("pause") => "paused",
+        _ => "stopped",
+    };
+
+    // Streams have no title but may name the station instead
+    let track_name = song.get("Title").or_else(|| song.get("Name"));
+    let duration = status
+        .parsed::<f64>("duration")
+        .or_else(|| song.parsed("duration"))
+        .or_else(|| song.parsed("Time"));
+
+    SpotifyStatus {
+        track_name: track_name.map(str::to_string),
+        artist_name: song.joined("Artist"),
+        metadata: TrackMetadata {
+            track_id: song.get("Id").map(str::to_string),
+            uri: song.get("file").map(str::to_string),
+            album_name: song.get("Album").map(str::to_string),
+            album_artist: song.joined("AlbumArtist"),
+            disc_number: song.get("Disc").and_then(leading_number),
+            track_number: song.get("Track").and_then(leading_number),
+            explicit: None,
+            popularity: None,
+        },
+        // MPD reports -1 when it has no mixer
+        track_volume: status
+            .parsed::<i32>("volume")
+            .and_then(|volume| u32::try_from(volume).ok()),
+        position: status.parsed("elapsed"),
+        track_duration: duration,
+        album_cover: None,
+        player_state: Some(player_state.to_string()),
+        shuffle: status.get("random").map(|random| random == "1"),
+        repeat_mode: match (status.get("repeat"), status.get("single")) {
+            (Some("1"), Some("1")) => Some(RepeatMode::Track),
+            (Some("1"), _) => Some(RepeatMode::Context),
+            (Some(_), _) => Some(RepeatMode::Off),
+            _ => None,
+        },
+        is_saved: None,
+        device: None,
+        error: None,
+    }
+}
+
+/// Reads the artwork of a song, from its folder (`albumart`) or embedded in the
+/// file (`readpicture`), `None` if it has none.
+fn read_artwork(connection: &mut MpdConnection, file: &str) -> Result<Option<Vec<u8>>, MpdError> {
+    for command in ["albumart", "readpicture"] {
+        match read_binary(connection, command, file) {
+            Ok(Some(data)) => return Ok(Some(data)),
+            Ok(None) => {}
+            // Missing artwork, or a server too old to know the command
+            Err(MpdError::Ack { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Reads a binary payload chunk by chunk, `None` if MPD sent none.
+fn read_binary(
+    connection: &mut MpdConnection,
+    command: &str,
+    file: &str,
+) -> Result<Option<Vec<u8>>, MpdError> {
+    let mut data = Vec::new();
+    loop {
+        let response =
+            connection.command(&format!("{} {} {}", command, quote(file)?, data.len()))?;
+        let size: usize = match response.parsed("size") {
+            Some(size) if !response.binary.is_empty() => size,
+            _ => return Ok(None),
+        };
+        data.extend_from_slice(&response.binary);
+        if data.len() >= size {
+            return Ok(Some(data));
+        }
+    }
+}
+
+/// Connects to the configured host, trying each of its addresses in turn.
+fn connect(config: &MpdConfig) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in (config.host.as_str(), config.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, params::MPD_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host has no address")))
+}
+
+/// Quotes a command argument, escaping backslashes and double quotes.
+///
+/// Control characters are rejected: a newline would end the command early and
+/// send the rest of the argument as a command of its own.
+fn quote(argument: &str) -> Result<String, MpdError> {
+    if argument.chars().any(char::is_control) {
+        // The argument may be the password, so it is not part of the message
+        return Err(MpdError::Argument(
+            "argument contains control characters".to_string(),
+        ));
+    }
+    let escaped = argument.replace('\\', "\\\\").replace('"', "\\\"");
+    Ok(format!("\"{}\"", escaped))
+}
+
+/// Parses an error line such as `[50@0] {albumart} No file exists`.
+fn parse_ack(ack: &str) -> MpdError {
+    let code = ack
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once('@'))
+        .and_then(|(code, _)| code.parse().ok())
+        .unwrap_or(0);
+    let message = ack
+        .split_once("} ")
+        .map_or(ack, |(_, message)| message)
+        .to_string();
+    MpdError::Ack { code, message }
+}
+
+/// Returns whether a read failed because MPD did not answer in time.
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Creates the error reported for output that does not follow the protocol.
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Maps a failed exchange to a `PlayerError`, treating a refused connection as a stopped MPD.
+fn mpd_error(error: MpdError) -> PlayerError {
+    match error {
+        MpdError::Io(e) => match e.kind() {
+            io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound => {
+                PlayerError::PlayerNotRunning
+            }
+            _ if is_timeout(&e) => PlayerError::Timeout,
+            io::ErrorKind::InvalidData => PlayerError::parse(e.to_string()),
+            _ => PlayerError::backend(e),
+        },
+        MpdError::Ack { code, .. } if code == ACK_PASSWORD || code == ACK_PERMISSION => {
+            PlayerError::NotAuthenticated
+        }
+        MpdError::Ack { message, .. } => PlayerError::backend(message),
+        MpdError::Argument(message) => PlayerError::invalid_argument(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::player::testing::TempDir;
+
+    /// MPD stand-in on a loopback port, answering each command with a callback.
+    struct FakeMpd {
+        port: u16,                         // Port the stand-in listens on
+        commands: Arc<Mutex<Vec<String>>>, // Commands received, command lists as sent
+    }
+
+    impl FakeMpd {
+        /// Starts the stand-in. `answer` returns the response to a command, including
+        /// the final `OK` or `ACK` line, or `None` to close the connection.
+        fn start(answer: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let commands = Arc::new(Mutex::new(Vec::new()));
+            let answer = Arc::new(answer);
+
+            let received = commands.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let answer = answer.clone();
+                    let received = received.clone();
+                    thread::spawn(move || {
+                        let mut writer = stream.try_clone().unwrap();
+                        let mut reader = BufReader::new(stream);
+                        writer.write_all(b"OK MPD 0.23.5\n").unwrap();
+                        while let Some(command) = read_command(&mut reader) {
+                            received.lock().unwrap().push(command.clone());
+                            match answer(&command) {
+                                Some(response) => writer.write_all(response.as_bytes()).unwrap(),
+                                None => return,
+                            }
+                        }
+                    });
+                }
+            });
+
+            Self { port, commands }
+        }
+
+        /// Returns a backend connecting to the stand-in.
+        fn player(&self, password: Option<&str>) -> MpdPlayer {
+            MpdPlayer::new(MpdConfig {
+                host: "127.0.0.1".to_string(),
+                port: self.port,
+                password: password.map(str::to_string),
+            })
+        }
+
+        /// Returns the commands received so far, without the connection setup.
+        fn commands(&self) -> Vec<String> {
+            self.commands
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|command| !command.starts_with("binarylimit"))
+                .cloned()
+                .collect()
+        }
+    }
+
+    /// Reads a command, or a whole command list, `None` once the client is gone.
+    fn read_command(reader: &mut BufReader<TcpStream>) -> Option<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return None;
+            }
+            let line = line.trim_end_matches('\n').to_string();
+            let done =
+                lines.is_empty() && line != "command_list_begin" || line == "command_list_end";
+            lines.push(line);
+            if done {
+                return Some(lines.join("\n"));
+            }
+        }
+    }
+
+    const STATUS: &str = "volume: 42\nrepeat: 1\nrandom: 1\nsingle: 0\nstate: play\nsong: 2\nsongid: 7\nelapsed: 12.500\nduration: 200.000\nOK\n";
+    const CURRENT_SONG: &str = "file: music/a \"b\".flac\nArtist: X\nArtist: Y\nAlbumArtist: X\nTitle: Song\nAlbum: Album\nDisc: 1/2\nTrack: 03/12\nId: 7\nOK\n";
+
+    /// Answers the status of a playing song without artwork.
+    fn playing(command: &str) -> Option<String> {
+        let response = match command {
+            "status" => STATUS,
+            "currentsong" => CURRENT_SONG,
+            _ if command.starts_with("albumart") || command.starts_with("readpicture") => {
+                "ACK [50@0] {albumart} No file exists\n"
+            }
+            _ => "OK\n",
+        };
+        Some(response.to_string())
+    }
+
+    #[test]
+    fn status_is_parsed() {
+        let mpd = FakeMpd::start(playing);
+        let status = mpd.player(None).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("X, Y"));
+        assert_eq!(status.metadata.album_name.as_deref(), Some("Album"));
+        assert_eq!(status.metadata.album_artist.as_deref(), Some("X"));
+        assert_eq!(status.metadata.disc_number, Some(1));
+        assert_eq!(status.metadata.track_number, Some(3));
+        assert_eq!(status.metadata.track_id.as_deref(), Some("7"));
+        assert_eq!(status.metadata.uri.as_deref(), Some("music/a \"b\".flac"));
+        assert_eq!(status.track_volume, Some(42));
+        assert_eq!(status.position, Some(12.5));
+        assert_eq!(status.track_duration, Some(200.0));
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(true));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Context));
+        assert_eq!(status.album_cover, None);
+        assert_eq!(
+            mpd.commands(),
+            [
+                "status",
+                "currentsong",
+                r#"albumart "music/a \"b\".flac" 0"#,
+                r#"readpicture "music/a \"b\".flac" 0"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_without_mixer_is_parsed() {
+        let mpd = FakeMpd::start(|command| {
+            let response = match command {
+                "status" => "volume: -1\nrepeat: 1\nsingle: 1\nrandom: 0\nstate: pause\nelapsed: 61.000\nOK\n",
+                "currentsong" => "file: http://radio.example/stream\nName: Jazz Radio\nOK\n",
+                _ => "ACK [50@0] {albumart} No file exists\n",
+            };
+            Some(response.to_string())
+        });
+        let status = mpd.player(None).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Jazz Radio"));
+        assert_eq!(status.track_volume, None);
+        assert_eq!(status.track_duration, None);
+        assert_eq!(status.player_state.as_deref(), Some("paused"));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Track));
+    }
+
+    #[test]
+    fn artwork_is_read_in_chunks_once_per_song() {
+        let mpd = FakeMpd::start(|command| {
+            let response = match command {
+                "status" => STATUS,
+                "currentsong" => "file: music/chunked.flac\nTitle: Song\nOK\n",
+                r#"albumart "music/chunked.flac" 0"# => "ACK [50@0] {albumart} No file exists\n",
+                r#"readpicture "music/chunked.flac" 0"# => "size: 6\nbinary: 4\nABCD\nOK\n",
+                r#"readpicture "music/chunked.flac" 4"# => "size: 6\nbinary: 2\nEF\nOK\n",
+                _ => "OK\n",
+            };
+            Some(response.to_string())
+        });
+        let artwork = TempDir::new("mpd-artwork");
+        let player = MpdPlayer {
+            artwork: ArtworkCache::with_dir("mpd", artwork.path.clone()),
+            ..mpd.player(None)
+        };
+
+        let cover = player.status().unwrap().album_cover.unwrap();
+        let name = cover.rsplit("%2F").next().unwrap();
+        assert!(name.starts_with("mpd-"));
+        assert_eq!(fs::read(artwork.path.join(name)).unwrap(), b"ABCDEF");
+
+        assert_eq!(player.status().unwrap().album_cover, Some(cover));
+        let reads = mpd
+            .commands()
+            .iter()
+            .filter(|command| command.starts_with("readpicture"))
+            .count();
+        assert_eq!(reads, 2);
+    }
+
+    #[test]
+    fn commands_are_sent() {
+        let mpd = FakeMpd::start(playing);
+        let player = mpd.player(None);
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.set_position(30.25).unwrap();
+        player.set_volume(150).unwrap();
+        player.set_shuffle(false).unwrap();
+        player.set_repeat(RepeatMode::Track).unwrap();
+
+        assert_eq!(
+            mpd.commands(),
+            [
+                "status",
+                "pause 1",
+                "next",
+                "seekcur 30.250",
+                "setvol 100",
+                "random 0",
+                "command_list_begin\nrepeat 1\nsingle 1\ncommand_list_end",
+            ]
+        );
+    }
+
+    #[test]
+    fn password_is_quoted() {
+        let mpd = FakeMpd::start(playing);
+        mpd.player(Some(r#"p"a\ss"#)).next_track().unwrap();
+
+        assert_eq!(mpd.commands(), [r#"password "p\"a\\ss""#, "next"]);
+    }
+
+    #[test]
+    fn wrong_password_is_not_authenticated() {
+        let mpd = FakeMpd::start(|command| {
+            let response = match command {
+                _ if command.starts_with("password") => "ACK [3@0] {password} incorrect password\n",
+                _ => "OK\n",
+            };
+            Some(response.to_string())
+        });
+
+        assert_eq!(
+            mpd.player(Some("wrong")).next_track(),
+            Err(PlayerError::NotAuthenticated)
+        );
+    }
+
+    #[test]
+    fn control_characters_are_not_sent() {
+        let mpd = FakeMpd::start(playing);
+
+        assert!(matches!(
+            mpd.player(Some("secret\nkill")).next_track(),
+            Err(PlayerError::InvalidArgument { .. })
+        ));
+        assert!(mpd.commands().is_empty());
+        assert!(quote("a\tb").is_err());
+        assert!(quote("a\rb").is_err());
+        assert_eq!(quote("Sigur Rós – ( )").unwrap(), "\"Sigur Rós – ( )\"");
+    }
+
+    #[test]
+    fn dropped_connection_is_reopened_once() {
+        let statuses = AtomicUsize::new(0);
+        let mpd = FakeMpd::start(move |command| match command {
+            // MPD closed the idle connection before the second poll
+            "status" if statuses.fetch_add(1, Ordering::SeqCst) == 1 => None,
+            _ => playing(command),
+        });
+        let player = mpd.player(None);
+
+        player.status().unwrap();
+        player.status().unwrap();
+        let statuses = mpd
+            .commands()
+            .iter()
+            .filter(|command| *command == "status")
+            .count();
+        assert_eq!(statuses, 3);
+    }
+
+    #[test]
+    fn closed_port_is_not_running() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let player = MpdPlayer::new(MpdConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            password: None,
+        });
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn changes_are_pushed_from_idle() {
+        let idles = AtomicUsize::new(0);
+        let mpd = FakeMpd::start(move |command| {
+            if !command.starts_with("idle") {
+                return playing(command);
+            }
+            match idles.fetch_add(1, Ordering::SeqCst) {
+                0 => Some("changed: player\nchanged: mixer\nOK\n".to_string()),
+                // Park the watcher until the test is over
+                _ => {
+                    thread::sleep(Duration::from_secs(60));
+                    None
+                }
+            }
+        });
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = changes.clone();
+
+        assert!(mpd.player(None).watch(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })));
+
+        // Once when connected, once for the change
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while changes.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(changes.load(Ordering::SeqCst), 2);
+        assert!(mpd.commands().contains(&IDLE_COMMAND.to_string()));
+    }
+
+    #[test]
+    fn quiet_idle_is_cancelled_and_started_again() {
+        let mpd = FakeMpd::start(|command| match command {
+            // Nothing changes, so MPD only answers the idle once it is cancelled
+            IDLE_COMMAND => Some(String::new()),
+            _ => playing(command),
+        });
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = changes.clone();
+        let player = MpdPlayer {
+            idle_timeout: Duration::from_millis(20),
+            ..mpd.player(None)
+        };
+
+        assert!(player.watch(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })));
+
+        let rearmed = || {
+            let commands = mpd.commands();
+            commands.len() >= 4 && commands[..4] == [IDLE_COMMAND, "noidle", IDLE_COMMAND, "noidle"]
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !rearmed() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(rearmed());
+        // Only the connection was reported, cancelling is not a change
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn unanswered_noidle_drops_the_watcher_connection() {
+        let mpd = FakeMpd::start(|command| match command {
+            // A half-open connection: nothing comes back any more
+            IDLE_COMMAND | "noidle" => Some(String::new()),
+            _ => playing(command),
+        });
+        let config = mpd.player(None).config;
+
+        let result = watch_changes(
+            &config,
+            &(Arc::new(|| {}) as ChangeListener),
+            Duration::from_millis(20),
+        );
+        assert!(matches!(result, Err(MpdError::Io(e)) if is_timeout(&e)));
+        assert_eq!(mpd.commands(), [IDLE_COMMAND, "noidle"]);
+    }
+}
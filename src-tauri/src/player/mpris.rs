@@ -0,0 +1,882 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use zbus::blocking::{fdo::DBusProxy, proxy, Connection, Proxy};
+use zbus::fdo;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+use super::{MediaPlayer, PlayerCapabilities, PlayerError, SpotifyUri};
+use crate::params::{self, QueueTrack, RepeatMode, SpotifyStatus, TrackMetadata};
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const MPRIS_ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const MPRIS_TRACKLIST_INTERFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+const MPRIS_NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// Backend controlling any MPRIS2 compatible player over the D-Bus session bus (Linux).
+pub struct MprisPlayer {
+    connection: Option<Connection>, // Session bus connection, `None` if the bus is unavailable
+    bus_name: Option<String>,       // Bus name of the player to control, discovered if `None`
+    discovered: Mutex<Option<String>>, // Player found by the last discovery, if not bound
+}
+
+impl MprisPlayer {
+    /// Creates a backend on the session bus that follows the first MPRIS player found,
+    /// preferring one that is currently playing.
+    pub fn new() -> Self {
+        Self {
+            connection: Connection::session().ok(),
+            bus_name: None,
+            discovered: Mutex::new(None),
+        }
+    }
+
+    /// Creates a backend on the given connection, optionally bound to a specific
+    /// bus name (e.g. `org.mpris.MediaPlayer2.spotify`).
+    pub fn with_connection(connection: Connection, bus_name: Option<String>) -> Self {
+        Self {
+            connection: Some(connection),
+            bus_name,
+            discovered: Mutex::new(None),
+        }
+    }
+
+    /// Returns a proxy to the `Player` interface of the controlled player.
+    fn player_proxy(&self) -> Result<Proxy<'static>, PlayerError> {
+        self.proxy(MPRIS_PLAYER_INTERFACE)
+    }
+
+    /// Returns a proxy to the `TrackList` interface, `Unsupported` if the player has none.
+    fn tracklist_proxy(&self) -> Result<Proxy<'static>, PlayerError> {
+        let has_tracklist = self
+            .proxy(MPRIS_ROOT_INTERFACE)?
+            .get_property::<bool>("HasTrackList")
+            .unwrap_or(false);
+        if !has_tracklist {
+            return Err(PlayerError::unsupported("queue"));
+        }
+        self.proxy(MPRIS_TRACKLIST_INTERFACE)
+    }
+
+    /// Returns a proxy to an interface of the controlled player.
+    fn proxy(&self, interface: &'static str) -> Result<Proxy<'static>, PlayerError> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or_else(|| PlayerError::backend("D-Bus session bus is not available"))?;
+        let bus_name = match &self.bus_name {
+            Some(name) => name.clone(),
+            None => {
+                let mut discovered = self.discovered();
+                match discovered.as_ref() {
+                    Some(name) => name.clone(),
+                    None => discovered.insert(discover_player(connection)?).clone(),
+                }
+            }
+        };
+
+        player_proxy(connection, bus_name, interface).map_err(dbus_error)
+    }
+
+    /// Runs an operation on the controlled player. If a previously discovered
+    /// player vanished in the meantime, the player is discovered again and the
+    /// operation retried once.
+    fn retry<T>(&self, operation: impl Fn() -> Result<T, PlayerError>) -> Result<T, PlayerError> {
+        let was_discovered = self.discovered().is_some();
+        match operation() {
+            Err(PlayerError::PlayerNotRunning) if was_discovered => {
+                *self.discovered() = None;
+                operation()
+            }
+            result => result,
+        }
+    }
+
+    /// Locks the discovered bus name, recovering from a poisoned lock.
+    fn discovered(&self) -> MutexGuard<'_, Option<String>> {
+        self.discovered.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reads all properties of the `Player` interface in a single call.
+    fn player_properties(&self) -> Result<HashMap<String, OwnedValue>, PlayerError> {
+        self.proxy(PROPERTIES_INTERFACE)?
+            .call_method("GetAll", &(MPRIS_PLAYER_INTERFACE,))
+            .and_then(|reply| reply.body().deserialize())
+            .map_err(dbus_error)
+    }
+
+    /// Calls a method on the `Player` interface and maps the error to a `PlayerError`.
+    fn call<B>(&self, method: &str, body: &B) -> Result<(), PlayerError>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        self.retry(|| {
+            self.player_proxy()?
+                .call_method(method, body)
+                .map(|_| ())
+                .map_err(dbus_error)
+        })
+    }
+
+    /// Seeks within the current track.
+    fn seek(&self, position: f64) -> Result<(), PlayerError> {
+        let proxy = self.player_proxy()?;
+        let metadata: HashMap<String, OwnedValue> =
+            proxy.get_property("Metadata").map_err(dbus_error)?;
+
+        // SetPosition is ignored by players unless it names the current track
+        let track_id = match metadata.get("mpris:trackid").map(|v| &**v) {
+            Some(Value::ObjectPath(path)) => path.to_owned(),
+            Some(Value::Str(path)) => ObjectPath::try_from(path.as_str())
+                .map_err(|e| PlayerError::parse(format!("Invalid track id: {}", e)))?
+                .into_owned(),
+            _ => return Err(PlayerError::parse("Current track has no track id")),
+        };
+        let position = (position.max(0.0) * 1_000_000.0) as i64;
+
+        proxy
+            .call_method("SetPosition", &(track_id, position))
+            .map(|_| ())
+            .map_err(dbus_error)
+    }
+}
+
+impl Default for MprisPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaPlayer for MprisPlayer {
+    fn name(&self) -> &str {
+        "MPRIS"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        // Polled often, so all flags are fetched in one round trip
+        let properties = self.player_properties().unwrap_or_default();
+        let flag = |property: &str| {
+            matches!(
+                properties.get(property).map(|v| &**v),
+                Some(Value::Bool(true))
+            )
+        };
+
+        PlayerCapabilities {
+            can_play_pause: flag("CanPause") || flag("CanPlay"),
+            can_go_next: flag("CanGoNext"),
+            can_go_previous: flag("CanGoPrevious"),
+            can_seek: flag("CanSeek"),
+            can_set_volume: flag("CanControl"),
+            can_shuffle: flag("CanControl"),
+            can_repeat: flag("CanControl"),
+            can_play_uri: flag("CanControl"),
+            can_save: false,
+            can_queue: self
+                .proxy(MPRIS_ROOT_INTERFACE)
+                .ok()
+                .and_then(|p| p.get_property::<bool>("HasTrackList").ok())
+                .unwrap_or(false),
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        // Discover the player once per poll, so that another player starting to play
+        // is picked up; commands and reads in between reuse the result
+        *self.discovered() = None;
+        let proxy = self.player_proxy()?;
+        let metadata: HashMap<String, OwnedValue> =
+            proxy.get_property("Metadata").map_err(dbus_error)?;
+        let playback_status: String = proxy.get_property("PlaybackStatus").map_err(dbus_error)?;
+
+        // Volume and position are optional in the spec, so missing values are tolerated
+        let volume = proxy.get_property::<f64>("Volume").ok();
+        let position = proxy.get_property::<i64>("Position").ok();
+        let shuffle = proxy.get_property::<bool>("Shuffle").ok();
+        let loop_status = proxy.get_property::<String>("LoopStatus").ok();
+
+        let track = map_track(&metadata);
+        Ok(SpotifyStatus {
+            track_name: track.track_name,
+            artist_name: track.artist_name,
+            metadata: track.metadata,
+            track_volume: volume.map(|v| (v.clamp(0.0, 1.0) * 100.0).round() as u32),
+            position: position.map(micros_to_seconds),
+            track_duration: track.track_duration,
+            album_cover: track.album_cover,
+            player_state: Some(playback_status.to_lowercase()),
+            shuffle,
+            repeat_mode: loop_status.as_deref().and_then(repeat_mode),
+            is_saved: None,
+            device: None,
+            error: None,
+        })
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        self.call("PlayPause", &())
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.call("Next", &())
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.call("Previous", &())
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        self.retry(|| self.seek(position))
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        self.retry(|| {
+            self.player_proxy()?
+                .set_property("Volume", level as f64 / 100.0)
+                .map_err(dbus_error)
+        })
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        self.retry(|| {
+            self.player_proxy()?
+                .set_property("Shuffle", enabled)
+                .map_err(dbus_error)
+        })
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        let loop_status = match mode {
+            RepeatMode::Off => "None",
+            RepeatMode::Track => "Track",
+            RepeatMode::Context => "Playlist",
+        };
+        self.retry(|| {
+            self.player_proxy()?
+                .set_property("LoopStatus", loop_status)
+                .map_err(dbus_error)
+        })
+    }
+
+    fn play_uri(&self, uri: &SpotifyUri) -> Result<(), PlayerError> {
+        self.call("OpenUri", &(uri.to_string(),))
+    }
+
+    fn queue(&self) -> Result<Vec<QueueTrack>, PlayerError> {
+        let tracklist = self.tracklist_proxy()?;
+        let tracks: Vec<OwnedObjectPath> = tracklist.get_property("Tracks").map_err(dbus_error)?;
+        let metadata: HashMap<String, OwnedValue> = self
+            .player_proxy()?
+            .get_property("Metadata")
+            .map_err(dbus_error)?;
+
+        // Only the tracks after the current one are upcoming
+        let current = metadata_track_id(&metadata);
+        let start = tracks
+            .iter()
+            .position(|track| Some(track.as_str()) == current.as_deref())
+            .map_or(0, |index| index + 1);
+        let upcoming: Vec<OwnedObjectPath> = tracks
+            .into_iter()
+            .skip(start)
+            .take(params::QUEUE_MAX_LENGTH)
+            .collect();
+        if upcoming.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let reply = tracklist
+            .call_method("GetTracksMetadata", &(upcoming,))
+            .map_err(dbus_error)?;
+        let tracks: Vec<HashMap<String, OwnedValue>> = reply
+            .body()
+            .deserialize()
+            .map_err(|e| PlayerError::parse(e.to_string()))?;
+        Ok(tracks.iter().map(map_track).collect())
+    }
+
+    fn add_to_queue(&self, uri: &SpotifyUri) -> Result<(), PlayerError> {
+        let tracklist = self.tracklist_proxy()?;
+        let tracks: Vec<OwnedObjectPath> = tracklist.get_property("Tracks").map_err(dbus_error)?;
+        let last = match tracks.last() {
+            Some(track) => track.clone(),
+            None => ObjectPath::try_from(MPRIS_NO_TRACK)
+                .map_err(|e| PlayerError::parse(e.to_string()))?
+                .into(),
+        };
+
+        tracklist
+            .call_method("AddTrack", &(uri.to_string(), last, false))
+            .map(|_| ())
+            .map_err(dbus_error)
+    }
+}
+
+/// Maps MPRIS metadata onto the track model.
+fn map_track(metadata: &HashMap<String, OwnedValue>) -> QueueTrack {
+    QueueTrack {
+        track_name: metadata_str(metadata, "xesam:title"),
+        artist_name: metadata_list(metadata, "xesam:artist"),
+        track_duration: metadata_int(metadata, "mpris:length").map(micros_to_seconds),
+        album_cover: metadata_str(metadata, "mpris:artUrl"),
+        metadata: TrackMetadata {
+            track_id: metadata_track_id(metadata),
+            uri: metadata_str(metadata, "xesam:url"),
+            album_name: metadata_str(metadata, "xesam:album"),
+            album_artist: metadata_list(metadata, "xesam:albumArtist"),
+            disc_number: metadata_int(metadata, "xesam:discNumber")
+                .and_then(|n| u32::try_from(n).ok()),
+            track_number: metadata_int(metadata, "xesam:trackNumber")
+                .and_then(|n| u32::try_from(n).ok()),
+            explicit: None,
+            popularity: None,
+        },
+    }
+}
+
+/// Finds the bus name of an MPRIS player, preferring one that is currently playing.
+fn discover_player(connection: &Connection) -> Result<String, PlayerError> {
+    let names = DBusProxy::new(connection)
+        .map_err(dbus_error)?
+        .list_names()
+        .map_err(dbus_error)?;
+    let players: Vec<String> = names
+        .into_iter()
+        .map(|name| name.to_string())
+        .filter(|name| name.starts_with(MPRIS_PREFIX))
+        .collect();
+
+    let is_playing = |name: &String| {
+        player_proxy(connection, name.clone(), MPRIS_PLAYER_INTERFACE)
+            .and_then(|p| p.get_property::<String>("PlaybackStatus"))
+            .map(|s| s == "Playing")
+            .unwrap_or(false)
+    };
+
+    players
+        .iter()
+        .find(|name| is_playing(name))
+        .or_else(|| players.first())
+        .cloned()
+        .ok_or(PlayerError::PlayerNotRunning)
+}
+
+/// Creates a proxy to an interface of a player.
+///
+/// Properties are not cached: the proxies are short-lived, and caching would
+/// fetch all properties of the interface on the first read.
+fn player_proxy(
+    connection: &Connection,
+    bus_name: String,
+    interface: &'static str,
+) -> zbus::Result<Proxy<'static>> {
+    proxy::Builder::new(connection)
+        .destination(bus_name)?
+        .path(MPRIS_PATH)?
+        .interface(interface)?
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+/// Maps a D-Bus error to a `PlayerError`, treating a vanished bus name as a stopped player.
+fn dbus_error(error: impl Into<fdo::Error>) -> PlayerError {
+    // Property reads return the error of the `Properties` call wrapped in a `zbus::Error`
+    let error = match error.into() {
+        fdo::Error::ZBus(zbus::Error::FDO(error)) => *error,
+        error => error,
+    };
+    match error {
+        fdo::Error::ServiceUnknown(_) | fdo::Error::NameHasNoOwner(_) => {
+            PlayerError::PlayerNotRunning
+        }
+        fdo::Error::NoReply(_) | fdo::Error::Timeout(_) | fdo::Error::TimedOut(_) => {
+            PlayerError::Timeout
+        }
+        // Optional properties such as `Shuffle` are missing or read-only on some players
+        fdo::Error::NotSupported(message)
+        | fdo::Error::UnknownMethod(message)
+        | fdo::Error::UnknownProperty(message)
+        | fdo::Error::UnknownInterface(message)
+        | fdo::Error::PropertyReadOnly(message) => PlayerError::unsupported(message),
+        error => PlayerError::backend(error),
+    }
+}
+
+/// Maps an MPRIS `LoopStatus` to a repeat mode.
+fn repeat_mode(loop_status: &str) -> Option<RepeatMode> {
+    match loop_status {
+        "None" => Some(RepeatMode::Off),
+        "Track" => Some(RepeatMode::Track),
+        "Playlist" => Some(RepeatMode::Context),
+        _ => None,
+    }
+}
+
+/// Reads a string entry from MPRIS metadata.
+fn metadata_str(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match metadata.get(key).map(|v| &**v) {
+        Some(Value::Str(s)) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads the track id (an object path) from MPRIS metadata.
+fn metadata_track_id(metadata: &HashMap<String, OwnedValue>) -> Option<String> {
+    match metadata.get("mpris:trackid").map(|v| &**v) {
+        Some(Value::ObjectPath(path)) => Some(path.to_string()),
+        Some(Value::Str(s)) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads a string list (e.g. `xesam:artist`) from MPRIS metadata, joined with commas.
+fn metadata_list(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match metadata.get(key).map(|v| &**v) {
+        Some(Value::Array(artists)) => {
+            let names: Vec<&str> = artists
+                .iter()
+                .filter_map(|artist| match artist {
+                    Value::Str(s) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect();
+            (!names.is_empty()).then(|| names.join(", "))
+        }
+        // Some players send a single string despite the spec
+        Some(Value::Str(s)) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads an integer value (e.g. microseconds) from MPRIS metadata, accepting any integer type.
+fn metadata_int(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<i64> {
+    match metadata.get(key).map(|v| &**v) {
+        Some(Value::I64(v)) => Some(*v),
+        Some(Value::U64(v)) => i64::try_from(*v).ok(),
+        Some(Value::I32(v)) => Some(i64::from(*v)),
+        Some(Value::U32(v)) => Some(i64::from(*v)),
+        _ => None,
+    }
+}
+
+/// Converts an MPRIS time value (microseconds) to seconds.
+fn micros_to_seconds(micros: i64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use zbus::blocking::connection::Builder;
+
+    use super::*;
+
+    /// Private session bus, killed when dropped.
+    struct Bus {
+        daemon: Child,   // `dbus-daemon` process
+        address: String, // Address clients connect to
+    }
+
+    impl Bus {
+        /// Starts a private `dbus-daemon`.
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon is required for the MPRIS tests");
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Self {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+
+        /// Opens a new connection to the bus.
+        fn connect(&self) -> Connection {
+            Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+
+        /// Registers a fake player under `org.mpris.MediaPlayer2.<name>`. The player
+        /// leaves the bus when the returned connection is dropped.
+        fn serve(&self, name: &str, player: FakePlayer) -> Connection {
+            Builder::address(self.address.as_str())
+                .unwrap()
+                .name(format!("{}{}", MPRIS_PREFIX, name))
+                .unwrap()
+                .serve_at(MPRIS_PATH, player)
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Calls received by a fake player.
+    #[derive(Default)]
+    struct Calls {
+        play_pause: AtomicUsize,            // `PlayPause` calls
+        status_reads: AtomicUsize,          // `PlaybackStatus` reads
+        next: AtomicUsize,                  // `Next` calls
+        previous: AtomicUsize,              // `Previous` calls
+        position: Mutex<Option<i64>>,       // Last `SetPosition` position
+        volume: Mutex<Option<f64>>,         // Last `Volume` set
+        shuffle: Mutex<Option<bool>>,       // Last `Shuffle` set
+        loop_status: Mutex<Option<String>>, // Last `LoopStatus` set
+        opened_uri: Mutex<Option<String>>,  // Last `OpenUri` URI
+    }
+
+    /// Player interface of a fake MPRIS player.
+    struct FakePlayer {
+        status: &'static str, // Playback status
+        calls: Arc<Calls>,    // Calls received
+    }
+
+    impl FakePlayer {
+        /// Creates a player with the given status, returning its call log.
+        fn new(status: &'static str) -> (Self, Arc<Calls>) {
+            let calls = Arc::new(Calls::default());
+            let player = Self {
+                status,
+                calls: calls.clone(),
+            };
+            (player, calls)
+        }
+    }
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl FakePlayer {
+        fn play_pause(&self) {
+            self.calls.play_pause.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn next(&self) {
+            self.calls.next.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn previous(&self) {
+            self.calls.previous.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn set_position(&self, _track_id: ObjectPath<'_>, position: i64) {
+            *self.calls.position.lock().unwrap() = Some(position);
+        }
+
+        fn open_uri(&self, uri: String) {
+            *self.calls.opened_uri.lock().unwrap() = Some(uri);
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let entries = [
+                ("xesam:title", Value::from("Song\twith tab")),
+                ("xesam:artist", Value::from(vec!["A", "B"])),
+                ("xesam:album", Value::from("Album")),
+                ("xesam:albumArtist", Value::from(vec!["A"])),
+                ("xesam:discNumber", Value::from(2i32)),
+                ("xesam:trackNumber", Value::from(5i32)),
+                ("xesam:url", Value::from("file:///music/song.flac")),
+                ("mpris:artUrl", Value::from("file:///covers/song.jpg")),
+                ("mpris:length", Value::from(180_000_000i64)),
+                (
+                    "mpris:trackid",
+                    Value::from(ObjectPath::try_from("/track/1").unwrap()),
+                ),
+            ];
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.try_into().unwrap()))
+                .collect()
+        }
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            self.calls.status_reads.fetch_add(1, Ordering::SeqCst);
+            self.status.to_string()
+        }
+
+        #[zbus(property)]
+        fn volume(&self) -> f64 {
+            0.5
+        }
+
+        #[zbus(property)]
+        fn set_volume(&mut self, volume: f64) {
+            *self.calls.volume.lock().unwrap() = Some(volume);
+        }
+
+        #[zbus(property)]
+        fn shuffle(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn set_shuffle(&mut self, shuffle: bool) {
+            *self.calls.shuffle.lock().unwrap() = Some(shuffle);
+        }
+
+        #[zbus(property)]
+        fn loop_status(&self) -> String {
+            "Playlist".to_string()
+        }
+
+        #[zbus(property)]
+        fn set_loop_status(&mut self, loop_status: String) {
+            *self.calls.loop_status.lock().unwrap() = Some(loop_status);
+        }
+
+        #[zbus(property)]
+        fn position(&self) -> i64 {
+            5_000_000
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn status_is_read_from_player() {
+        let bus = Bus::start();
+        let (fake, _) = FakePlayer::new("Playing");
+        let _fake = bus.serve("fake", fake);
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+
+        let status = player.status().unwrap();
+        assert_eq!(status.track_name.as_deref(), Some("Song\twith tab"));
+        assert_eq!(status.artist_name.as_deref(), Some("A, B"));
+        assert_eq!(
+            status.metadata,
+            TrackMetadata {
+                track_id: Some("/track/1".to_string()),
+                uri: Some("file:///music/song.flac".to_string()),
+                album_name: Some("Album".to_string()),
+                album_artist: Some("A".to_string()),
+                disc_number: Some(2),
+                track_number: Some(5),
+                explicit: None,
+                popularity: None,
+            }
+        );
+        assert_eq!(
+            status.album_cover.as_deref(),
+            Some("file:///covers/song.jpg")
+        );
+        assert_eq!(status.track_duration, Some(180.0));
+        assert_eq!(status.position, Some(5.0));
+        assert_eq!(status.track_volume, Some(50));
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(true));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Context));
+    }
+
+    #[test]
+    fn loop_status_maps_to_repeat_mode() {
+        assert_eq!(repeat_mode("None"), Some(RepeatMode::Off));
+        assert_eq!(repeat_mode("Track"), Some(RepeatMode::Track));
+        assert_eq!(repeat_mode("Playlist"), Some(RepeatMode::Context));
+        assert_eq!(repeat_mode("Shuffle"), None);
+    }
+
+    #[test]
+    fn capabilities_follow_the_player_flags() {
+        let bus = Bus::start();
+        let (fake, _) = FakePlayer::new("Playing");
+        let _fake = bus.serve("fake", fake);
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+
+        // Flags the player does not expose are unsupported
+        assert_eq!(
+            player.capabilities(),
+            PlayerCapabilities {
+                can_play_pause: true,
+                can_go_next: false,
+                can_go_previous: false,
+                can_seek: true,
+                can_set_volume: true,
+                can_shuffle: true,
+                can_repeat: true,
+                can_play_uri: true,
+                can_save: false,
+                can_queue: false,
+                can_transfer: false,
+            }
+        );
+    }
+
+    #[test]
+    fn capabilities_without_player_are_unsupported() {
+        let bus = Bus::start();
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+
+        assert_eq!(
+            player.capabilities(),
+            PlayerCapabilities {
+                can_play_pause: false,
+                can_go_next: false,
+                can_go_previous: false,
+                can_seek: false,
+                can_set_volume: false,
+                can_shuffle: false,
+                can_repeat: false,
+                can_play_uri: false,
+                can_save: false,
+                can_queue: false,
+                can_transfer: false,
+            }
+        );
+    }
+
+    #[test]
+    fn commands_reach_player() {
+        let bus = Bus::start();
+        let (fake, calls) = FakePlayer::new("Playing");
+        let _fake = bus.serve("fake", fake);
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.previous_track().unwrap();
+        player.set_position(42.5).unwrap();
+        player.set_volume(25).unwrap();
+        player.set_shuffle(false).unwrap();
+        player.set_repeat(RepeatMode::Track).unwrap();
+        player
+            .play_uri(&SpotifyUri::parse("spotify:track:4uLU6hMCjMI75M1A2tKUQC").unwrap())
+            .unwrap();
+        assert_eq!(calls.play_pause.load(Ordering::SeqCst), 1);
+        assert_eq!(calls.next.load(Ordering::SeqCst), 1);
+        assert_eq!(calls.previous.load(Ordering::SeqCst), 1);
+        assert_eq!(*calls.position.lock().unwrap(), Some(42_500_000));
+        // MPRIS volumes are fractions of the full volume
+        assert_eq!(*calls.volume.lock().unwrap(), Some(0.25));
+        assert_eq!(*calls.shuffle.lock().unwrap(), Some(false));
+        assert_eq!(calls.loop_status.lock().unwrap().as_deref(), Some("Track"));
+        assert_eq!(
+            calls.opened_uri.lock().unwrap().as_deref(),
+            Some("spotify:track:4uLU6hMCjMI75M1A2tKUQC")
+        );
+    }
+
+    #[test]
+    fn playing_player_is_preferred() {
+        let bus = Bus::start();
+        let (paused, paused_calls) = FakePlayer::new("Paused");
+        let _paused = bus.serve("paused", paused);
+        let (playing, playing_calls) = FakePlayer::new("Playing");
+        let _playing = bus.serve("playing", playing);
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+
+        assert_eq!(
+            player.status().unwrap().player_state.as_deref(),
+            Some("playing")
+        );
+        player.toggle_playback().unwrap();
+        assert_eq!(playing_calls.play_pause.load(Ordering::SeqCst), 1);
+        assert_eq!(paused_calls.play_pause.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn player_is_discovered_once_per_poll() {
+        let bus = Bus::start();
+        let (first, first_calls) = FakePlayer::new("Paused");
+        let _first = bus.serve("first", first);
+        let (second, second_calls) = FakePlayer::new("Paused");
+        let _second = bus.serve("second", second);
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+        let reads = || {
+            first_calls.status_reads.load(Ordering::SeqCst)
+                + second_calls.status_reads.load(Ordering::SeqCst)
+        };
+
+        // Neither plays, so discovery asks both
+        player.status().unwrap();
+        let after_poll = reads();
+        assert!(after_poll >= 2);
+
+        // Commands reuse the player found by the poll
+        for _ in 0..5 {
+            player.toggle_playback().unwrap();
+        }
+        assert_eq!(reads(), after_poll);
+
+        // The next poll looks again
+        player.status().unwrap();
+        assert_eq!(reads(), 2 * after_poll);
+    }
+
+    #[test]
+    fn vanished_player_is_rediscovered() {
+        let bus = Bus::start();
+        let (first, first_calls) = FakePlayer::new("Playing");
+        let first_connection = bus.serve("first", first);
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+        player.status().unwrap();
+
+        // The player quits and another one starts before the next poll
+        drop(first_connection);
+        let (second, second_calls) = FakePlayer::new("Paused");
+        let _second = bus.serve("second", second);
+
+        player.toggle_playback().unwrap();
+        assert_eq!(first_calls.play_pause.load(Ordering::SeqCst), 0);
+        assert_eq!(second_calls.play_pause.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn no_player_is_not_running() {
+        let bus = Bus::start();
+        let player = MprisPlayer::with_connection(bus.connect(), None);
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+        assert_eq!(
+            player.toggle_playback().unwrap_err(),
+            PlayerError::PlayerNotRunning
+        );
+    }
+
+    #[test]
+    fn bound_player_is_not_discovered() {
+        let bus = Bus::start();
+        let (playing, _) = FakePlayer::new("Playing");
+        let _playing = bus.serve("playing", playing);
+        let player = MprisPlayer::with_connection(
+            bus.connect(),
+            Some(format!("{}{}", MPRIS_PREFIX, "absent")),
+        );
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+}
@@ -0,0 +1,333 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use super::{MediaPlayer, PlayerError};
+use crate::params::{self, QueueTrack};
+
+/// Queue snapshot and refresh bookkeeping of the active player.
+#[derive(Debug, Default)]
+struct QueueState {
+    snapshot: Option<Vec<QueueTrack>>, // Queue fetched last, `None` before the first fetch
+    refreshed_at: Option<Instant>,     // When the queue was fetched last
+    refresh_requested: bool,           // Whether a command changed the queue since
+    unsupported: bool,                 // Whether the player has no readable queue
+}
+
+/// Refetches the queue of the active player when it may have changed and tells
+/// whether it did.
+///
+/// Reading the queue costs a request, so it is only fetched when the track
+/// changes, after a command altered it, and periodically while the notch is
+/// expanded.
+#[derive(Debug, Default)]
+pub struct QueueWatcher {
+    state: Mutex<QueueState>, // Snapshot and refresh bookkeeping
+}
+
+impl QueueWatcher {
+    /// Creates a watcher with no snapshot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refetches the queue if due and returns it when it differs from the previous snapshot.
+    pub fn poll(
+        &self,
+        backend: &dyn MediaPlayer,
+        track_changed: bool,
+        expanded: bool,
+    ) -> Option<Vec<QueueTrack>> {
+        self.poll_at(backend, track_changed, expanded, Instant::now())
+    }
+
+    /// Refetches the queue if due at the instant `now`.
+    fn poll_at(
+        &self,
+        backend: &dyn MediaPlayer,
+        track_changed: bool,
+        expanded: bool,
+        now: Instant,
+    ) -> Option<Vec<QueueTrack>> {
+        {
+            let state = self.state();
+            if state.unsupported {
+                return None;
+            }
+            let stale = state.refreshed_at.map_or(true, |at| {
+                now.saturating_duration_since(at) >= params::QUEUE_REFRESH_INTERVAL
+            });
+            if !(track_changed || state.refresh_requested || (expanded && stale)) {
+                return None;
+            }
+        }
+
+        let queue = match backend.queue() {
+            Ok(queue) => queue,
+            Err(PlayerError::Unsupported { .. }) => {
+                self.state().unsupported = true;
+                return None;
+            }
+            Err(error) => {
+                log::debug!("Failed to fetch the queue: {}", error);
+                return None;
+            }
+        };
+
+        let mut state = self.state();
+        state.refreshed_at = Some(now);
+        state.refresh_requested = false;
+        if state.snapshot.as_ref() == Some(&queue) {
+            return None;
+        }
+        state.snapshot = Some(queue.clone());
+        Some(queue)
+    }
+
+    /// Asks for the queue to be refetched at the next poll.
+    pub fn request_refresh(&self) {
+        self.state().refresh_requested = true;
+    }
+
+    /// Forgets the snapshot, e.g. after switching to another player.
+    pub fn reset(&self) {
+        *self.state() = QueueState::default();
+    }
+
+    /// Locks the state, recovering from a poisoned lock.
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::params::{RepeatMode, SpotifyStatus};
+    use crate::player::PlayerCapabilities;
+
+    /// Player answering queue requests with a settable result and counting them.
+    struct FakePlayer {
+        queue: Mutex<Result<Vec<QueueTrack>, PlayerError>>, // Answer to `queue`
+        fetches: AtomicUsize,                               // `queue` calls
+    }
+
+    impl FakePlayer {
+        fn new(queue: Result<Vec<QueueTrack>, PlayerError>) -> Self {
+            Self {
+                queue: Mutex::new(queue),
+                fetches: AtomicUsize::new(0),
+            }
+        }
+
+        fn set(&self, queue: Result<Vec<QueueTrack>, PlayerError>) {
+            *self.queue.lock().unwrap() = queue;
+        }
+
+        fn fetches(&self) -> usize {
+            self.fetches.load(Ordering::SeqCst)
+        }
+    }
+
+    impl MediaPlayer for FakePlayer {
+        fn name(&self) -> &str {
+            "Fake"
+        }
+
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                can_play_pause: false,
+                can_go_next: false,
+                can_go_previous: false,
+                can_seek: false,
+                can_set_volume: false,
+                can_shuffle: false,
+                can_repeat: false,
+                can_play_uri: false,
+                can_save: false,
+                can_queue: true,
+                can_transfer: false,
+            }
+        }
+
+        fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+            Ok(SpotifyStatus::default())
+        }
+
+        fn toggle_playback(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn next_track(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn previous_track(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_position(&self, _position: f64) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_volume(&self, _level: u32) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_shuffle(&self, _enabled: bool) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_repeat(&self, _mode: RepeatMode) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn queue(&self) -> Result<Vec<QueueTrack>, PlayerError> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.queue.lock().unwrap().clone()
+        }
+    }
+
+    /// Returns a queue of tracks with the given names.
+    fn tracks(names: &[&str]) -> Vec<QueueTrack> {
+        names
+            .iter()
+            .map(|name| QueueTrack {
+                track_name: Some(name.to_string()),
+                ..QueueTrack::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queue_is_reported_only_when_it_differs() {
+        let player = FakePlayer::new(Ok(tracks(&["A", "B"])));
+        let watcher = QueueWatcher::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            watcher.poll_at(&player, true, false, now),
+            Some(tracks(&["A", "B"]))
+        );
+        assert_eq!(watcher.poll_at(&player, true, false, now), None);
+
+        player.set(Ok(tracks(&["B"])));
+        assert_eq!(
+            watcher.poll_at(&player, true, false, now),
+            Some(tracks(&["B"]))
+        );
+        assert_eq!(player.fetches(), 3);
+    }
+
+    #[test]
+    fn queue_is_not_fetched_without_a_reason() {
+        let player = FakePlayer::new(Ok(tracks(&["A"])));
+        let watcher = QueueWatcher::new();
+
+        assert_eq!(watcher.poll_at(&player, false, false, Instant::now()), None);
+        assert_eq!(player.fetches(), 0);
+    }
+
+    #[test]
+    fn track_change_forces_a_refresh() {
+        let player = FakePlayer::new(Ok(tracks(&["A"])));
+        let watcher = QueueWatcher::new();
+        let now = Instant::now();
+        watcher.poll_at(&player, false, true, now);
+
+        // Well within the refresh interval, only a track change refetches
+        assert_eq!(watcher.poll_at(&player, false, true, now), None);
+        assert_eq!(player.fetches(), 1);
+        player.set(Ok(tracks(&["B"])));
+        assert_eq!(
+            watcher.poll_at(&player, true, true, now),
+            Some(tracks(&["B"]))
+        );
+        assert_eq!(player.fetches(), 2);
+    }
+
+    #[test]
+    fn expanded_notch_refreshes_at_the_interval() {
+        let player = FakePlayer::new(Ok(tracks(&["A"])));
+        let watcher = QueueWatcher::new();
+        let start = Instant::now();
+        let almost = start + params::QUEUE_REFRESH_INTERVAL - Duration::from_millis(1);
+        let due = start + params::QUEUE_REFRESH_INTERVAL;
+
+        watcher.poll_at(&player, false, true, start);
+        watcher.poll_at(&player, false, true, almost);
+        assert_eq!(player.fetches(), 1);
+        watcher.poll_at(&player, false, false, due);
+        assert_eq!(player.fetches(), 1);
+        watcher.poll_at(&player, false, true, due);
+        assert_eq!(player.fetches(), 2);
+    }
+
+    #[test]
+    fn requested_refresh_is_done_once() {
+        let player = FakePlayer::new(Ok(tracks(&["A"])));
+        let watcher = QueueWatcher::new();
+        let now = Instant::now();
+
+        watcher.request_refresh();
+        assert_eq!(
+            watcher.poll_at(&player, false, false, now),
+            Some(tracks(&["A"]))
+        );
+        assert_eq!(watcher.poll_at(&player, false, false, now), None);
+        assert_eq!(player.fetches(), 1);
+    }
+
+    #[test]
+    fn unsupported_queue_is_not_polled_again() {
+        let player = FakePlayer::new(Err(PlayerError::unsupported("queue")));
+        let watcher = QueueWatcher::new();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(watcher.poll_at(&player, true, true, now), None);
+        }
+        assert_eq!(player.fetches(), 1);
+
+        // Another player may have a queue
+        watcher.reset();
+        player.set(Ok(tracks(&["A"])));
+        assert_eq!(
+            watcher.poll_at(&player, true, false, now),
+            Some(tracks(&["A"]))
+        );
+    }
+
+    #[test]
+    fn failed_fetch_is_retried() {
+        let player = FakePlayer::new(Err(PlayerError::Timeout));
+        let watcher = QueueWatcher::new();
+        let now = Instant::now();
+
+        watcher.request_refresh();
+        assert_eq!(watcher.poll_at(&player, false, false, now), None);
+        player.set(Ok(tracks(&["A"])));
+        assert_eq!(
+            watcher.poll_at(&player, false, false, now),
+            Some(tracks(&["A"]))
+        );
+        assert_eq!(player.fetches(), 2);
+    }
+
+    #[test]
+    fn reset_forgets_the_snapshot() {
+        let player = FakePlayer::new(Ok(tracks(&["A"])));
+        let watcher = QueueWatcher::new();
+        let now = Instant::now();
+        watcher.poll_at(&player, true, false, now);
+
+        watcher.reset();
+
+        assert_eq!(
+            watcher.poll_at(&player, true, false, now),
+            Some(tracks(&["A"]))
+        );
+    }
+}
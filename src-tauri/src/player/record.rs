@@ -0,0 +1,263 @@
+use super::PlayerError;
+
+/// Separator between the fields of a record printed by an AppleScript (ASCII unit separator).
+pub const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// AppleScript handlers shared by the scripts printing records.
+///
+/// `clean_field` turns `missing value` into an empty field and replaces the
+/// separator so a value can never shift the following fields.
+pub const APPLESCRIPT_HELPERS: &str = r#"
+        on replace_text(t, r, w)
+            set AppleScript's text item delimiters to r
+            set t_items to every text item of t
+            set AppleScript's text item delimiters to w
+            set t to t_items as string
+            set AppleScript's text item delimiters to ""
+            return t
+        end replace_text
+
+        on clean_field(value)
+            if value is missing value then return ""
+            return my replace_text(value as text, character id 31, " ")
+        end clean_field
+"#;
+
+/// Quotes a value as an AppleScript string literal.
+pub fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A record printed by an AppleScript as separator delimited fields.
+///
+/// The first field is a marker (`ok`, or a state such as `not_running`), the
+/// remaining ones are values addressed by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'a> {
+    fields: Vec<&'a str>, // Raw fields, including the marker
+}
+
+impl<'a> Record<'a> {
+    /// Splits script output into a record, stripping only the trailing line break
+    /// `osascript` appends so values keep their surrounding whitespace.
+    pub fn parse(stdout: &'a str) -> Self {
+        let line = stdout.trim_end_matches(['\n', '\r']);
+        Self {
+            fields: line.split(FIELD_SEPARATOR).collect(),
+        }
+    }
+
+    /// Returns the marker (first field) of the record.
+    pub fn marker(&self) -> &'a str {
+        self.fields.first().map_or("", |marker| marker.trim())
+    }
+
+    /// Fails unless the record holds exactly `count` fields, including the marker.
+    pub fn expect_fields(&self, count: usize) -> Result<(), PlayerError> {
+        if self.fields.len() == count {
+            Ok(())
+        } else {
+            Err(PlayerError::parse(format!(
+                "expected {} fields, got {}",
+                count,
+                self.fields.len()
+            )))
+        }
+    }
+
+    /// Returns a text field, or `None` if it is empty or `missing value`.
+    pub fn text(&self, index: usize) -> Option<String> {
+        let value = *self.fields.get(index)?;
+        if value.is_empty() || value == "missing value" {
+            None
+        } else {
+            Some(value.to_string())
+        }
+    }
+
+    /// Returns a boolean field printed as `true` or `false`.
+    pub fn boolean(&self, index: usize) -> Option<bool> {
+        match self.fields.get(index)?.trim() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns a positive whole number field, or `None` if it is zero or missing.
+    pub fn count(&self, index: usize) -> Option<u32> {
+        self.number(index)
+            .filter(|n| *n >= 1.0)
+            .map(|n| n.round().min(u32::MAX as f64) as u32)
+    }
+
+    /// Returns a numeric field parsed independently of the system locale.
+    pub fn number(&self, index: usize) -> Option<f64> {
+        parse_number(self.fields.get(index)?)
+    }
+}
+
+/// Parses a number as printed by AppleScript under any locale.
+///
+/// Accepts decimal commas (`12,5`), grouping spaces including non-breaking ones
+/// (`1 234,5`) and exponents (`1,2E+4`); returns `None` for `missing value`,
+/// empty fields and anything else.
+pub fn parse_number(value: &str) -> Option<f64> {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\u{a0}' && *c != '\u{202f}' && *c != '\'')
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect();
+
+    // A comma was a grouping separator if more than one separator remains
+    let cleaned = if cleaned.matches('.').count() > 1 {
+        match cleaned.rfind('.') {
+            Some(last) => format!("{}{}", cleaned[..last].replace('.', ""), &cleaned[last..]),
+            None => cleaned,
+        }
+    } else {
+        cleaned
+    };
+
+    cleaned.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Parses the number before the total in tag values such as `3/12`, as reported
+/// for disc and track numbers by MPD and VLC.
+pub fn leading_number(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Joins fields the way the status scripts print them, with `osascript`'s line break.
+    fn printed(fields: &[&str]) -> String {
+        let separator = FIELD_SEPARATOR.to_string();
+        format!("{}\n", fields.join(&separator))
+    }
+
+    #[test]
+    fn nasty_titles_survive_parsing() {
+        let titles = [
+            "Tab\tseparated",
+            "Line one\nline two",
+            "Carriage\r\nreturn",
+            r#"He said "hi" \o/"#,
+            "Emoji 🎧🔥 and ünïcödé",
+            "  surrounding spaces  ",
+        ];
+        for title in titles {
+            let stdout = printed(&["ok", title, "Artist"]);
+            let record = Record::parse(&stdout);
+
+            assert_eq!(record.marker(), "ok");
+            assert!(record.expect_fields(3).is_ok(), "{:?}", title);
+            assert_eq!(record.text(1).as_deref(), Some(title));
+            assert_eq!(record.text(2).as_deref(), Some("Artist"));
+        }
+    }
+
+    #[test]
+    fn trailing_line_breaks_are_stripped_from_the_last_field_only() {
+        let record = Record::parse("ok\u{1f}\tindented\u{1f}last\r\n");
+
+        assert_eq!(record.text(1).as_deref(), Some("\tindented"));
+        assert_eq!(record.text(2).as_deref(), Some("last"));
+    }
+
+    #[test]
+    fn unit_separator_in_a_value_is_caught_by_the_field_count() {
+        // `clean_field` replaces the separator; a value printed without it shifts the fields
+        let stdout = printed(&["ok", "Broken\u{1f}title", "Artist"]);
+        let record = Record::parse(&stdout);
+
+        assert!(record.expect_fields(3).is_err());
+    }
+
+    #[test]
+    fn empty_and_missing_fields_are_none() {
+        let stdout = printed(&["ok", "", "missing value"]);
+        let record = Record::parse(&stdout);
+
+        assert_eq!(record.text(1), None);
+        assert_eq!(record.text(2), None);
+        assert_eq!(record.text(9), None);
+        assert_eq!(record.number(9), None);
+    }
+
+    #[test]
+    fn marker_is_trimmed() {
+        assert_eq!(Record::parse(" not_running \n").marker(), "not_running");
+        assert_eq!(Record::parse("").marker(), "");
+    }
+
+    #[test]
+    fn numbers_parse_under_any_locale() {
+        let cases = [
+            ("42", Some(42.0)),
+            ("12.5", Some(12.5)),
+            ("12,5", Some(12.5)),
+            ("1 234,5", Some(1234.5)),
+            ("1\u{a0}234,5", Some(1234.5)),
+            ("1\u{202f}234,5", Some(1234.5)),
+            ("1'234.5", Some(1234.5)),
+            ("1.234,5", Some(1234.5)),
+            ("1,234.5", Some(1234.5)),
+            ("1,2E+4", Some(12000.0)),
+            ("1.2E-2", Some(0.012)),
+            ("-3,5", Some(-3.5)),
+            ("missing value", None),
+            ("", None),
+            ("abc", None),
+            ("NaN", None),
+            ("inf", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_number(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn booleans_are_true_or_false() {
+        let stdout = printed(&["ok", "true", "false", " true ", "yes", ""]);
+        let record = Record::parse(&stdout);
+
+        assert_eq!(record.boolean(1), Some(true));
+        assert_eq!(record.boolean(2), Some(false));
+        assert_eq!(record.boolean(3), Some(true));
+        assert_eq!(record.boolean(4), None);
+        assert_eq!(record.boolean(5), None);
+        assert_eq!(record.boolean(6), None);
+    }
+
+    #[test]
+    fn counts_are_positive_whole_numbers() {
+        let stdout = printed(&["ok", "3", "2.6", "0", "-1", "", "missing value"]);
+        let record = Record::parse(&stdout);
+
+        assert_eq!(record.count(1), Some(3));
+        assert_eq!(record.count(2), Some(3));
+        assert_eq!(record.count(3), None);
+        assert_eq!(record.count(4), None);
+        assert_eq!(record.count(5), None);
+        assert_eq!(record.count(6), None);
+        assert_eq!(record.count(7), None);
+    }
+
+    #[test]
+    fn applescript_strings_are_escaped() {
+        assert_eq!(applescript_string(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+    }
+
+    #[test]
+    fn leading_number_ignores_the_total() {
+        assert_eq!(leading_number("3"), Some(3));
+        assert_eq!(leading_number("03/12"), Some(3));
+        assert_eq!(leading_number(" 1 / 2"), Some(1));
+        assert_eq!(leading_number("/12"), None);
+        assert_eq!(leading_number("A1"), None);
+        assert_eq!(leading_number(""), None);
+    }
+}
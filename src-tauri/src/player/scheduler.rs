@@ -0,0 +1,185 @@
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::config::PollingConfig;
+
+/// What the player was doing at the last poll, which drives the polling interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerActivity {
+    Playing, // A track is playing
+    Idle,    // The player is paused or stopped
+    Absent,  // The player is not running or could not be reached
+    Watched, // The players push their changes, polls only catch up on missed ones
+}
+
+/// Decides how long the status thread sleeps between polls.
+///
+/// Polls fast while playing with the notch expanded, slows down while paused or
+/// collapsed or while the players push their changes, and backs off exponentially
+/// while the player is absent. `wake` cuts the current sleep short, e.g. right
+/// after a user command or a pushed change.
+#[derive(Debug)]
+pub struct PollScheduler {
+    config: PollingConfig,    // Configured intervals
+    backoff: Mutex<Duration>, // Next interval to use while the player is absent
+    woken: Mutex<bool>,       // Whether a wake up is pending
+    wakeup: Condvar,          // Notified when a wake up is requested
+}
+
+impl PollScheduler {
+    /// Creates a scheduler using the given intervals.
+    pub fn new(config: PollingConfig) -> Self {
+        Self {
+            backoff: Mutex::new(config.absent_initial_interval()),
+            config,
+            woken: Mutex::new(false),
+            wakeup: Condvar::new(),
+        }
+    }
+
+    /// Returns the interval to wait before the next poll.
+    pub fn next_interval(&self, activity: PlayerActivity, expanded: bool) -> Duration {
+        let mut backoff = lock(&self.backoff);
+        if activity != PlayerActivity::Absent {
+            *backoff = self.config.absent_initial_interval();
+        }
+
+        match (activity, expanded) {
+            (PlayerActivity::Playing, true) => self.config.playing_interval(),
+            (PlayerActivity::Playing, false) | (PlayerActivity::Idle, true) => {
+                self.config.collapsed_interval()
+            }
+            (PlayerActivity::Idle, false) | (PlayerActivity::Watched, _) => {
+                self.config.paused_interval()
+            }
+            (PlayerActivity::Absent, _) => {
+                let interval = *backoff;
+                *backoff = (interval * 2).min(self.config.absent_max_interval());
+                interval
+            }
+        }
+    }
+
+    /// Sleeps for the given interval, returning early if `wake` is called meanwhile.
+    pub fn sleep(&self, interval: Duration) {
+        let deadline = Instant::now() + interval;
+        let mut woken = lock(&self.woken);
+        while !*woken {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            woken = self
+                .wakeup
+                .wait_timeout(woken, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *woken = false;
+    }
+
+    /// Wakes the status thread up so it polls immediately, and restarts the backoff.
+    pub fn wake(&self) {
+        *lock(&self.backoff) = self.config.absent_initial_interval();
+        *lock(&self.woken) = true;
+        self.wakeup.notify_all();
+    }
+}
+
+/// Locks a mutex, recovering from a poisoned lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polling configuration with distinct intervals, in milliseconds.
+    fn config() -> PollingConfig {
+        PollingConfig {
+            playing_interval_ms: 500,
+            collapsed_interval_ms: 1_000,
+            paused_interval_ms: 4_000,
+            absent_initial_interval_ms: 1_000,
+            absent_max_interval_ms: 5_000,
+        }
+    }
+
+    #[test]
+    fn interval_follows_activity_and_notch() {
+        let scheduler = PollScheduler::new(config());
+        let ms = |activity, expanded| scheduler.next_interval(activity, expanded).as_millis();
+
+        assert_eq!(ms(PlayerActivity::Playing, true), 500);
+        assert_eq!(ms(PlayerActivity::Playing, false), 1_000);
+        assert_eq!(ms(PlayerActivity::Idle, true), 1_000);
+        assert_eq!(ms(PlayerActivity::Idle, false), 4_000);
+        // Pushed changes wake the thread, polls only catch up on missed ones
+        assert_eq!(ms(PlayerActivity::Watched, true), 4_000);
+        assert_eq!(ms(PlayerActivity::Watched, false), 4_000);
+    }
+
+    #[test]
+    fn absent_backoff_doubles_up_to_the_maximum() {
+        let scheduler = PollScheduler::new(config());
+        let intervals: Vec<u128> = (0..5)
+            .map(|_| {
+                scheduler
+                    .next_interval(PlayerActivity::Absent, false)
+                    .as_millis()
+            })
+            .collect();
+
+        assert_eq!(intervals, vec![1_000, 2_000, 4_000, 5_000, 5_000]);
+    }
+
+    #[test]
+    fn backoff_restarts_once_the_player_is_back() {
+        let scheduler = PollScheduler::new(config());
+        scheduler.next_interval(PlayerActivity::Absent, false);
+        scheduler.next_interval(PlayerActivity::Absent, false);
+        scheduler.next_interval(PlayerActivity::Idle, false);
+
+        assert_eq!(
+            scheduler.next_interval(PlayerActivity::Absent, false),
+            Duration::from_millis(1_000)
+        );
+    }
+
+    #[test]
+    fn wake_restarts_the_backoff_and_cuts_the_sleep_short() {
+        let scheduler = PollScheduler::new(config());
+        scheduler.next_interval(PlayerActivity::Absent, false);
+        scheduler.next_interval(PlayerActivity::Absent, false);
+
+        scheduler.wake();
+        let started = Instant::now();
+        scheduler.sleep(Duration::from_secs(10));
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            scheduler.next_interval(PlayerActivity::Absent, false),
+            Duration::from_millis(1_000)
+        );
+    }
+
+    #[test]
+    fn clamped_zero_intervals_keep_backing_off() {
+        let mut polling = PollingConfig {
+            playing_interval_ms: 0,
+            absent_initial_interval_ms: 0,
+            absent_max_interval_ms: 0,
+            ..config()
+        };
+        polling.clamp();
+        let scheduler = PollScheduler::new(polling);
+
+        assert!(!scheduler
+            .next_interval(PlayerActivity::Playing, true)
+            .is_zero());
+        assert!(!scheduler
+            .next_interval(PlayerActivity::Absent, false)
+            .is_zero());
+    }
+}
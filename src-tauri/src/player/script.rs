@@ -0,0 +1,352 @@
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::PlayerError;
+use crate::params::{self, SpotifyStatus};
+
+/// Interval at which a running script is checked for completion.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// AppleScript error raised for a missing object (errAENoSuchObject), e.g. the
+/// current track of a player with nothing loaded.
+const NO_SUCH_OBJECT: &str = "(-1728)";
+
+/// Result of running a script, independent of how it was executed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptOutput {
+    pub exit_code: Option<i32>, // Exit code of the script, `None` if it was killed by a signal
+    pub stdout: String,         // Standard output of the script
+    pub stderr: String,         // Standard error of the script
+}
+
+impl ScriptOutput {
+    /// Creates a successful output with the given stdout.
+    pub fn success(stdout: impl Into<String>) -> Self {
+        Self {
+            exit_code: Some(0),
+            stdout: stdout.into(),
+            stderr: String::new(),
+        }
+    }
+
+    /// Creates a failed output with the given exit code and stderr.
+    pub fn failure(exit_code: i32, stderr: impl Into<String>) -> Self {
+        Self {
+            exit_code: Some(exit_code),
+            stdout: String::new(),
+            stderr: stderr.into(),
+        }
+    }
+
+    /// Returns whether the script exited successfully.
+    pub fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Executes scripts on behalf of AppleScript based backends.
+///
+/// The real implementation shells out to `osascript`; `FakeScriptRunner` lets
+/// the tests exercise the backends on machines without it.
+pub trait ScriptRunner: Send + Sync {
+    /// Runs the script and returns its output, or an error if it could not be started.
+    ///
+    /// A script still running after `timeout` is killed and reported as an
+    /// `io::ErrorKind::TimedOut` error.
+    fn run(&self, script: &str, timeout: Duration) -> io::Result<ScriptOutput>;
+}
+
+/// Runs AppleScript through the `osascript` command (macOS only).
+#[derive(Debug, Default)]
+pub struct OsaScriptRunner;
+
+impl ScriptRunner for OsaScriptRunner {
+    fn run(&self, script: &str, timeout: Duration) -> io::Result<ScriptOutput> {
+        // Execute the AppleScript using osascript command
+        let mut child = Command::new("osascript")
+            .arg("-e")
+            .arg(script)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Drain the pipes on their own threads so a full pipe cannot block the script
+        let stdout = drain_pipe(child.stdout.take());
+        let stderr = drain_pipe(child.stderr.take());
+
+        // Wait for the script, killing it once the timeout is exceeded
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("osascript did not finish within {:?}", timeout),
+                ));
+            }
+            thread::sleep(WAIT_POLL_INTERVAL);
+        };
+
+        Ok(ScriptOutput {
+            exit_code: status.code(),
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+}
+
+/// Reads a child pipe to the end on a separate thread.
+fn drain_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+/// Runs the scripts of an AppleScript backend and maps their failures to `PlayerError`.
+///
+/// Status queries are serialized so a hanging player never has several scripts piled up.
+pub struct AppleScriptClient {
+    runner: Arc<dyn ScriptRunner>, // Runner executing the AppleScript snippets
+    status_query: Mutex<()>,       // Held while a status query is in flight
+}
+
+impl AppleScriptClient {
+    /// Creates a client running scripts through the given runner.
+    pub fn new(runner: Arc<dyn ScriptRunner>) -> Self {
+        Self {
+            runner,
+            status_query: Mutex::new(()),
+        }
+    }
+
+    /// Runs a status script and returns its trimmed stdout, or `None` if the player
+    /// has no current track to read.
+    pub fn query(&self, script: &str) -> Result<Option<String>, PlayerError> {
+        let _in_flight = self.status_query.lock().unwrap_or_else(|e| e.into_inner());
+        match self.run(script, params::STATUS_SCRIPT_TIMEOUT) {
+            Err(PlayerError::ScriptFailed { stderr }) if stderr.contains(NO_SUCH_OBJECT) => {
+                Ok(None)
+            }
+            result => result.map(Some),
+        }
+    }
+
+    /// Runs a command script, discarding its output.
+    pub fn command(&self, script: &str) -> Result<(), PlayerError> {
+        self.run(script, params::COMMAND_SCRIPT_TIMEOUT).map(|_| ())
+    }
+
+    /// Runs a script and returns its trimmed stdout, mapping failures to a `PlayerError`.
+    pub fn run(&self, script: &str, timeout: Duration) -> Result<String, PlayerError> {
+        let output = self
+            .runner
+            .run(script, timeout)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::TimedOut => PlayerError::Timeout,
+                _ => PlayerError::ScriptFailed {
+                    stderr: format!("Failed to run AppleScript: {}", e),
+                },
+            })?;
+
+        // Check for errors in the AppleScript execution
+        if !output.is_success() {
+            return Err(script_error(&output));
+        }
+
+        Ok(output.stdout.trim().to_string())
+    }
+}
+
+/// Status of a player running without a current track.
+pub fn no_track_status() -> SpotifyStatus {
+    SpotifyStatus {
+        player_state: Some("stopped".to_string()),
+        ..SpotifyStatus::default()
+    }
+}
+
+/// Maps a failed `osascript` run to a `PlayerError`.
+///
+/// A missing object (-1728) stays a `ScriptFailed`: it means the player has no
+/// current track, which `AppleScriptClient::query` reports as such.
+pub fn script_error(output: &ScriptOutput) -> PlayerError {
+    let stderr = output.stderr.trim();
+
+    // -10814 is "application not found" (Launch Services); AppleScript words it as
+    // "Can't get application" when the script names an unknown application
+    let lowercase = stderr.to_lowercase().replace('’', "'");
+    if stderr.contains("(-10814)")
+        || lowercase.contains("can't get application")
+        || lowercase.contains("application isn't found")
+    {
+        return PlayerError::PlayerNotInstalled;
+    }
+    // -600 is "Application isn't running", raised if the player quits mid-script
+    if stderr.contains("(-600)") {
+        return PlayerError::PlayerNotRunning;
+    }
+
+    PlayerError::ScriptFailed {
+        stderr: stderr.to_string(),
+    }
+}
+
+/// In-process script runner that records every script and replays canned outputs.
+///
+/// Outputs are returned in the order they were queued; once the queue is empty
+/// every script succeeds with an empty stdout.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct FakeScriptRunner {
+    scripts: Mutex<Vec<String>>, // Scripts run so far, in order
+    outputs: Mutex<VecDeque<io::Result<ScriptOutput>>>, // Outputs to return for the next runs
+}
+
+#[cfg(test)]
+impl FakeScriptRunner {
+    /// Creates a runner with no queued outputs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues an output to be returned by the next unanswered run.
+    pub fn push_output(&self, output: ScriptOutput) {
+        self.outputs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(Ok(output));
+    }
+
+    /// Queues a timeout to be returned by the next unanswered run.
+    pub fn push_timeout(&self) {
+        self.outputs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "script timed out",
+            )));
+    }
+
+    /// Returns every script run so far, in order.
+    pub fn scripts(&self) -> Vec<String> {
+        self.scripts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+impl ScriptRunner for FakeScriptRunner {
+    fn run(&self, script: &str, _timeout: Duration) -> io::Result<ScriptOutput> {
+        self.scripts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(script.to_string());
+
+        self.outputs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+            .unwrap_or_else(|| Ok(ScriptOutput::success("")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Maps a failing run with the given stderr.
+    fn error_for(stderr: &str) -> PlayerError {
+        script_error(&ScriptOutput::failure(1, stderr))
+    }
+
+    #[test]
+    fn application_not_found_is_not_installed() {
+        assert_eq!(
+            error_for("execution error: An error of type -10814 has occurred. (-10814)\n"),
+            PlayerError::PlayerNotInstalled
+        );
+        assert_eq!(
+            error_for("syntax error: Can’t get application \"Spotify\". (-1728)\n"),
+            PlayerError::PlayerNotInstalled
+        );
+        assert_eq!(
+            error_for("syntax error: Application isn’t found. (-2740)\n"),
+            PlayerError::PlayerNotInstalled
+        );
+    }
+
+    #[test]
+    fn application_quitting_mid_script_is_not_running() {
+        assert_eq!(
+            error_for("execution error: Spotify got an error: Application isn’t running. (-600)\n"),
+            PlayerError::PlayerNotRunning
+        );
+    }
+
+    #[test]
+    fn missing_object_is_not_an_installation_problem() {
+        let stderr = "execution error: Can’t get name of current track. (-1728)";
+        assert_eq!(
+            error_for(stderr),
+            PlayerError::ScriptFailed {
+                stderr: stderr.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn other_failures_keep_their_stderr() {
+        assert_eq!(
+            error_for("  execution error: Expected end of line. (-2741)\n"),
+            PlayerError::ScriptFailed {
+                stderr: "execution error: Expected end of line. (-2741)".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn status_query_without_current_track_yields_no_output() {
+        let runner = Arc::new(FakeScriptRunner::new());
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Can’t get name of current track. (-1728)\n",
+        ));
+        runner.push_output(ScriptOutput::success("ok\n"));
+        let client = AppleScriptClient::new(runner);
+
+        assert_eq!(client.query("status"), Ok(None));
+        assert_eq!(client.query("status"), Ok(Some("ok".to_string())));
+    }
+
+    #[test]
+    fn commands_without_current_track_fail() {
+        let runner = Arc::new(FakeScriptRunner::new());
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Can’t get current track. (-1728)",
+        ));
+        let client = AppleScriptClient::new(runner);
+
+        assert!(matches!(
+            client.command("next track"),
+            Err(PlayerError::ScriptFailed { .. })
+        ));
+    }
+}
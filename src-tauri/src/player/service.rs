@@ -0,0 +1,495 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+
+use super::scheduler::{PlayerActivity, PollScheduler};
+use super::{PlayerError, PlayerEvent, PlayerManager, QueueWatcher, StatusDiffer, VolumeControl};
+use crate::config::PollingConfig;
+use crate::params::{self, SpotifyStatus};
+use crate::window;
+
+/// Timestamped playback position published as the `position-sync` event.
+///
+/// Consumers extrapolate the current position as
+/// `position + rate * (Date.now() - synced_at_ms) / 1000`, clamped to `track_duration`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct PositionSync {
+    pub position: f64,               // Position in the track when sampled (in seconds)
+    pub rate: f64,                   // Playback rate (1.0 while playing, 0.0 otherwise)
+    pub synced_at_ms: u64,           // Unix timestamp of the sample (in milliseconds)
+    pub track_duration: Option<f64>, // Duration of the track (in seconds)
+}
+
+/// Position sample the current position is extrapolated from.
+#[derive(Debug, Clone, Copy)]
+struct PositionClock {
+    sync: PositionSync, // Sample as published to consumers
+    synced_at: Instant, // Monotonic time of the sample
+}
+
+impl PositionClock {
+    /// Creates a clock starting at the given position at the instant `now`.
+    fn new(position: f64, rate: f64, track_duration: Option<f64>, now: Instant) -> Self {
+        let synced_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            sync: PositionSync {
+                position,
+                rate,
+                synced_at_ms,
+                track_duration,
+            },
+            synced_at: now,
+        }
+    }
+
+    /// Returns the extrapolated position at the given instant.
+    fn position_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.synced_at).as_secs_f64();
+        let position = self.sync.position + self.sync.rate * elapsed;
+        match self.sync.track_duration {
+            Some(duration) => position.clamp(0.0, duration),
+            None => position.max(0.0),
+        }
+    }
+}
+
+/// Keeps the extrapolated playback position, decides when it is resynced and
+/// schedules the polls of the active player.
+///
+/// Registered as Tauri managed state in `lib.rs::run`.
+#[derive(Debug)]
+pub struct StatusService {
+    clock: Mutex<Option<PositionClock>>, // Current position clock, `None` until the first status
+    resync_requested: AtomicBool,        // Whether the next status must resync the clock
+    scheduler: PollScheduler,            // Scheduler of the status polls
+    volume: VolumeControl,               // Volume levels of the active player
+    queue: QueueWatcher,                 // Last queue of the active player
+}
+
+impl StatusService {
+    /// Creates a service with no known position, polling with the given intervals.
+    pub fn new(polling: PollingConfig) -> Self {
+        Self {
+            clock: Mutex::new(None),
+            resync_requested: AtomicBool::new(false),
+            scheduler: PollScheduler::new(polling),
+            volume: VolumeControl::new(),
+            queue: QueueWatcher::new(),
+        }
+    }
+
+    /// Returns the scheduler of the status polls.
+    pub fn scheduler(&self) -> &PollScheduler {
+        &self.scheduler
+    }
+
+    /// Returns the volume control of the active player.
+    pub fn volume(&self) -> &VolumeControl {
+        &self.volume
+    }
+
+    /// Returns the queue watcher of the active player.
+    pub fn queue(&self) -> &QueueWatcher {
+        &self.queue
+    }
+
+    /// Returns the position extrapolated to now, or `None` if no track is known.
+    pub fn interpolated_position(&self) -> Option<f64> {
+        self.interpolated_position_at(Instant::now())
+    }
+
+    /// Returns the position extrapolated to the instant `now`.
+    fn interpolated_position_at(&self, now: Instant) -> Option<f64> {
+        self.clock().as_ref().map(|clock| clock.position_at(now))
+    }
+
+    /// Returns the duration of the track the clock runs for, if known.
+    pub fn track_duration(&self) -> Option<f64> {
+        self.clock()
+            .as_ref()
+            .and_then(|clock| clock.sync.track_duration)
+    }
+
+    /// Moves the clock to a position the user just seeked to and resyncs on the next status.
+    pub fn seeked_to(&self, position: f64) {
+        self.seeked_to_at(position, Instant::now());
+    }
+
+    /// Moves the clock to a position the user seeked to at the instant `now`.
+    fn seeked_to_at(&self, position: f64, now: Instant) {
+        if let Some(clock) = self.clock().as_mut() {
+            *clock = PositionClock::new(position, clock.sync.rate, clock.sync.track_duration, now);
+        }
+        self.request_resync();
+    }
+
+    /// Forces the clock to be resynced with the backend, polling it immediately.
+    pub fn request_resync(&self) {
+        self.resync_requested.store(true, Ordering::SeqCst);
+        self.scheduler.wake();
+    }
+
+    /// Forgets the position, e.g. after the player went away.
+    pub fn reset(&self) {
+        *self.clock() = None;
+    }
+
+    /// Updates the clock from a fresh status and returns the sample to publish, if any.
+    ///
+    /// The clock is resynced when a track change, playback state change or seek was
+    /// detected, when a resync was requested, or every `POSITION_RESYNC_INTERVAL`;
+    /// in between consumers keep extrapolating the last published sample.
+    pub fn process(&self, status: &SpotifyStatus, events: &[PlayerEvent]) -> Option<PositionSync> {
+        self.process_at(status, events, Instant::now())
+    }
+
+    /// Updates the clock from a status sampled at the instant `now`.
+    fn process_at(
+        &self,
+        status: &SpotifyStatus,
+        events: &[PlayerEvent],
+        now: Instant,
+    ) -> Option<PositionSync> {
+        let position = status.position?;
+        let mut clock = self.clock();
+
+        let transition = events.iter().any(|event| {
+            matches!(
+                event,
+                PlayerEvent::TrackChanged { .. }
+                    | PlayerEvent::PlaybackStateChanged { .. }
+                    | PlayerEvent::Seeked { .. }
+            )
+        });
+        let stale = clock.as_ref().map_or(true, |clock| {
+            now.saturating_duration_since(clock.synced_at) >= params::POSITION_RESYNC_INTERVAL
+        });
+        let requested = self.resync_requested.swap(false, Ordering::SeqCst);
+        if !(transition || stale || requested) {
+            return None;
+        }
+
+        let rate = if status.player_state.as_deref() == Some("playing") {
+            1.0
+        } else {
+            0.0
+        };
+        let synced = PositionClock::new(position, rate, status.track_duration, now);
+        *clock = Some(synced);
+        Some(synced.sync)
+    }
+
+    /// Locks the clock, recovering from a poisoned lock.
+    fn clock(&self) -> std::sync::MutexGuard<'_, Option<PositionClock>> {
+        self.clock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Spawns the thread polling the players and emitting the status events of the active one
+/// to the window.
+pub fn spawn_status_thread(handle: AppHandle, window: WebviewWindow) {
+    thread::spawn(move || {
+        let mut differ = StatusDiffer::new();
+        let mut last_error: Option<PlayerError> = None;
+
+        // Poll right away when a player pushes a change
+        let waker = handle.clone();
+        handle.state::<PlayerManager>().watch(Arc::new(move || {
+            waker.state::<StatusService>().scheduler().wake()
+        }));
+
+        loop {
+            let manager = handle.state::<PlayerManager>();
+            let service = handle.state::<StatusService>();
+            let poll = manager.poll();
+
+            // Start over with the new player so its track is reported as a change
+            if poll.switched {
+                differ.reset();
+                service.reset();
+                service.volume().reset();
+                service.queue().reset();
+                last_error = None;
+                let _ = window.emit("active-player-changed", manager.active_info());
+            }
+
+            let activity = match poll.status {
+                Ok(status) => {
+                    let activity = if manager.is_watched() {
+                        PlayerActivity::Watched
+                    } else if status.player_state.as_deref() == Some("playing") {
+                        PlayerActivity::Playing
+                    } else {
+                        PlayerActivity::Idle
+                    };
+                    // Emit only the transitions since the previous status
+                    let mut events = differ.update(&status);
+                    // Volume changes made through Noci were already answered by the command
+                    if !service.volume().observe(status.track_volume) {
+                        events.retain(|event| !matches!(event, PlayerEvent::VolumeChanged { .. }));
+                    }
+                    for event in &events {
+                        let _ = window.emit(event.name(), event.clone());
+                    }
+                    // Refetch the queue when the track changed and now and then while expanded
+                    let track_changed = events
+                        .iter()
+                        .any(|event| matches!(event, PlayerEvent::TrackChanged { .. }));
+                    let expanded = window::is_notch_expanded();
+                    if let Some(queue) =
+                        service
+                            .queue()
+                            .poll(&*manager.get(), track_changed, expanded)
+                    {
+                        let _ = window.emit("queue-changed", queue);
+                    }
+                    // Publish a new position sample when the clock was resynced
+                    if let Some(sync) = service.process(&status, &events) {
+                        let _ = window.emit("position-sync", sync);
+                    }
+                    // Emit the full Spotify status to the window if enabled
+                    if params::EMIT_STATUS_SNAPSHOT {
+                        let _ = window.emit("spotify-status-update", status);
+                    }
+                    last_error = None;
+                    activity
+                }
+                Err(error) => {
+                    differ.reset();
+                    service.reset();
+                    // Only emit errors when they change to avoid flooding the window
+                    if last_error.as_ref() != Some(&error) {
+                        let _ = window.emit("player-error", error.clone());
+                    }
+                    last_error = Some(error);
+                    PlayerActivity::Absent
+                }
+            };
+
+            // Wait longer while nothing is playing or the notch is collapsed
+            let scheduler = service.scheduler();
+            scheduler.sleep(scheduler.next_interval(activity, window::is_notch_expanded()));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::player::events::diff_statuses;
+
+    /// Status of a track at the given position.
+    fn status(track: &str, state: &str, position: f64) -> SpotifyStatus {
+        SpotifyStatus {
+            track_name: Some(track.to_string()),
+            position: Some(position),
+            track_duration: Some(200.0),
+            player_state: Some(state.to_string()),
+            ..SpotifyStatus::default()
+        }
+    }
+
+    /// Service whose clock was synced to the status at the instant `start`.
+    fn synced(initial: &SpotifyStatus, start: Instant) -> StatusService {
+        let service = StatusService::new(PollingConfig::default());
+        assert!(service.process_at(initial, &[], start).is_some());
+        service
+    }
+
+    /// Processes a status polled `elapsed` after the previous one, diffing it first.
+    fn poll(
+        service: &StatusService,
+        previous: &SpotifyStatus,
+        current: &SpotifyStatus,
+        at: Instant,
+        elapsed: Duration,
+    ) -> Option<PositionSync> {
+        let events = diff_statuses(previous, current, elapsed);
+        service.process_at(current, &events, at)
+    }
+
+    #[test]
+    fn position_advances_while_playing() {
+        let start = Instant::now();
+        let service = synced(&status("Song", "playing", 10.0), start);
+
+        assert_eq!(service.interpolated_position_at(start), Some(10.0));
+        assert_eq!(
+            service.interpolated_position_at(start + Duration::from_millis(2500)),
+            Some(12.5)
+        );
+        // Never past the end of the track
+        assert_eq!(
+            service.interpolated_position_at(start + Duration::from_secs(500)),
+            Some(200.0)
+        );
+    }
+
+    #[test]
+    fn position_is_frozen_while_paused() {
+        let start = Instant::now();
+        let service = synced(&status("Song", "paused", 10.0), start);
+
+        assert_eq!(
+            service.interpolated_position_at(start + Duration::from_secs(30)),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn drift_within_tolerance_is_not_synced() {
+        let start = Instant::now();
+        let previous = status("Song", "playing", 10.0);
+        let service = synced(&previous, start);
+        let elapsed = Duration::from_secs(2);
+        let current = status("Song", "playing", 12.0 + params::SEEK_DRIFT_TOLERANCE / 2.0);
+
+        assert_eq!(
+            poll(&service, &previous, &current, start + elapsed, elapsed),
+            None
+        );
+        // Consumers keep extrapolating the previous sample
+        assert_eq!(
+            service.interpolated_position_at(start + elapsed),
+            Some(12.0)
+        );
+    }
+
+    #[test]
+    fn seek_resyncs() {
+        let start = Instant::now();
+        let previous = status("Song", "playing", 10.0);
+        let service = synced(&previous, start);
+        let elapsed = Duration::from_secs(1);
+        let current = status("Song", "playing", 90.0);
+
+        let sync = poll(&service, &previous, &current, start + elapsed, elapsed).unwrap();
+
+        assert_eq!(sync.position, 90.0);
+        assert_eq!(sync.rate, 1.0);
+        assert_eq!(
+            service.interpolated_position_at(start + elapsed),
+            Some(90.0)
+        );
+    }
+
+    #[test]
+    fn track_change_resyncs() {
+        let start = Instant::now();
+        let previous = status("Song", "playing", 10.0);
+        let service = synced(&previous, start);
+        let elapsed = Duration::from_secs(1);
+        // Close to where the previous track would be, but a different track
+        let current = status("Other", "playing", 11.0);
+
+        let sync = poll(&service, &previous, &current, start + elapsed, elapsed).unwrap();
+
+        assert_eq!(sync.position, 11.0);
+    }
+
+    #[test]
+    fn pause_resyncs_with_a_stopped_rate() {
+        let start = Instant::now();
+        let previous = status("Song", "playing", 10.0);
+        let service = synced(&previous, start);
+        let elapsed = Duration::from_secs(1);
+        let current = status("Song", "paused", 11.0);
+
+        let sync = poll(&service, &previous, &current, start + elapsed, elapsed).unwrap();
+
+        assert_eq!(sync.rate, 0.0);
+        assert_eq!(
+            service.interpolated_position_at(start + Duration::from_secs(60)),
+            Some(11.0)
+        );
+    }
+
+    #[test]
+    fn requested_resync_is_done_once() {
+        let start = Instant::now();
+        let paused = status("Song", "paused", 10.0);
+        let service = synced(&paused, start);
+
+        service.request_resync();
+
+        assert!(service.process_at(&paused, &[], start).is_some());
+        assert!(service.process_at(&paused, &[], start).is_none());
+    }
+
+    #[test]
+    fn stale_clock_is_resynced() {
+        let start = Instant::now();
+        let paused = status("Song", "paused", 10.0);
+        let service = synced(&paused, start);
+        let almost = start + params::POSITION_RESYNC_INTERVAL - Duration::from_millis(1);
+
+        assert!(service.process_at(&paused, &[], almost).is_none());
+        assert!(service
+            .process_at(&paused, &[], start + params::POSITION_RESYNC_INTERVAL)
+            .is_some());
+    }
+
+    #[test]
+    fn seeked_to_overrides_the_clock() {
+        let start = Instant::now();
+        let playing = status("Song", "playing", 10.0);
+        let service = synced(&playing, start);
+        let seeked_at = start + Duration::from_secs(2);
+
+        service.seeked_to_at(120.0, seeked_at);
+
+        // The clock runs from the seek target, at the same rate
+        assert_eq!(
+            service.interpolated_position_at(seeked_at + Duration::from_secs(3)),
+            Some(123.0)
+        );
+        // And the next status resyncs it with the player
+        let sync = service
+            .process_at(&status("Song", "playing", 121.0), &[], seeked_at)
+            .unwrap();
+        assert_eq!(sync.position, 121.0);
+    }
+
+    #[test]
+    fn seek_before_any_status_is_ignored() {
+        let service = StatusService::new(PollingConfig::default());
+
+        service.seeked_to_at(120.0, Instant::now());
+
+        assert_eq!(service.interpolated_position_at(Instant::now()), None);
+    }
+
+    #[test]
+    fn status_without_position_is_not_synced() {
+        let start = Instant::now();
+        let service = StatusService::new(PollingConfig::default());
+        let unknown = SpotifyStatus {
+            position: None,
+            ..status("Song", "playing", 0.0)
+        };
+
+        assert_eq!(service.process_at(&unknown, &[], start), None);
+        assert_eq!(service.interpolated_position_at(start), None);
+    }
+
+    #[test]
+    fn track_duration_follows_the_synced_status() {
+        let start = Instant::now();
+        let service = StatusService::new(PollingConfig::default());
+        assert_eq!(service.track_duration(), None);
+
+        service.process_at(&status("Song", "playing", 10.0), &[], start);
+
+        assert_eq!(service.track_duration(), Some(200.0));
+    }
+}
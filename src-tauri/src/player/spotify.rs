@@ -0,0 +1,514 @@
+use std::sync::Arc;
+
+use super::record::{Record, APPLESCRIPT_HELPERS};
+use super::script::{no_track_status, AppleScriptClient, OsaScriptRunner, ScriptRunner};
+use super::{MediaPlayer, PlayerCapabilities, PlayerError, SpotifyUri};
+use crate::params::{RepeatMode, SpotifyStatus, TrackMetadata};
+
+/// AppleScript printing Spotify track information as a record (see `record::Record`).
+///
+/// Times are printed as integer milliseconds so no locale specific decimal
+/// separator is involved; the fields are, in order: marker, track name, artist,
+/// album, volume, position, duration, artwork URL, player state, shuffle, repeat
+/// (Spotify only reports whether repeat is on, which maps to `context`), track
+/// id, Spotify URL, album artist, disc number, track number and popularity.
+const STATUS_SCRIPT_BODY: &str = r#"
+        tell application "Spotify"
+            if it is running then
+                set US to character id 31
+                set trackName to my clean_field(name of current track)
+                set artistName to my clean_field(artist of current track)
+                set albumName to my clean_field(album of current track)
+                set trackVolume to sound volume as integer
+                set positionMs to (player position * 1000) as integer
+                set durationMs to (duration of current track) as integer
+                set albumCover to my clean_field(artwork url of current track)
+                set playerState to player state as text
+                set shuffleState to shuffling as text
+                set repeatState to repeating as text
+                set trackId to my clean_field(id of current track)
+                set trackUrl to my clean_field(spotify url of current track)
+                set albumArtist to my clean_field(album artist of current track)
+                set discNumber to my clean_field(disc number of current track)
+                set trackNumber to my clean_field(track number of current track)
+                set trackPopularity to my clean_field(popularity of current track)
+
+                return "ok" & US & trackName & US & artistName & US & albumName & US & trackVolume & US & positionMs & US & durationMs & US & albumCover & US & playerState & US & shuffleState & US & repeatState & US & trackId & US & trackUrl & US & albumArtist & US & discNumber & US & trackNumber & US & trackPopularity
+            else
+                return "not_running"
+            end if
+        end tell
+"#;
+
+/// Number of fields in the record printed by the status script, including the marker.
+const STATUS_FIELDS: usize = 17;
+
+/// Builds the AppleScript printing the Spotify status record.
+fn status_script() -> String {
+    format!("{}{}", APPLESCRIPT_HELPERS, STATUS_SCRIPT_BODY)
+}
+
+/// AppleScript toggling playback (play/pause).
+const TOGGLE_PLAYBACK_SCRIPT: &str = r#"
+        tell application "Spotify"
+            if it is running then
+                if player state is playing then
+                    pause
+                else
+                    play
+                end if
+            end if
+        end tell
+"#;
+
+/// AppleScript skipping to the next track.
+const NEXT_TRACK_SCRIPT: &str = r#"
+        tell application "Spotify"
+            if it is running then
+                next track
+            end if
+        end tell
+"#;
+
+/// AppleScript returning to the previous track.
+const PREVIOUS_TRACK_SCRIPT: &str = r#"
+        tell application "Spotify"
+            if it is running then
+                previous track
+            end if
+        end tell
+"#;
+
+/// Builds the AppleScript setting the track position (in seconds).
+fn set_position_script(position: f64) -> String {
+    format!(
+        r#"
+        tell application "Spotify"
+            if it is running then
+                set player position to {}
+            end if
+        end tell
+        "#,
+        position
+    )
+}
+
+/// Builds the AppleScript setting the volume level (0–100).
+fn set_volume_script(level: u32) -> String {
+    format!(
+        r#"
+        tell application "Spotify"
+            if it is running then
+                set sound volume to {}
+            end if
+        end tell
+        "#,
+        level
+    )
+}
+
+/// Builds the AppleScript enabling or disabling shuffle.
+fn set_shuffle_script(enabled: bool) -> String {
+    format!(
+        r#"
+        tell application "Spotify"
+            if it is running then
+                set shuffling to {}
+            end if
+        end tell
+        "#,
+        enabled
+    )
+}
+
+/// Builds the AppleScript turning repeat on or off.
+fn set_repeating_script(enabled: bool) -> String {
+    format!(
+        r#"
+        tell application "Spotify"
+            if it is running then
+                set repeating to {}
+            end if
+        end tell
+        "#,
+        enabled
+    )
+}
+
+/// Builds the AppleScript playing a track or context URI.
+///
+/// The URI is validated by `SpotifyUri`, so it holds no quotes or backslashes.
+fn play_uri_script(uri: &SpotifyUri) -> String {
+    format!(
+        r#"
+        tell application "Spotify"
+            if it is running then
+                play track "{}"
+            end if
+        end tell
+        "#,
+        uri
+    )
+}
+
+/// Backend controlling the Spotify desktop app through AppleScript (macOS only).
+pub struct SpotifyPlayer {
+    client: AppleScriptClient, // Runs the AppleScript snippets
+}
+
+impl SpotifyPlayer {
+    /// Creates a new Spotify backend running scripts through `osascript`.
+    pub fn new() -> Self {
+        Self::with_runner(Arc::new(OsaScriptRunner))
+    }
+
+    /// Creates a new Spotify backend running scripts through the given runner.
+    pub fn with_runner(runner: Arc<dyn ScriptRunner>) -> Self {
+        Self {
+            client: AppleScriptClient::new(runner),
+        }
+    }
+}
+
+impl Default for SpotifyPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaPlayer for SpotifyPlayer {
+    fn name(&self) -> &str {
+        "Spotify"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: true,
+            can_shuffle: true,
+            can_repeat: true,
+            can_play_uri: true,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        match self.client.query(&status_script())? {
+            Some(stdout) => parse_status(&stdout),
+            None => Ok(no_track_status()),
+        }
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        self.client.command(TOGGLE_PLAYBACK_SCRIPT)
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.client.command(NEXT_TRACK_SCRIPT)
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.client.command(PREVIOUS_TRACK_SCRIPT)
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        self.client.command(&set_position_script(position))
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        self.client.command(&set_volume_script(level))
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        self.client.command(&set_shuffle_script(enabled))
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        match mode {
+            RepeatMode::Off => self.client.command(&set_repeating_script(false)),
+            RepeatMode::Context => self.client.command(&set_repeating_script(true)),
+            RepeatMode::Track => Err(PlayerError::unsupported("repeat track")),
+        }
+    }
+
+    fn play_uri(&self, uri: &SpotifyUri) -> Result<(), PlayerError> {
+        self.client.command(&play_uri_script(uri))
+    }
+}
+
+/// Parses the record printed by the status script.
+///
+/// A `not_running` record is reported as `PlayerNotRunning` rather than as a status.
+pub fn parse_status(stdout: &str) -> Result<SpotifyStatus, PlayerError> {
+    let record = Record::parse(stdout);
+    match record.marker() {
+        "ok" => record.expect_fields(STATUS_FIELDS)?,
+        "not_running" => return Err(PlayerError::PlayerNotRunning),
+        marker => {
+            return Err(PlayerError::parse(format!(
+                "unexpected marker {:?}",
+                marker
+            )))
+        }
+    }
+
+    Ok(SpotifyStatus {
+        track_name: record.text(1),
+        artist_name: record.text(2),
+        metadata: TrackMetadata {
+            track_id: record.text(11),
+            uri: record.text(12),
+            album_name: record.text(3),
+            album_artist: record.text(13),
+            disc_number: record.count(14),
+            track_number: record.count(15),
+            explicit: None,
+            popularity: record.count(16),
+        },
+        track_volume: record
+            .number(4)
+            .map(|volume| volume.round().clamp(0.0, 100.0) as u32),
+        position: record.number(5).map(|ms| ms / 1000.0),
+        track_duration: record.number(6).map(|ms| ms / 1000.0),
+        album_cover: record.text(7),
+        player_state: record.text(8),
+        shuffle: record.boolean(9),
+        repeat_mode: record.boolean(10).map(|repeating| {
+            if repeating {
+                RepeatMode::Context
+            } else {
+                RepeatMode::Off
+            }
+        }),
+        is_saved: None,
+        device: None,
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::player::script::{FakeScriptRunner, ScriptOutput};
+
+    /// Status record of a playing track, as printed by the status script.
+    const PLAYING_RECORD: &str = "ok\u{1f}Song\u{1f}Artist\u{1f}Album\u{1f}64\u{1f}12500\u{1f}200000\u{1f}https://i.scdn.co/image/a\u{1f}playing\u{1f}true\u{1f}false\u{1f}spotify:track:4uLU6hMCjMI75M1A2tKUQC\u{1f}https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC\u{1f}Album Artist\u{1f}1\u{1f}7\u{1f}0\n";
+
+    fn player() -> (Arc<FakeScriptRunner>, SpotifyPlayer) {
+        let runner = Arc::new(FakeScriptRunner::new());
+        let player = SpotifyPlayer::with_runner(runner.clone());
+        (runner, player)
+    }
+
+    #[test]
+    fn commands_send_the_expected_scripts() {
+        let (runner, player) = player();
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.previous_track().unwrap();
+        player.set_position(42.5).unwrap();
+        player.set_volume(30).unwrap();
+        player.set_shuffle(true).unwrap();
+        player.set_repeat(RepeatMode::Context).unwrap();
+        player.set_repeat(RepeatMode::Off).unwrap();
+
+        assert_eq!(
+            runner.scripts(),
+            vec![
+                TOGGLE_PLAYBACK_SCRIPT.to_string(),
+                NEXT_TRACK_SCRIPT.to_string(),
+                PREVIOUS_TRACK_SCRIPT.to_string(),
+                set_position_script(42.5),
+                set_volume_script(30),
+                set_shuffle_script(true),
+                set_repeating_script(true),
+                set_repeating_script(false),
+            ]
+        );
+        assert!(runner.scripts()[3].contains("set player position to 42.5"));
+    }
+
+    #[test]
+    fn uri_is_played_as_given() {
+        let (runner, player) = player();
+        let uri = SpotifyUri::parse("spotify:album:4uLU6hMCjMI75M1A2tKUQC").unwrap();
+
+        player.play_uri(&uri).unwrap();
+
+        assert_eq!(runner.scripts(), vec![play_uri_script(&uri)]);
+        assert!(
+            runner.scripts()[0].contains(r#"play track "spotify:album:4uLU6hMCjMI75M1A2tKUQC""#)
+        );
+    }
+
+    #[test]
+    fn status_runs_the_status_script_and_parses_its_record() {
+        let (runner, player) = player();
+        runner.push_output(ScriptOutput::success(PLAYING_RECORD));
+
+        let status = player.status().unwrap();
+
+        assert_eq!(runner.scripts(), vec![status_script()]);
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("Artist"));
+        assert_eq!(
+            status.metadata,
+            TrackMetadata {
+                track_id: Some("spotify:track:4uLU6hMCjMI75M1A2tKUQC".to_string()),
+                uri: Some("https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC".to_string()),
+                album_name: Some("Album".to_string()),
+                album_artist: Some("Album Artist".to_string()),
+                disc_number: Some(1),
+                track_number: Some(7),
+                explicit: None,
+                // Zero reads as unknown, as for disc and track numbers
+                popularity: None,
+            }
+        );
+        assert_eq!(status.track_volume, Some(64));
+        assert_eq!(status.position, Some(12.5));
+        assert_eq!(status.track_duration, Some(200.0));
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(true));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Off));
+    }
+
+    #[test]
+    fn repeating_maps_to_context_repeat() {
+        let record = PLAYING_RECORD.replace("true\u{1f}false", "false\u{1f}true");
+
+        let status = parse_status(&record).unwrap();
+
+        assert_eq!(status.shuffle, Some(false));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Context));
+    }
+
+    #[test]
+    fn repeating_a_single_track_is_unsupported() {
+        let (runner, player) = player();
+
+        assert_eq!(
+            player.set_repeat(RepeatMode::Track).unwrap_err(),
+            PlayerError::unsupported("repeat track")
+        );
+        assert!(runner.scripts().is_empty());
+    }
+
+    #[test]
+    fn not_running_marker_is_reported_as_not_running() {
+        let (runner, player) = player();
+        runner.push_output(ScriptOutput::success("not_running\n"));
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn unexpected_records_are_parse_errors() {
+        for stdout in ["ok\u{1f}Song\n", "busy\n", ""] {
+            assert!(
+                matches!(parse_status(stdout), Err(PlayerError::ParseError { .. })),
+                "{:?}",
+                stdout
+            );
+        }
+    }
+
+    #[test]
+    fn stderr_is_mapped_to_the_matching_error() {
+        let (runner, player) = player();
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Can’t get application \"Spotify\". (-1728)\n",
+        ));
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Spotify got an error: Application isn’t running. (-600)\n",
+        ));
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Some other failure. (-2753)\n",
+        ));
+
+        assert_eq!(
+            player.next_track().unwrap_err(),
+            PlayerError::PlayerNotInstalled
+        );
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+        assert_eq!(
+            player.next_track().unwrap_err(),
+            PlayerError::ScriptFailed {
+                stderr: "execution error: Some other failure. (-2753)".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn running_without_track_is_an_empty_status() {
+        let (runner, player) = player();
+        runner.push_output(ScriptOutput::failure(
+            1,
+            "execution error: Spotify got an error: Can’t get name of current track. (-1728)\n",
+        ));
+
+        let status = player.status().unwrap();
+
+        assert_eq!(status.track_name, None);
+        assert_eq!(status.player_state.as_deref(), Some("stopped"));
+    }
+
+    #[test]
+    fn timeout_is_reported_as_timeout() {
+        let (runner, player) = player();
+        runner.push_timeout();
+        runner.push_timeout();
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::Timeout);
+        assert_eq!(player.toggle_playback().unwrap_err(), PlayerError::Timeout);
+    }
+
+    /// Runner that takes a while per script and records how many ran at once.
+    #[derive(Default)]
+    struct SlowRunner {
+        running: AtomicUsize,     // Scripts currently running
+        max_running: AtomicUsize, // Most scripts seen running at once
+    }
+
+    impl ScriptRunner for SlowRunner {
+        fn run(&self, _script: &str, _timeout: Duration) -> io::Result<ScriptOutput> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(ScriptOutput::success(PLAYING_RECORD))
+        }
+    }
+
+    #[test]
+    fn status_queries_are_serialized() {
+        let runner = Arc::new(SlowRunner::default());
+        let player = Arc::new(SpotifyPlayer::with_runner(runner.clone()));
+
+        let pollers: Vec<_> = (0..4)
+            .map(|_| {
+                let player = player.clone();
+                thread::spawn(move || player.status().unwrap())
+            })
+            .collect();
+        for poller in pollers {
+            poller.join().unwrap();
+        }
+
+        assert_eq!(runner.max_running.load(Ordering::SeqCst), 1);
+    }
+}
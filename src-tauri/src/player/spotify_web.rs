@@ -0,0 +1,1164 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{MediaPlayer, PlayerCapabilities, PlayerError, SpotifyUri};
+use crate::params::{self, PlaybackDevice, QueueTrack, RepeatMode, SpotifyStatus, TrackMetadata};
+
+pub mod auth;
+
+pub use auth::{KeyringTokenStore, MemoryTokenStore, SpotifyAuth, TokenStore, Tokens};
+
+/// Playback state returned by `GET /me/player`.
+#[derive(Debug, Deserialize)]
+struct PlaybackState {
+    device: Option<Device>,
+    progress_ms: Option<u64>,
+    is_playing: bool,
+    item: Option<Item>,
+    shuffle_state: Option<bool>,
+    repeat_state: Option<String>, // `off`, `track` or `context`
+}
+
+/// Spotify Connect device, as listed by `GET /me/player/devices`.
+#[derive(Debug, Deserialize)]
+struct Device {
+    id: Option<String>, // Absent for restricted devices
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String, // `Computer`, `Smartphone`, `Speaker`...
+    volume_percent: Option<u32>,
+}
+
+impl Device {
+    /// Maps the device onto the device model.
+    fn into_playback_device(self) -> PlaybackDevice {
+        PlaybackDevice {
+            id: self.id,
+            name: self.name,
+            device_type: self.kind,
+            is_active: self.is_active,
+            volume: self.volume_percent,
+        }
+    }
+}
+
+/// Devices returned by `GET /me/player/devices`.
+#[derive(Debug, Deserialize)]
+struct Devices {
+    #[serde(default)]
+    devices: Vec<Device>,
+}
+
+/// Queue returned by `GET /me/player/queue`.
+#[derive(Debug, Deserialize)]
+struct Queue {
+    #[serde(default)]
+    queue: Vec<Item>, // Upcoming tracks and episodes
+}
+
+/// Track or podcast episode being played.
+#[derive(Debug, Deserialize)]
+struct Item {
+    #[serde(rename = "type")]
+    kind: Option<String>, // `track` or `episode`
+    id: Option<String>, // Absent for local files
+    uri: Option<String>,
+    name: String,
+    duration_ms: u64,
+    disc_number: Option<u32>,  // Set for tracks only
+    track_number: Option<u32>, // Set for tracks only
+    explicit: Option<bool>,
+    popularity: Option<u32>, // Set for tracks only
+    #[serde(default)]
+    artists: Vec<NamedObject>, // Artists of a track
+    album: Option<Album>,    // Album of a track
+    show: Option<NamedObject>, // Show of an episode
+    #[serde(default)]
+    images: Vec<Image>, // Images of an episode
+}
+
+/// Album of a track.
+#[derive(Debug, Deserialize)]
+struct Album {
+    name: Option<String>,
+    #[serde(default)]
+    artists: Vec<NamedObject>,
+    #[serde(default)]
+    images: Vec<Image>,
+}
+
+/// Any object identified by its name (artist, show).
+#[derive(Debug, Deserialize)]
+struct NamedObject {
+    name: String,
+}
+
+/// Cover image, listed from the largest to the smallest.
+#[derive(Debug, Deserialize)]
+struct Image {
+    url: String,
+}
+
+/// Track or episode as addressed by the library endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LibraryItem {
+    id: String,    // Spotify id of the item
+    episode: bool, // Whether the item is a podcast episode
+}
+
+impl LibraryItem {
+    /// Returns the library item for a playing item, `None` for local files.
+    fn of(item: &Item) -> Option<Self> {
+        Some(Self {
+            id: item.id.clone()?,
+            episode: item.kind.as_deref() == Some("episode"),
+        })
+    }
+
+    /// Returns the path of the library endpoint holding items of this kind.
+    fn path(&self) -> &'static str {
+        if self.episode {
+            "/me/episodes"
+        } else {
+            "/me/tracks"
+        }
+    }
+}
+
+/// Saved state of an item, `None` if the library did not answer for it.
+type SavedState = Option<(LibraryItem, Option<bool>)>;
+
+/// Backend controlling Spotify through the Web API, on any device of the account.
+pub struct SpotifyWebPlayer {
+    auth: Arc<SpotifyAuth>,   // Provides the access tokens
+    api_url: String,          // Base URL of the Web API
+    agent: ureq::Agent,       // HTTP client for the Web API
+    saved: Mutex<SavedState>, // Saved state of the last seen item
+}
+
+impl SpotifyWebPlayer {
+    /// Creates a backend talking to the Web API at `api_url` with the given authenticator.
+    pub fn new(auth: Arc<SpotifyAuth>, api_url: impl Into<String>) -> Self {
+        Self {
+            auth,
+            api_url: api_url.into(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+            saved: Mutex::new(None),
+        }
+    }
+
+    /// Sends a request to the Web API, refreshing the access token once if it was rejected.
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> Result<ureq::Response, PlayerError> {
+        let url = format!("{}{}", self.api_url, path);
+        let mut token = self.auth.access_token()?;
+        let mut refreshed = false;
+
+        loop {
+            let mut request = self
+                .agent
+                .request(method, &url)
+                .set("Authorization", &format!("Bearer {}", token));
+            for (name, value) in query {
+                request = request.query(name, value);
+            }
+            let result = match (method, body) {
+                ("GET", _) => request.call(),
+                (_, Some(body)) => request.send_json(body),
+                // Spotify rejects bodyless PUT/POST requests without a Content-Length
+                (_, None) => request.send_bytes(&[]),
+            };
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(ureq::Error::Status(401, _)) if !refreshed => {
+                    token = self.auth.refresh(&token)?;
+                    refreshed = true;
+                }
+                Err(e) => return Err(api_error(e)),
+            }
+        }
+    }
+
+    /// Returns whether the item is saved in the library, querying it only when the
+    /// item differs from the last one seen. A failed query is not cached, so it is
+    /// retried at the next poll.
+    fn is_saved(&self, item: LibraryItem) -> Option<bool> {
+        if let Some((known, saved)) = self.saved_state().as_ref() {
+            if *known == item {
+                return *saved;
+            }
+        }
+
+        let path = format!("{}/contains", item.path());
+        let contains = self
+            .send("GET", &path, &[("ids", &item.id)], None)
+            .and_then(|response| {
+                response
+                    .into_json::<Vec<bool>>()
+                    .map_err(|e| PlayerError::parse(e.to_string()))
+            });
+        match contains {
+            Ok(contains) => {
+                let saved = contains.first().copied();
+                *self.saved_state() = Some((item, saved));
+                saved
+            }
+            Err(e) => {
+                // Not fatal for the status, e.g. a token granted before the library scopes
+                log::warn!("Failed to query the saved state: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Locks the cached saved state, recovering from a poisoned lock.
+    fn saved_state(&self) -> MutexGuard<'_, SavedState> {
+        self.saved.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Fetches the playback state, `None` if nothing is playing on any device.
+    fn playback_state(&self) -> Result<Option<PlaybackState>, PlayerError> {
+        let response = self.send("GET", "/me/player", &[], None)?;
+        if response.status() == 204 {
+            return Ok(None);
+        }
+        response
+            .into_json()
+            .map(Some)
+            .map_err(|e| PlayerError::parse(e.to_string()))
+    }
+}
+
+impl MediaPlayer for SpotifyWebPlayer {
+    fn name(&self) -> &str {
+        "Spotify Web API"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: true,
+            can_shuffle: true,
+            can_repeat: true,
+            can_play_uri: true,
+            can_save: true,
+            can_queue: true,
+            can_transfer: true,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        let state = self
+            .playback_state()?
+            .ok_or(PlayerError::PlayerNotRunning)?;
+        let item = state.item.as_ref().and_then(LibraryItem::of);
+        let mut status = map_status(state);
+        status.is_saved = item.and_then(|item| self.is_saved(item));
+        Ok(status)
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        let playing = self.playback_state()?.is_some_and(|state| state.is_playing);
+        let path = if playing {
+            "/me/player/pause"
+        } else {
+            "/me/player/play"
+        };
+        self.send("PUT", path, &[], None).map(|_| ())
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.send("POST", "/me/player/next", &[], None).map(|_| ())
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.send("POST", "/me/player/previous", &[], None)
+            .map(|_| ())
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        let position_ms = ((position.max(0.0) * 1000.0).round() as u64).to_string();
+        self.send(
+            "PUT",
+            "/me/player/seek",
+            &[("position_ms", &position_ms)],
+            None,
+        )
+        .map(|_| ())
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        let level = level.to_string();
+        self.send(
+            "PUT",
+            "/me/player/volume",
+            &[("volume_percent", &level)],
+            None,
+        )
+        .map(|_| ())
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        let state = enabled.to_string();
+        self.send("PUT", "/me/player/shuffle", &[("state", &state)], None)
+            .map(|_| ())
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        let state = match mode {
+            RepeatMode::Off => "off",
+            RepeatMode::Track => "track",
+            RepeatMode::Context => "context",
+        };
+        self.send("PUT", "/me/player/repeat", &[("state", state)], None)
+            .map(|_| ())
+    }
+
+    fn play_uri(&self, uri: &SpotifyUri) -> Result<(), PlayerError> {
+        let body = if uri.kind().is_context() {
+            serde_json::json!({ "context_uri": uri.to_string() })
+        } else {
+            serde_json::json!({ "uris": [uri.to_string()] })
+        };
+        self.send("PUT", "/me/player/play", &[], Some(&body))
+            .map(|_| ())
+    }
+
+    fn set_current_track_saved(&self, saved: bool) -> Result<(), PlayerError> {
+        let item = self
+            .playback_state()?
+            .ok_or(PlayerError::PlayerNotRunning)?
+            .item
+            .as_ref()
+            .and_then(LibraryItem::of)
+            .ok_or_else(|| PlayerError::unsupported("save local file"))?;
+
+        let method = if saved { "PUT" } else { "DELETE" };
+        self.send(method, item.path(), &[("ids", &item.id)], None)
+            .map_err(|e| match e {
+                // A token granted before the library scopes were requested needs a new login
+                PlayerError::Unsupported { .. } => PlayerError::NotAuthenticated,
+                e => e,
+            })?;
+        *self.saved_state() = Some((item, Some(saved)));
+        Ok(())
+    }
+
+    fn queue(&self) -> Result<Vec<QueueTrack>, PlayerError> {
+        let response = self.send("GET", "/me/player/queue", &[], None)?;
+        // Like the playback state, the queue is empty when no device is active
+        if response.status() == 204 {
+            return Err(PlayerError::PlayerNotRunning);
+        }
+        let queue: Queue = response
+            .into_json()
+            .map_err(|e| PlayerError::parse(e.to_string()))?;
+        Ok(queue
+            .queue
+            .iter()
+            .take(params::QUEUE_MAX_LENGTH)
+            .map(map_track)
+            .collect())
+    }
+
+    fn add_to_queue(&self, uri: &SpotifyUri) -> Result<(), PlayerError> {
+        let uri = uri.to_string();
+        self.send("POST", "/me/player/queue", &[("uri", &uri)], None)
+            .map(|_| ())
+    }
+
+    fn devices(&self) -> Result<Vec<PlaybackDevice>, PlayerError> {
+        let response = self.send("GET", "/me/player/devices", &[], None)?;
+        if response.status() == 204 {
+            return Ok(Vec::new());
+        }
+        let devices: Devices = response
+            .into_json()
+            .map_err(|e| PlayerError::parse(e.to_string()))?;
+        Ok(devices
+            .devices
+            .into_iter()
+            .map(Device::into_playback_device)
+            .collect())
+    }
+
+    fn transfer_playback(&self, device_id: &str, play: bool) -> Result<(), PlayerError> {
+        let body = serde_json::json!({ "device_ids": [device_id], "play": play });
+        self.send("PUT", "/me/player", &[], Some(&body))
+            .map(|_| ())
+            .map_err(|e| match e {
+                // Here a 404 means that the device is unknown, not that none is active
+                PlayerError::PlayerNotRunning => {
+                    PlayerError::invalid_argument(format!("unknown device {}", device_id))
+                }
+                e => e,
+            })
+    }
+}
+
+/// Maps the Web API playback state onto the status model.
+fn map_status(state: PlaybackState) -> SpotifyStatus {
+    let track = state.item.as_ref().map(map_track).unwrap_or_default();
+    let player_state = if state.is_playing {
+        "playing"
+    } else {
+        "paused"
+    };
+
+    SpotifyStatus {
+        track_name: track.track_name,
+        artist_name: track.artist_name,
+        metadata: track.metadata,
+        track_volume: state
+            .device
+            .as_ref()
+            .and_then(|device| device.volume_percent),
+        position: state.progress_ms.map(|ms| ms as f64 / 1000.0),
+        track_duration: track.track_duration,
+        album_cover: track.album_cover,
+        player_state: Some(player_state.to_string()),
+        shuffle: state.shuffle_state,
+        repeat_mode: state
+            .repeat_state
+            .as_deref()
+            .and_then(|repeat| match repeat {
+                "off" => Some(RepeatMode::Off),
+                "track" => Some(RepeatMode::Track),
+                "context" => Some(RepeatMode::Context),
+                _ => None,
+            }),
+        is_saved: None, // Filled in by the backend from its cache
+        device: state.device.map(Device::into_playback_device),
+        error: None,
+    }
+}
+
+/// Maps a track or episode onto the track model.
+fn map_track(item: &Item) -> QueueTrack {
+    let album = item.album.as_ref();
+    QueueTrack {
+        track_name: Some(item.name.clone()),
+        artist_name: join_names(&item.artists)
+            .or_else(|| item.show.as_ref().map(|show| show.name.clone())),
+        track_duration: Some(item.duration_ms as f64 / 1000.0),
+        album_cover: album
+            .map_or(&item.images, |album| &album.images)
+            .first()
+            .map(|image| image.url.clone()),
+        metadata: TrackMetadata {
+            track_id: item.id.clone(),
+            uri: item.uri.clone(),
+            album_name: album.and_then(|album| album.name.clone()),
+            album_artist: album.and_then(|album| join_names(&album.artists)),
+            disc_number: item.disc_number,
+            track_number: item.track_number,
+            explicit: item.explicit,
+            popularity: item.popularity,
+        },
+    }
+}
+
+/// Joins the names of artists with commas, `None` if there are none.
+fn join_names(objects: &[NamedObject]) -> Option<String> {
+    let names: Vec<&str> = objects.iter().map(|o| o.name.as_str()).collect();
+    (!names.is_empty()).then(|| names.join(", "))
+}
+
+/// Maps a failed Web API request to a `PlayerError`.
+fn api_error(error: ureq::Error) -> PlayerError {
+    match error {
+        ureq::Error::Status(401, _) => PlayerError::NotAuthenticated,
+        ureq::Error::Status(403, _) => PlayerError::unsupported("requires Spotify Premium"),
+        // The player endpoints answer 404 when no device is active
+        ureq::Error::Status(404, _) => PlayerError::PlayerNotRunning,
+        ureq::Error::Status(429, response) => PlayerError::backend(format!(
+            "Rate limited, retry after {}s",
+            response.header("Retry-After").unwrap_or("?")
+        )),
+        ureq::Error::Status(code, _) => {
+            PlayerError::backend(format!("Web API answered with status {}", code))
+        }
+        ureq::Error::Transport(transport) => PlayerError::backend(transport),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SpotifyWebConfig;
+    use crate::player::testing::{MockRequest, MockServer};
+
+    /// Returns a backend talking to the mock Web API, authenticated by the mock
+    /// accounts service with a valid access token `A1`.
+    fn player(accounts: &MockServer, api: &MockServer) -> SpotifyWebPlayer {
+        let store = MemoryTokenStore::default();
+        store
+            .save(&Tokens {
+                access_token: "A1".to_string(),
+                refresh_token: "R1".to_string(),
+                expires_at: u64::MAX,
+            })
+            .unwrap();
+        let config = SpotifyWebConfig {
+            client_id: Some("client".to_string()),
+            accounts_url: accounts.url.clone(),
+            api_url: api.url.clone(),
+            ..SpotifyWebConfig::default()
+        };
+        let auth = SpotifyAuth::new(config, Box::new(store));
+        SpotifyWebPlayer::new(Arc::new(auth), api.url.clone())
+    }
+
+    /// Accounts service granting `A2`.
+    fn accounts() -> MockServer {
+        MockServer::start(|_| {
+            (
+                200,
+                r#"{"access_token":"A2","refresh_token":"R2","expires_in":3600}"#.to_string(),
+            )
+        })
+    }
+
+    /// Returns a backend talking to a mock Web API answering with `handler`, with a
+    /// token that needs no refresh.
+    fn api(
+        handler: impl Fn(&MockRequest) -> (u16, String) + Send + Sync + 'static,
+    ) -> (MockServer, SpotifyWebPlayer) {
+        let accounts = MockServer::start(|_| (500, String::new()));
+        let api = MockServer::start(handler);
+        let player = player(&accounts, &api);
+        (api, player)
+    }
+
+    /// Answer of the Web API when no device is active.
+    fn no_active_device() -> (u16, String) {
+        (
+            404,
+            r#"{"error":{"status":404,"message":"Player command failed: No active device found","reason":"NO_ACTIVE_DEVICE"}}"#.to_string(),
+        )
+    }
+
+    /// Answer of the Web API to a player command of a free account.
+    fn premium_required() -> (u16, String) {
+        (
+            403,
+            r#"{"error":{"status":403,"message":"Player command failed: Premium required","reason":"PREMIUM_REQUIRED"}}"#.to_string(),
+        )
+    }
+
+    /// Returns the status mapped from a recorded `GET /me/player` body, with the item
+    /// saved in the library.
+    fn status_of(body: &'static str) -> SpotifyStatus {
+        let (api, player) = api(move |request| match request.path.as_str() {
+            "/me/player" => (200, body.to_string()),
+            _ => (200, "[true]".to_string()),
+        });
+        let status = player.status().unwrap();
+        assert_eq!(api.request_lines()[0], "GET /me/player");
+        status
+    }
+
+    #[test]
+    fn playing_track_is_mapped() {
+        let status = status_of(
+            r#"{
+                "device": {"id": "D1", "is_active": true, "name": "Laptop", "type": "Computer", "volume_percent": 40},
+                "progress_ms": 12345, "is_playing": true, "shuffle_state": false, "repeat_state": "off",
+                "currently_playing_type": "track",
+                "item": {
+                    "type": "track", "id": "T1", "uri": "spotify:track:T1", "name": "Song", "duration_ms": 200500,
+                    "disc_number": 1, "track_number": 4, "explicit": true, "popularity": 63,
+                    "artists": [{"name": "A"}, {"name": "B"}],
+                    "album": {"name": "Album", "artists": [{"name": "A"}], "images": [{"url": "https://i.scdn.co/image/large"}, {"url": "https://i.scdn.co/image/small"}]}
+                }
+            }"#,
+        );
+
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("A, B"));
+        assert_eq!(
+            status.metadata,
+            TrackMetadata {
+                track_id: Some("T1".to_string()),
+                uri: Some("spotify:track:T1".to_string()),
+                album_name: Some("Album".to_string()),
+                album_artist: Some("A".to_string()),
+                disc_number: Some(1),
+                track_number: Some(4),
+                explicit: Some(true),
+                popularity: Some(63),
+            }
+        );
+        assert_eq!(status.track_volume, Some(40));
+        assert_eq!(status.position, Some(12.345));
+        assert_eq!(status.track_duration, Some(200.5));
+        assert_eq!(
+            status.album_cover.as_deref(),
+            Some("https://i.scdn.co/image/large")
+        );
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(false));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Off));
+        assert_eq!(status.is_saved, Some(true));
+        assert_eq!(
+            status.device,
+            Some(PlaybackDevice {
+                id: Some("D1".to_string()),
+                name: "Laptop".to_string(),
+                device_type: "Computer".to_string(),
+                is_active: true,
+                volume: Some(40),
+            })
+        );
+    }
+
+    #[test]
+    fn repeat_state_is_mapped_to_repeat_mode() {
+        let cases = [
+            (
+                r#"{"is_playing": true, "item": null, "repeat_state": "off"}"#,
+                Some(RepeatMode::Off),
+            ),
+            (
+                r#"{"is_playing": true, "item": null, "repeat_state": "track"}"#,
+                Some(RepeatMode::Track),
+            ),
+            (
+                r#"{"is_playing": true, "item": null, "repeat_state": "context"}"#,
+                Some(RepeatMode::Context),
+            ),
+            (
+                r#"{"is_playing": true, "item": null, "repeat_state": "album"}"#,
+                None,
+            ),
+            (r#"{"is_playing": true, "item": null}"#, None),
+        ];
+        for (body, expected) in cases {
+            assert_eq!(status_of(body).repeat_mode, expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn podcast_episode_is_mapped_to_its_show() {
+        let status = status_of(
+            r#"{
+                "device": {"id": "D1", "is_active": true, "name": "Phone", "type": "Smartphone", "volume_percent": null},
+                "progress_ms": 0, "is_playing": false, "currently_playing_type": "episode",
+                "item": {
+                    "type": "episode", "id": "E1", "uri": "spotify:episode:E1", "name": "Episode", "duration_ms": 3600000, "explicit": false,
+                    "show": {"name": "Show"},
+                    "images": [{"url": "https://i.scdn.co/image/show"}]
+                }
+            }"#,
+        );
+
+        assert_eq!(status.track_name.as_deref(), Some("Episode"));
+        assert_eq!(status.artist_name.as_deref(), Some("Show"));
+        assert_eq!(
+            status.metadata,
+            TrackMetadata {
+                track_id: Some("E1".to_string()),
+                uri: Some("spotify:episode:E1".to_string()),
+                explicit: Some(false),
+                ..TrackMetadata::default()
+            }
+        );
+        assert_eq!(status.track_volume, None);
+        assert_eq!(status.position, Some(0.0));
+        assert_eq!(status.track_duration, Some(3600.0));
+        assert_eq!(
+            status.album_cover.as_deref(),
+            Some("https://i.scdn.co/image/show")
+        );
+        assert_eq!(status.player_state.as_deref(), Some("paused"));
+    }
+
+    #[test]
+    fn null_item_has_no_track() {
+        // Sent e.g. while an ad is playing or right after a device was selected
+        let status = status_of(
+            r#"{
+                "device": {"id": "D1", "is_active": true, "name": "Laptop", "type": "Computer", "volume_percent": 40},
+                "progress_ms": null, "is_playing": true, "currently_playing_type": "ad",
+                "item": null
+            }"#,
+        );
+
+        assert_eq!(status.track_name, None);
+        assert_eq!(status.artist_name, None);
+        assert_eq!(status.position, None);
+        assert_eq!(status.track_duration, None);
+        assert_eq!(status.album_cover, None);
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+    }
+
+    #[test]
+    fn nothing_playing_is_not_running() {
+        let (_api, player) = api(|_| (204, String::new()));
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn toggle_pauses_when_playing_and_plays_otherwise() {
+        for (is_playing, command) in [
+            (true, "PUT /me/player/pause"),
+            (false, "PUT /me/player/play"),
+        ] {
+            let state = format!(r#"{{"is_playing": {}, "item": null}}"#, is_playing);
+            let (api, player) = api(move |request| match request.path.as_str() {
+                "/me/player" => (200, state.clone()),
+                _ => (204, String::new()),
+            });
+
+            player.toggle_playback().unwrap();
+            assert_eq!(api.request_lines(), ["GET /me/player", command]);
+            assert_eq!(api.requests()[1].header("content-length"), Some("0"));
+        }
+    }
+
+    #[test]
+    fn seek_is_sent_in_rounded_milliseconds() {
+        let (api, player) = api(|_| (204, String::new()));
+
+        player.set_position(12.3456).unwrap();
+        player.set_position(0.0004).unwrap();
+        player.set_position(-3.0).unwrap();
+        assert_eq!(
+            api.request_lines(),
+            [
+                "PUT /me/player/seek?position_ms=12346",
+                "PUT /me/player/seek?position_ms=0",
+                "PUT /me/player/seek?position_ms=0",
+            ]
+        );
+    }
+
+    #[test]
+    fn volume_is_sent_as_a_percentage() {
+        let (api, player) = api(|_| (204, String::new()));
+
+        player.set_volume(35).unwrap();
+        assert_eq!(
+            api.request_lines(),
+            ["PUT /me/player/volume?volume_percent=35"]
+        );
+        assert_eq!(api.requests()[0].header("content-length"), Some("0"));
+    }
+
+    #[test]
+    fn shuffle_and_repeat_are_sent_as_states() {
+        let (api, player) = api(|_| (204, String::new()));
+
+        player.set_shuffle(true).unwrap();
+        player.set_repeat(RepeatMode::Off).unwrap();
+        player.set_repeat(RepeatMode::Track).unwrap();
+        player.set_repeat(RepeatMode::Context).unwrap();
+        assert_eq!(
+            api.request_lines(),
+            [
+                "PUT /me/player/shuffle?state=true",
+                "PUT /me/player/repeat?state=off",
+                "PUT /me/player/repeat?state=track",
+                "PUT /me/player/repeat?state=context",
+            ]
+        );
+    }
+
+    #[test]
+    fn contexts_and_tracks_are_played_with_their_own_body() {
+        let id = "4uLU6hMCjMI75M1A2tKUQC";
+        let cases = [
+            (
+                "album",
+                serde_json::json!({ "context_uri": format!("spotify:album:{}", id) }),
+            ),
+            (
+                "playlist",
+                serde_json::json!({ "context_uri": format!("spotify:playlist:{}", id) }),
+            ),
+            (
+                "artist",
+                serde_json::json!({ "context_uri": format!("spotify:artist:{}", id) }),
+            ),
+            (
+                "show",
+                serde_json::json!({ "context_uri": format!("spotify:show:{}", id) }),
+            ),
+            (
+                "track",
+                serde_json::json!({ "uris": [format!("spotify:track:{}", id)] }),
+            ),
+            (
+                "episode",
+                serde_json::json!({ "uris": [format!("spotify:episode:{}", id)] }),
+            ),
+        ];
+
+        for (kind, expected) in cases {
+            let (api, player) = api(|_| (204, String::new()));
+            let uri = SpotifyUri::parse(&format!("spotify:{}:{}", kind, id)).unwrap();
+
+            player.play_uri(&uri).unwrap();
+
+            let requests = api.requests();
+            assert_eq!(api.request_lines(), ["PUT /me/player/play"]);
+            let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+            assert_eq!(body, expected, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn rejected_token_is_refreshed_once_and_retried() {
+        let accounts = accounts();
+        let api = MockServer::start(|request| match request.header("authorization") {
+            Some("Bearer A2") => (204, String::new()),
+            _ => (401, r#"{"error":{"status":401}}"#.to_string()),
+        });
+        let player = player(&accounts, &api);
+
+        player.next_track().unwrap();
+        assert_eq!(
+            api.request_lines(),
+            ["POST /me/player/next", "POST /me/player/next"]
+        );
+        assert_eq!(accounts.requests().len(), 1);
+
+        // The new token is used directly afterwards
+        player.previous_track().unwrap();
+        assert_eq!(accounts.requests().len(), 1);
+        assert_eq!(
+            api.requests().last().unwrap().header("authorization"),
+            Some("Bearer A2")
+        );
+    }
+
+    #[test]
+    fn token_rejected_after_refresh_is_not_authenticated() {
+        let accounts = accounts();
+        let api = MockServer::start(|_| (401, String::new()));
+        let player = player(&accounts, &api);
+
+        assert_eq!(player.next_track(), Err(PlayerError::NotAuthenticated));
+        assert_eq!(api.requests().len(), 2);
+        assert_eq!(accounts.requests().len(), 1);
+    }
+
+    /// Playback state of a playing track.
+    const PLAYING: &str = r#"{
+        "device": {"id": "D1", "is_active": true, "name": "Laptop", "type": "Computer", "volume_percent": 40},
+        "progress_ms": 12500, "is_playing": true, "shuffle_state": false, "repeat_state": "context",
+        "item": {"type": "track", "id": "T1", "uri": "spotify:track:T1", "name": "Song", "duration_ms": 200000}
+    }"#;
+
+    /// Playback state of a playing podcast episode.
+    const EPISODE: &str = r#"{
+        "progress_ms": 0, "is_playing": true,
+        "item": {"type": "episode", "id": "E1", "uri": "spotify:episode:E1", "name": "Episode", "duration_ms": 3600000}
+    }"#;
+
+    /// Playback state of a playing local file, which has no id.
+    const LOCAL_FILE: &str = r#"{
+        "progress_ms": 0, "is_playing": true,
+        "item": {"type": "track", "id": null, "uri": "spotify:local:A:B:Song:200", "name": "Song", "duration_ms": 200000}
+    }"#;
+
+    #[test]
+    fn saved_state_is_queried_once_per_track() {
+        let (api, player) = api(|request| match request.path.as_str() {
+            "/me/player" => (200, PLAYING.to_string()),
+            _ => (200, "[true]".to_string()),
+        });
+
+        assert_eq!(player.status().unwrap().is_saved, Some(true));
+        assert_eq!(player.status().unwrap().is_saved, Some(true));
+        assert_eq!(
+            api.request_lines(),
+            [
+                "GET /me/player",
+                "GET /me/tracks/contains?ids=T1",
+                "GET /me/player"
+            ]
+        );
+    }
+
+    #[test]
+    fn episodes_are_looked_up_in_the_saved_episodes() {
+        let (api, player) = api(|request| match request.path.as_str() {
+            "/me/player" => (200, EPISODE.to_string()),
+            _ => (200, "[false]".to_string()),
+        });
+
+        assert_eq!(player.status().unwrap().is_saved, Some(false));
+        player.set_current_track_saved(true).unwrap();
+        assert_eq!(
+            api.request_lines(),
+            [
+                "GET /me/player",
+                "GET /me/episodes/contains?ids=E1",
+                "GET /me/player",
+                "PUT /me/episodes?ids=E1"
+            ]
+        );
+    }
+
+    #[test]
+    fn local_files_cannot_be_saved() {
+        let (api, player) = api(|_| (200, LOCAL_FILE.to_string()));
+
+        assert_eq!(player.status().unwrap().is_saved, None);
+        assert!(matches!(
+            player.set_current_track_saved(true),
+            Err(PlayerError::Unsupported { .. })
+        ));
+        assert_eq!(api.request_lines(), ["GET /me/player", "GET /me/player"]);
+    }
+
+    #[test]
+    fn saving_updates_the_cached_state() {
+        let (api, player) = api(|request| match request.path.as_str() {
+            "/me/player" => (200, PLAYING.to_string()),
+            "/me/tracks?ids=T1" => (200, String::new()),
+            _ => (200, "[false]".to_string()),
+        });
+
+        assert_eq!(player.status().unwrap().is_saved, Some(false));
+        player.set_current_track_saved(true).unwrap();
+        assert_eq!(player.status().unwrap().is_saved, Some(true));
+        player.set_current_track_saved(false).unwrap();
+        assert_eq!(player.status().unwrap().is_saved, Some(false));
+        let changes: Vec<_> = api
+            .request_lines()
+            .into_iter()
+            .filter(|line| !line.starts_with("GET"))
+            .collect();
+        assert_eq!(
+            changes,
+            ["PUT /me/tracks?ids=T1", "DELETE /me/tracks?ids=T1"]
+        );
+        assert_eq!(api.request_lines().len(), 8);
+    }
+
+    #[test]
+    fn saving_without_the_library_scopes_needs_a_new_login() {
+        let (_api, player) = api(|request| match request.path.as_str() {
+            "/me/player" => (200, PLAYING.to_string()),
+            _ => (403, r#"{"error":{"status":403}}"#.to_string()),
+        });
+
+        assert_eq!(
+            player.set_current_track_saved(true),
+            Err(PlayerError::NotAuthenticated)
+        );
+    }
+
+    #[test]
+    fn failed_saved_state_query_is_retried() {
+        let failures = Mutex::new(1);
+        let (api, player) = api(move |request| match request.path.as_str() {
+            "/me/player" => (200, PLAYING.to_string()),
+            _ => {
+                let mut failures = failures.lock().unwrap();
+                if *failures > 0 {
+                    *failures -= 1;
+                    (503, String::new())
+                } else {
+                    (200, "[false]".to_string())
+                }
+            }
+        });
+
+        assert_eq!(player.status().unwrap().is_saved, None);
+        assert_eq!(player.status().unwrap().is_saved, Some(false));
+        assert_eq!(player.status().unwrap().is_saved, Some(false));
+        let lookups = api
+            .request_lines()
+            .into_iter()
+            .filter(|line| line.starts_with("GET /me/tracks/contains"))
+            .count();
+        assert_eq!(lookups, 2);
+    }
+
+    const QUEUE: &str = r#"{
+        "currently_playing": {"type": "track", "id": "T0", "name": "Now", "duration_ms": 1000},
+        "queue": [
+            {
+                "type": "track", "id": "T1", "uri": "spotify:track:T1", "name": "Next",
+                "duration_ms": 201500, "disc_number": 1, "track_number": 4, "explicit": true,
+                "artists": [{"name": "A"}, {"name": "B"}],
+                "album": {"name": "Album", "artists": [{"name": "A"}], "images": [{"url": "https://i.scdn.co/image/large"}, {"url": "https://i.scdn.co/image/small"}]}
+            },
+            {
+                "type": "episode", "id": "E1", "uri": "spotify:episode:E1", "name": "Episode",
+                "duration_ms": 3600000, "show": {"name": "Show"},
+                "images": [{"url": "https://i.scdn.co/image/show"}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn queue_lists_upcoming_tracks_and_episodes() {
+        let (api, player) = api(|_| (200, QUEUE.to_string()));
+
+        let queue = player.queue().unwrap();
+        assert_eq!(api.request_lines(), ["GET /me/player/queue"]);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[0].track_name.as_deref(), Some("Next"));
+        assert_eq!(queue[0].artist_name.as_deref(), Some("A, B"));
+        assert_eq!(queue[0].track_duration, Some(201.5));
+        assert_eq!(
+            queue[0].album_cover.as_deref(),
+            Some("https://i.scdn.co/image/large")
+        );
+        assert_eq!(queue[0].metadata.album_name.as_deref(), Some("Album"));
+        assert_eq!(queue[0].metadata.track_number, Some(4));
+        assert_eq!(queue[0].metadata.explicit, Some(true));
+        assert_eq!(queue[1].artist_name.as_deref(), Some("Show"));
+        assert_eq!(
+            queue[1].album_cover.as_deref(),
+            Some("https://i.scdn.co/image/show")
+        );
+    }
+
+    #[test]
+    fn queue_is_truncated() {
+        let track = r#"{"type": "track", "id": "T", "name": "T", "duration_ms": 1000}"#;
+        let tracks = vec![track; params::QUEUE_MAX_LENGTH + 5].join(",");
+        let body = format!(r#"{{"queue": [{}]}}"#, tracks);
+        let (_api, player) = api(move |_| (200, body.clone()));
+
+        assert_eq!(player.queue().unwrap().len(), params::QUEUE_MAX_LENGTH);
+    }
+
+    #[test]
+    fn queue_without_active_device_is_not_running() {
+        let (_api, player) = api(|_| (204, String::new()));
+        assert_eq!(player.queue().unwrap_err(), PlayerError::PlayerNotRunning);
+
+        let (_api, player) = api(|_| no_active_device());
+        assert_eq!(player.queue().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn add_to_queue_sends_the_uri() {
+        let (api, player) = api(|_| (204, String::new()));
+        let uri = SpotifyUri::parse("spotify:track:4uLU6hMCjMI75M1A2tKUQC").unwrap();
+
+        player.add_to_queue(&uri).unwrap();
+        assert_eq!(
+            api.request_lines(),
+            ["POST /me/player/queue?uri=spotify%3Atrack%3A4uLU6hMCjMI75M1A2tKUQC"]
+        );
+        assert_eq!(api.requests()[0].header("content-length"), Some("0"));
+    }
+
+    #[test]
+    fn devices_are_listed() {
+        let (api, player) = api(|_| {
+            (
+                200,
+                r#"{"devices": [
+                    {"id": "D1", "is_active": true, "name": "Laptop", "type": "Computer", "volume_percent": 40},
+                    {"id": null, "is_active": false, "is_restricted": true, "name": "Office", "type": "Speaker", "volume_percent": null}
+                ]}"#
+                .to_string(),
+            )
+        });
+
+        let devices = player.devices().unwrap();
+        assert_eq!(api.request_lines(), ["GET /me/player/devices"]);
+        assert_eq!(
+            devices,
+            [
+                PlaybackDevice {
+                    id: Some("D1".to_string()),
+                    name: "Laptop".to_string(),
+                    device_type: "Computer".to_string(),
+                    is_active: true,
+                    volume: Some(40),
+                },
+                PlaybackDevice {
+                    id: None,
+                    name: "Office".to_string(),
+                    device_type: "Speaker".to_string(),
+                    is_active: false,
+                    volume: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn transfer_sends_the_device_and_play_flag() {
+        let (api, player) = api(|_| (204, String::new()));
+
+        player.transfer_playback("D2", true).unwrap();
+        player.transfer_playback("D1", false).unwrap();
+        let requests = api.requests();
+        assert_eq!(api.request_lines(), ["PUT /me/player", "PUT /me/player"]);
+        assert_eq!(requests[0].body, r#"{"device_ids":["D2"],"play":true}"#);
+        assert_eq!(requests[1].body, r#"{"device_ids":["D1"],"play":false}"#);
+    }
+
+    #[test]
+    fn no_devices_is_an_empty_list() {
+        let (_api, player) = api(|_| (200, r#"{"devices": []}"#.to_string()));
+        assert!(player.devices().unwrap().is_empty());
+
+        let (_api, player) = api(|_| (204, String::new()));
+        assert!(player.devices().unwrap().is_empty());
+    }
+
+    #[test]
+    fn transfer_to_unknown_device_is_invalid() {
+        let (_api, player) = api(|_| {
+            (
+                404,
+                r#"{"error":{"status":404,"message":"Device not found"}}"#.to_string(),
+            )
+        });
+
+        assert!(matches!(
+            player.transfer_playback("gone", true),
+            Err(PlayerError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn commands_without_active_device_are_not_running() {
+        let (_api, player) = api(|request| match request.path.as_str() {
+            "/me/player" => (204, String::new()),
+            _ => no_active_device(),
+        });
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+        assert_eq!(player.next_track(), Err(PlayerError::PlayerNotRunning));
+        assert_eq!(player.set_volume(50), Err(PlayerError::PlayerNotRunning));
+    }
+
+    #[test]
+    fn free_accounts_need_premium() {
+        let (_api, player) = api(|_| premium_required());
+        let uri = SpotifyUri::parse("spotify:track:4uLU6hMCjMI75M1A2tKUQC").unwrap();
+
+        let premium = PlayerError::unsupported("requires Spotify Premium");
+        assert_eq!(player.transfer_playback("D1", true), Err(premium.clone()));
+        assert_eq!(player.add_to_queue(&uri), Err(premium.clone()));
+        assert_eq!(player.next_track(), Err(premium));
+    }
+}
@@ -0,0 +1,772 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::config::SpotifyWebConfig;
+use crate::player::PlayerError;
+
+/// Scopes requested when logging in to Spotify.
+pub const SCOPES: &str = "user-read-playback-state user-modify-playback-state \
+    user-read-currently-playing user-library-read user-library-modify";
+
+/// Keychain service under which the tokens are stored.
+const KEYRING_SERVICE: &str = "noci.spotify";
+
+/// Keychain account under which the tokens are stored.
+const KEYRING_ACCOUNT: &str = "tokens";
+
+/// Time to wait for the user to finish logging in in the browser.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Access tokens are refreshed this long before they actually expire.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Tokens obtained from the accounts service.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Tokens {
+    pub access_token: String,  // Bearer token for the Web API
+    pub refresh_token: String, // Token used to obtain new access tokens
+    pub expires_at: u64,       // Unix time (in seconds) at which the access token expires
+}
+
+impl Tokens {
+    /// Returns whether the access token is expired or about to expire.
+    fn is_expired(&self) -> bool {
+        unix_now() + EXPIRY_MARGIN.as_secs() >= self.expires_at
+    }
+}
+
+/// Persists the Spotify tokens between runs.
+pub trait TokenStore: Send + Sync {
+    /// Returns the stored tokens, if any.
+    fn load(&self) -> Option<Tokens>;
+
+    /// Stores the tokens, replacing the previous ones.
+    fn save(&self, tokens: &Tokens) -> Result<(), PlayerError>;
+
+    /// Removes the stored tokens.
+    fn clear(&self);
+}
+
+/// Stores the tokens in the system keychain (macOS Keychain, Linux kernel keyring).
+#[derive(Debug, Default)]
+pub struct KeyringTokenStore;
+
+impl KeyringTokenStore {
+    /// Returns the keychain entry holding the tokens.
+    fn entry(&self) -> Result<keyring::Entry, PlayerError> {
+        keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT).map_err(PlayerError::backend)
+    }
+}
+
+impl TokenStore for KeyringTokenStore {
+    fn load(&self) -> Option<Tokens> {
+        let secret = self.entry().ok()?.get_password().ok()?;
+        serde_json::from_str(&secret).ok()
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), PlayerError> {
+        let secret = serde_json::to_string(tokens).map_err(PlayerError::backend)?;
+        self.entry()?
+            .set_password(&secret)
+            .map_err(PlayerError::backend)
+    }
+
+    fn clear(&self) {
+        if let Ok(entry) = self.entry() {
+            let _ = entry.delete_credential();
+        }
+    }
+}
+
+/// Keeps the tokens in memory only, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<Option<Tokens>>, // Stored tokens
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> Option<Tokens> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), PlayerError> {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokens.clone());
+        Ok(())
+    }
+
+    fn clear(&self) {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Verifier and challenge of a PKCE authorization request.
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,  // Random secret sent when exchanging the code
+    pub challenge: String, // Base64url encoded SHA-256 of the verifier
+}
+
+impl Pkce {
+    /// Generates a new random verifier and its challenge.
+    pub fn generate() -> Self {
+        Self::from_verifier(random_string(64))
+    }
+
+    /// Computes the challenge of the given verifier.
+    pub fn from_verifier(verifier: String) -> Self {
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        Self {
+            verifier,
+            challenge,
+        }
+    }
+}
+
+/// Token response of the accounts service.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: u64,
+}
+
+/// Error body of the accounts service.
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String, // e.g. `invalid_grant`
+}
+
+/// Failure of a token request.
+#[derive(Debug)]
+enum TokenError {
+    InvalidGrant,        // The code or refresh token was revoked, expired or already used
+    Player(PlayerError), // Any other failure
+}
+
+impl From<PlayerError> for TokenError {
+    fn from(error: PlayerError) -> Self {
+        Self::Player(error)
+    }
+}
+
+/// Handles the OAuth authorization code flow with PKCE and keeps the tokens fresh.
+///
+/// Shared between the Spotify Web API backend and the login commands.
+pub struct SpotifyAuth {
+    config: SpotifyWebConfig,      // Client id, redirect port and service URLs
+    store: Box<dyn TokenStore>,    // Persistent storage of the tokens
+    tokens: Mutex<Option<Tokens>>, // Cached tokens, loaded from the store on creation
+    refreshing: Mutex<()>,         // Held across a refresh so that only one runs at a time
+    agent: ureq::Agent,            // HTTP client for the accounts service
+}
+
+impl SpotifyAuth {
+    /// Creates the authenticator, restoring previously stored tokens.
+    pub fn new(config: SpotifyWebConfig, store: Box<dyn TokenStore>) -> Self {
+        let tokens = store.load();
+        Self {
+            config,
+            store,
+            tokens: Mutex::new(tokens),
+            refreshing: Mutex::new(()),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+        }
+    }
+
+    /// Returns whether tokens are available.
+    pub fn is_logged_in(&self) -> bool {
+        self.tokens().is_some()
+    }
+
+    /// Returns the authorization URL the user is sent to.
+    pub fn authorize_url(&self, pkce: &Pkce, state: &str) -> Result<String, PlayerError> {
+        let client_id = self.client_id()?;
+        let url = Url::parse_with_params(
+            &format!("{}/authorize", self.config.accounts_url),
+            &[
+                ("client_id", client_id),
+                ("response_type", "code"),
+                ("redirect_uri", &self.config.redirect_uri()),
+                ("code_challenge_method", "S256"),
+                ("code_challenge", &pkce.challenge),
+                ("scope", SCOPES),
+                ("state", state),
+            ],
+        )
+        .map_err(PlayerError::backend)?;
+
+        Ok(url.into())
+    }
+
+    /// Runs the whole login flow: opens the authorization page with `open`, waits
+    /// for the redirect on the loopback port and exchanges the code for tokens.
+    pub fn login(&self, open: impl FnOnce(&str) -> io::Result<()>) -> Result<(), PlayerError> {
+        let pkce = Pkce::generate();
+        let state = random_string(16);
+        let url = self.authorize_url(&pkce, &state)?;
+
+        // Listen before opening the browser so the redirect cannot be missed
+        let listener = TcpListener::bind(("127.0.0.1", self.config.redirect_port))
+            .map_err(PlayerError::backend)?;
+        open(&url).map_err(PlayerError::backend)?;
+
+        let code = wait_for_code(&listener, &state, LOGIN_TIMEOUT)?;
+        self.exchange_code(&code, &pkce.verifier)
+    }
+
+    /// Exchanges an authorization code for tokens and stores them.
+    pub fn exchange_code(&self, code: &str, verifier: &str) -> Result<(), PlayerError> {
+        let client_id = self.client_id()?;
+        let redirect_uri = self.config.redirect_uri();
+        let response = self
+            .request_token(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &redirect_uri),
+                ("client_id", client_id),
+                ("code_verifier", verifier),
+            ])
+            .map_err(|e| match e {
+                TokenError::InvalidGrant => PlayerError::NotAuthenticated,
+                TokenError::Player(e) => e,
+            })?;
+
+        let refresh_token = response
+            .refresh_token
+            .ok_or_else(|| PlayerError::parse("token response has no refresh token"))?;
+        self.store_tokens(Tokens {
+            access_token: response.access_token,
+            refresh_token,
+            expires_at: unix_now() + response.expires_in,
+        })
+    }
+
+    /// Returns a valid access token, refreshing it first if it expired.
+    pub fn access_token(&self) -> Result<String, PlayerError> {
+        let tokens = self.tokens().ok_or(PlayerError::NotAuthenticated)?;
+        if tokens.is_expired() {
+            return self.refresh(&tokens.access_token);
+        }
+        Ok(tokens.access_token)
+    }
+
+    /// Replaces `rejected`, an access token that expired or was refused by the
+    /// Web API, and returns the new one.
+    ///
+    /// Refreshes are serialized: a caller that waited for another refresh gets the
+    /// token it obtained instead of spending the (rotated) refresh token again.
+    /// Only an `invalid_grant` answer for the stored refresh token logs the user out.
+    pub fn refresh(&self, rejected: &str) -> Result<String, PlayerError> {
+        let _refreshing = self.refreshing.lock().unwrap_or_else(|e| e.into_inner());
+        let tokens = self.tokens().ok_or(PlayerError::NotAuthenticated)?;
+        if tokens.access_token != rejected && !tokens.is_expired() {
+            return Ok(tokens.access_token);
+        }
+
+        let client_id = self.client_id()?;
+        let response = match self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &tokens.refresh_token),
+            ("client_id", client_id),
+        ]) {
+            Ok(response) => response,
+            Err(TokenError::InvalidGrant) => {
+                if self
+                    .tokens()
+                    .is_some_and(|stored| stored.refresh_token == tokens.refresh_token)
+                {
+                    self.logout();
+                }
+                return Err(PlayerError::NotAuthenticated);
+            }
+            Err(TokenError::Player(e)) => return Err(e),
+        };
+
+        // Spotify may rotate the refresh token
+        let access_token = response.access_token.clone();
+        self.store_tokens(Tokens {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token.unwrap_or(tokens.refresh_token),
+            expires_at: unix_now() + response.expires_in,
+        })?;
+        Ok(access_token)
+    }
+
+    /// Forgets the tokens, in memory and in the store.
+    pub fn logout(&self) {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.store.clear();
+    }
+
+    /// Posts a form to the token endpoint.
+    fn request_token(&self, form: &[(&str, &str)]) -> Result<TokenResponse, TokenError> {
+        let url = format!("{}/api/token", self.config.accounts_url);
+        match self.agent.post(&url).send_form(form) {
+            Ok(response) => response
+                .into_json()
+                .map_err(|e| PlayerError::parse(e.to_string()).into()),
+            Err(ureq::Error::Status(400, response)) => {
+                match response.into_json::<TokenErrorResponse>() {
+                    Ok(body) if body.error == "invalid_grant" => Err(TokenError::InvalidGrant),
+                    Ok(body) => Err(PlayerError::backend(format!(
+                        "Token request failed: {}",
+                        body.error
+                    ))
+                    .into()),
+                    Err(_) => Err(PlayerError::backend("Token request failed").into()),
+                }
+            }
+            // invalid_client: the configured client id is not accepted
+            Err(ureq::Error::Status(401, _)) => Err(PlayerError::NotAuthenticated.into()),
+            Err(e) => Err(PlayerError::backend(e).into()),
+        }
+    }
+
+    /// Caches and persists new tokens.
+    fn store_tokens(&self, tokens: Tokens) -> Result<(), PlayerError> {
+        self.store.save(&tokens)?;
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokens);
+        Ok(())
+    }
+
+    /// Returns a copy of the cached tokens.
+    fn tokens(&self) -> Option<Tokens> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the configured client id.
+    fn client_id(&self) -> Result<&str, PlayerError> {
+        self.config
+            .client_id
+            .as_deref()
+            .ok_or_else(|| PlayerError::backend("No Spotify client id configured"))
+    }
+}
+
+/// Opens the URL in the default browser.
+pub fn open_in_browser(url: &str) -> io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(program).arg(url).spawn().map(|_| ())
+}
+
+/// Waits for the authorization redirect and returns the code it carries.
+fn wait_for_code(
+    listener: &TcpListener,
+    expected_state: &str,
+    timeout: Duration,
+) -> Result<String, PlayerError> {
+    listener
+        .set_nonblocking(true)
+        .map_err(PlayerError::backend)?;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                // Ignore stray requests such as the browser asking for a favicon
+                if let Some(result) = handle_redirect(stream, expected_state) {
+                    return result;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(PlayerError::Timeout);
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => return Err(PlayerError::backend(e)),
+        }
+    }
+}
+
+/// Reads a redirect request and answers it, returning `None` if it was not the callback.
+fn handle_redirect(
+    mut stream: TcpStream,
+    expected_state: &str,
+) -> Option<Result<String, PlayerError>> {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line).ok()?;
+    let target = request_line.split_whitespace().nth(1)?;
+    let url = Url::parse(&format!("http://127.0.0.1{}", target)).ok()?;
+    if url.path() != "/callback" {
+        let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        return None;
+    }
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let result = match (param("code"), param("state"), param("error")) {
+        (_, _, Some(error)) => Err(PlayerError::backend(format!("Login failed: {}", error))),
+        (Some(code), Some(state), None) if state == expected_state => Ok(code),
+        _ => Err(PlayerError::backend("Login failed: invalid redirect")),
+    };
+
+    let message = match &result {
+        Ok(_) => "Logged in to Spotify. You can close this tab and return to Noci.",
+        Err(_) => "Logging in to Spotify failed. Please try again from Noci.",
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        message.len(),
+        message
+    );
+
+    Some(result)
+}
+
+/// Returns a random alphanumeric string of the given length.
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Returns the current Unix time in seconds.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::player::testing::{MockRequest, MockServer};
+
+    /// Returns tokens with the given values, expiring in an hour or expired.
+    fn tokens(access_token: &str, refresh_token: &str, expired: bool) -> Tokens {
+        Tokens {
+            access_token: access_token.to_string(),
+            refresh_token: refresh_token.to_string(),
+            expires_at: if expired { 0 } else { unix_now() + 3600 },
+        }
+    }
+
+    /// Returns an authenticator talking to the mock accounts service, holding `stored`.
+    fn authenticator(accounts: &MockServer, stored: Tokens) -> SpotifyAuth {
+        let store = MemoryTokenStore::default();
+        store.save(&stored).unwrap();
+        let config = SpotifyWebConfig {
+            client_id: Some("client".to_string()),
+            accounts_url: accounts.url.clone(),
+            ..SpotifyWebConfig::default()
+        };
+        SpotifyAuth::new(config, Box::new(store))
+    }
+
+    /// Answers a grant the way the accounts service does.
+    fn grant(access_token: &str, refresh_token: &str) -> (u16, String) {
+        let body = serde_json::json!({
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "expires_in": 3600,
+        });
+        (200, body.to_string())
+    }
+
+    /// Returns whether the request uses the refresh token grant.
+    fn is_refresh(request: &MockRequest) -> bool {
+        request.body.contains("grant_type=refresh_token")
+    }
+
+    /// Sends a request for `target` to the login listener, returning the answer once closed.
+    fn redirect(address: SocketAddr, target: &str) -> thread::JoinHandle<String> {
+        let request = format!("GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n", target);
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut answer = String::new();
+            let _ = stream.read_to_string(&mut answer);
+            answer
+        })
+    }
+
+    #[test]
+    fn pkce_challenge_is_the_hashed_verifier() {
+        // Base64url without padding of the SHA-256 digest of `verifier`
+        let pkce = Pkce::from_verifier("verifier".to_string());
+        assert_eq!(
+            pkce.challenge,
+            "iMnq5o6zALKXGivsnlom_0F5_WYda32GHkxlV7mq7hQ"
+        );
+
+        let generated = Pkce::generate();
+        assert_eq!(generated.verifier.len(), 64);
+        assert_ne!(generated.verifier, Pkce::generate().verifier);
+    }
+
+    #[test]
+    fn authorize_url_carries_the_pkce_request() {
+        let config = SpotifyWebConfig {
+            client_id: Some("client".to_string()),
+            ..SpotifyWebConfig::default()
+        };
+        let auth = SpotifyAuth::new(config, Box::new(MemoryTokenStore::default()));
+        let pkce = Pkce::from_verifier("verifier".to_string());
+
+        let url = Url::parse(&auth.authorize_url(&pkce, "xyz").unwrap()).unwrap();
+        assert_eq!(
+            url.origin().ascii_serialization(),
+            "https://accounts.spotify.com"
+        );
+        assert_eq!(url.path(), "/authorize");
+        let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        let expected = [
+            ("client_id", "client"),
+            ("response_type", "code"),
+            ("redirect_uri", "http://127.0.0.1:8898/callback"),
+            ("code_challenge_method", "S256"),
+            ("code_challenge", pkce.challenge.as_str()),
+            ("scope", SCOPES),
+            ("state", "xyz"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(params, expected);
+    }
+
+    #[test]
+    fn authorize_url_needs_a_client_id() {
+        let auth = SpotifyAuth::new(
+            SpotifyWebConfig::default(),
+            Box::new(MemoryTokenStore::default()),
+        );
+        let pkce = Pkce::generate();
+        assert!(matches!(
+            auth.authorize_url(&pkce, "xyz"),
+            Err(PlayerError::Backend { .. })
+        ));
+    }
+
+    #[test]
+    fn redirect_code_is_returned_after_stray_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let browser = thread::spawn(move || {
+            let favicon = redirect(address, "/favicon.ico").join().unwrap();
+            let callback = redirect(address, "/callback?code=C0DE&state=xyz");
+            (favicon, callback.join().unwrap())
+        });
+
+        assert_eq!(
+            wait_for_code(&listener, "xyz", Duration::from_secs(5)).unwrap(),
+            "C0DE"
+        );
+        let (favicon, callback) = browser.join().unwrap();
+        assert!(favicon.starts_with("HTTP/1.1 404"));
+        assert!(callback.contains("Logged in to Spotify"));
+    }
+
+    #[test]
+    fn redirect_with_another_state_is_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let callback = redirect(
+            listener.local_addr().unwrap(),
+            "/callback?code=C0DE&state=forged",
+        );
+
+        assert_eq!(
+            wait_for_code(&listener, "xyz", Duration::from_secs(5)),
+            Err(PlayerError::backend("Login failed: invalid redirect"))
+        );
+        assert!(callback.join().unwrap().contains("failed"));
+    }
+
+    #[test]
+    fn redirect_with_an_error_is_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let callback = redirect(
+            listener.local_addr().unwrap(),
+            "/callback?error=access_denied&state=xyz",
+        );
+
+        assert_eq!(
+            wait_for_code(&listener, "xyz", Duration::from_secs(5)),
+            Err(PlayerError::backend("Login failed: access_denied"))
+        );
+        assert!(callback.join().unwrap().contains("failed"));
+    }
+
+    #[test]
+    fn missing_redirect_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert_eq!(
+            wait_for_code(&listener, "xyz", Duration::from_millis(100)),
+            Err(PlayerError::Timeout)
+        );
+    }
+
+    #[test]
+    fn code_is_exchanged_for_stored_tokens() {
+        let accounts = MockServer::start(|_| grant("A1", "R1"));
+        let auth = authenticator(&accounts, tokens("old", "old", true));
+
+        auth.exchange_code("C0DE", "verifier").unwrap();
+        assert_eq!(accounts.request_lines(), ["POST /api/token"]);
+        assert_eq!(
+            accounts.requests()[0].body,
+            "grant_type=authorization_code&code=C0DE&redirect_uri=http%3A%2F%2F127.0.0.1%3A8898%2Fcallback&client_id=client&code_verifier=verifier"
+        );
+        assert_eq!(auth.access_token().unwrap(), "A1");
+        assert_eq!(auth.store.load().unwrap().refresh_token, "R1");
+    }
+
+    #[test]
+    fn valid_token_is_returned_without_request() {
+        let accounts = MockServer::start(|_| (500, String::new()));
+        let auth = authenticator(&accounts, tokens("A1", "R1", false));
+
+        assert_eq!(auth.access_token().unwrap(), "A1");
+        assert!(accounts.requests().is_empty());
+    }
+
+    #[test]
+    fn expired_token_is_refreshed_and_rotated() {
+        let accounts = MockServer::start(|_| grant("A2", "R2"));
+        let auth = authenticator(&accounts, tokens("A1", "R1", true));
+
+        assert_eq!(auth.access_token().unwrap(), "A2");
+        assert_eq!(
+            accounts.requests()[0].body,
+            "grant_type=refresh_token&refresh_token=R1&client_id=client"
+        );
+        let stored = auth.store.load().unwrap();
+        assert_eq!(stored.access_token, "A2");
+        assert_eq!(stored.refresh_token, "R2");
+    }
+
+    #[test]
+    fn logout_forgets_the_tokens() {
+        let accounts = MockServer::start(|_| (500, String::new()));
+        let auth = authenticator(&accounts, tokens("A1", "R1", false));
+
+        auth.logout();
+        assert!(!auth.is_logged_in());
+        assert_eq!(auth.store.load(), None);
+        assert_eq!(auth.access_token(), Err(PlayerError::NotAuthenticated));
+    }
+
+    #[test]
+    fn concurrent_refreshes_send_one_request() {
+        let accounts = MockServer::start(|request| {
+            assert!(is_refresh(request));
+            // Keep the other callers waiting on the lock
+            thread::sleep(Duration::from_millis(200));
+            if request.body.contains("refresh_token=R1") {
+                grant("A2", "R2")
+            } else {
+                (400, r#"{"error":"invalid_grant"}"#.to_string())
+            }
+        });
+        let auth = Arc::new(authenticator(&accounts, tokens("A1", "R1", false)));
+
+        // Several requests got a 401 for the same access token
+        let callers: Vec<_> = (0..4)
+            .map(|_| {
+                let auth = auth.clone();
+                thread::spawn(move || auth.refresh("A1"))
+            })
+            .collect();
+        for caller in callers {
+            assert_eq!(caller.join().unwrap().unwrap(), "A2");
+        }
+
+        assert_eq!(accounts.requests().len(), 1);
+        assert!(auth.is_logged_in());
+    }
+
+    #[test]
+    fn invalid_grant_logs_out() {
+        let accounts = MockServer::start(|_| (400, r#"{"error":"invalid_grant"}"#.to_string()));
+        let auth = authenticator(&accounts, tokens("A1", "R1", true));
+
+        assert_eq!(auth.access_token(), Err(PlayerError::NotAuthenticated));
+        assert!(!auth.is_logged_in());
+        assert_eq!(auth.store.load(), None);
+    }
+
+    #[test]
+    fn invalid_grant_for_replaced_token_keeps_new_login() {
+        let accounts = MockServer::start(|request| {
+            if is_refresh(request) {
+                // Give the login below time to complete
+                thread::sleep(Duration::from_millis(300));
+                (400, r#"{"error":"invalid_grant"}"#.to_string())
+            } else {
+                grant("B1", "S1")
+            }
+        });
+        let auth = Arc::new(authenticator(&accounts, tokens("A1", "R1", true)));
+
+        let refresh = {
+            let auth = auth.clone();
+            thread::spawn(move || auth.access_token())
+        };
+        thread::sleep(Duration::from_millis(100));
+        auth.exchange_code("code", "verifier").unwrap();
+
+        assert_eq!(refresh.join().unwrap(), Err(PlayerError::NotAuthenticated));
+        assert_eq!(auth.access_token().unwrap(), "B1");
+        assert_eq!(auth.store.load().unwrap().refresh_token, "S1");
+    }
+
+    #[test]
+    fn other_errors_keep_tokens() {
+        let accounts = MockServer::start(|_| (400, r#"{"error":"invalid_request"}"#.to_string()));
+        let auth = authenticator(&accounts, tokens("A1", "R1", true));
+
+        assert!(matches!(
+            auth.access_token(),
+            Err(PlayerError::Backend { .. })
+        ));
+        assert!(auth.is_logged_in());
+
+        let accounts = MockServer::start(|_| (503, String::new()));
+        let auth = authenticator(&accounts, tokens("A1", "R1", true));
+        assert!(matches!(
+            auth.access_token(),
+            Err(PlayerError::Backend { .. })
+        ));
+        assert!(auth.is_logged_in());
+    }
+}
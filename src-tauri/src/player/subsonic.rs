@@ -0,0 +1,522 @@
+use std::io::Read;
+
+use md5::{Digest, Md5};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+
+use super::artwork::ArtworkCache;
+use super::{MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::config::SubsonicConfig;
+use crate::params::{self, SpotifyStatus, TrackMetadata};
+
+/// Version of the Subsonic API the requests are written against.
+const API_VERSION: &str = "1.16.1";
+/// Client name reported to the server.
+const CLIENT_NAME: &str = "noci";
+
+/// Subsonic error codes for wrong credentials and servers not accepting token authentication.
+const ERROR_WRONG_CREDENTIALS: u32 = 40;
+const ERROR_TOKEN_UNSUPPORTED: u32 = 41;
+
+/// Envelope of every Subsonic JSON response.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    #[serde(rename = "subsonic-response")]
+    response: SubsonicResponse,
+}
+
+/// Body of a Subsonic response.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubsonicResponse {
+    status: String, // `ok` or `failed`
+    error: Option<SubsonicError>,
+    now_playing: Option<NowPlaying>,
+}
+
+/// Error reported by the server.
+#[derive(Debug, Deserialize)]
+struct SubsonicError {
+    code: u32,
+    message: Option<String>,
+}
+
+/// Songs being played on any client of the server.
+#[derive(Debug, Deserialize)]
+struct NowPlaying {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+/// Song being played by a user.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    id: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    display_album_artist: Option<String>, // OpenSubsonic extension
+    duration: Option<f64>,                // Seconds
+    track: Option<u32>,
+    disc_number: Option<u32>,
+    cover_art: Option<String>, // Id to pass to `getCoverArt`
+    username: Option<String>,
+    minutes_ago: Option<u64>, // Minutes since the client reported the song
+}
+
+impl Entry {
+    /// Returns whether the song must have ended, as servers keep listing a song for
+    /// a while after its client stopped. Songs of unknown duration never are.
+    fn is_stale(&self) -> bool {
+        match (self.minutes_ago, self.duration) {
+            (Some(minutes_ago), Some(duration)) => {
+                let minutes = (duration / 60.0).ceil() as u64;
+                minutes_ago > minutes + params::SUBSONIC_STALE_MARGIN_MINUTES
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Backend reading what a user plays from a Subsonic-compatible server (Navidrome,
+/// Airsonic, Gonic...).
+///
+/// The API only tells what is playing, not where within the song, and offers no
+/// remote control of its clients, so the player cannot be controlled.
+pub struct SubsonicPlayer {
+    base_url: String,      // URL of the server, without the `/rest` path
+    username: String,      // User whose songs are followed and who authenticates
+    token: String,         // MD5 of the password and the salt
+    salt: String,          // Salt of the token
+    agent: ureq::Agent,    // HTTP client for the server
+    artwork: ArtworkCache, // Cover art of the last seen song
+}
+
+impl SubsonicPlayer {
+    /// Creates a backend for the configured server, `None` if it is not fully configured.
+    pub fn new(config: &SubsonicConfig) -> Option<Self> {
+        let (url, username, password) = match (&config.url, &config.username, &config.password) {
+            (Some(url), Some(username), Some(password)) => (url, username, password),
+            _ => return None,
+        };
+
+        let salt: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let digest = Md5::digest(format!("{}{}", password, salt).as_bytes());
+        Some(Self {
+            base_url: url.trim_end_matches('/').to_string(),
+            username: username.clone(),
+            token: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+            salt,
+            agent: ureq::AgentBuilder::new()
+                .timeout(params::MEDIA_SERVER_TIMEOUT)
+                .build(),
+            artwork: ArtworkCache::new("subsonic"),
+        })
+    }
+
+    /// Returns the URL of an API method, with the authentication parameters.
+    fn method_url(&self, method: &str, query: &[(&str, &str)]) -> String {
+        let mut url = format!(
+            "{}/rest/{}.view?u={}&t={}&s={}&v={}&c={}&f=json",
+            self.base_url,
+            method,
+            url_encode(&self.username),
+            self.token,
+            self.salt,
+            API_VERSION,
+            CLIENT_NAME
+        );
+        for (name, value) in query {
+            url.push_str(&format!("&{}={}", name, url_encode(value)));
+        }
+        url
+    }
+
+    /// Returns the song the user played last, `PlayerNotRunning` if they play nothing.
+    fn now_playing(&self) -> Result<Entry, PlayerError> {
+        let envelope: Envelope = self
+            .agent
+            .get(&self.method_url("getNowPlaying", &[]))
+            .call()
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| PlayerError::parse(e.to_string()))?;
+
+        let response = envelope.response;
+        if response.status != "ok" {
+            return Err(match response.error {
+                Some(error)
+                    if error.code == ERROR_WRONG_CREDENTIALS
+                        || error.code == ERROR_TOKEN_UNSUPPORTED =>
+                {
+                    PlayerError::NotAuthenticated
+                }
+                Some(error) => PlayerError::backend(
+                    error
+                        .message
+                        .unwrap_or_else(|| format!("Subsonic error {}", error.code)),
+                ),
+                None => PlayerError::backend("Subsonic request failed"),
+            });
+        }
+
+        // The server lists the songs of every user, the most recent first per user
+        response
+            .now_playing
+            .map(|now_playing| now_playing.entry)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| entry.username.as_deref() == Some(self.username.as_str()))
+            .min_by_key(|entry| entry.minutes_ago.unwrap_or(0))
+            .ok_or(PlayerError::PlayerNotRunning)
+    }
+
+    /// Downloads cover art, `None` if there is none.
+    ///
+    /// The cover is fetched here rather than by the webview because its URL
+    /// carries the credentials.
+    fn cover_art(&self, id: &str) -> Result<Option<Vec<u8>>, PlayerError> {
+        let size = params::COVER_ART_SIZE.to_string();
+        let url = self.method_url("getCoverArt", &[("id", id), ("size", &size)]);
+        let response = match self.agent.get(&url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(http_error(e)),
+        };
+        // A missing cover is reported as a Subsonic response instead of an image
+        if !response.content_type().starts_with("image/") {
+            return Ok(None);
+        }
+
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .take(params::COVER_ART_MAX_SIZE)
+            .read_to_end(&mut data)
+            .map_err(PlayerError::backend)?;
+        Ok((!data.is_empty()).then_some(data))
+    }
+}
+
+impl MediaPlayer for SubsonicPlayer {
+    fn name(&self) -> &str {
+        "Subsonic"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: false,
+            can_go_next: false,
+            can_go_previous: false,
+            can_seek: false,
+            can_set_volume: false,
+            can_shuffle: false,
+            can_repeat: false,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        let entry = self.now_playing()?;
+        let album_cover = entry
+            .cover_art
+            .as_deref()
+            .and_then(|id| self.artwork.resolve(id, || self.cover_art(id)));
+        let player_state = if entry.is_stale() {
+            "stopped"
+        } else {
+            "playing"
+        };
+
+        Ok(SpotifyStatus {
+            track_name: entry.title,
+            artist_name: entry.artist,
+            metadata: TrackMetadata {
+                track_id: Some(entry.id),
+                uri: None,
+                album_name: entry.album,
+                album_artist: entry.display_album_artist,
+                disc_number: entry.disc_number,
+                track_number: entry.track,
+                explicit: None,
+                popularity: None,
+            },
+            track_volume: None,
+            position: None,
+            track_duration: entry.duration,
+            album_cover,
+            player_state: Some(player_state.to_string()),
+            shuffle: None,
+            repeat_mode: None,
+            is_saved: None,
+            device: None,
+            error: None,
+        })
+    }
+}
+
+/// Percent-encodes a query value.
+fn url_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Maps a failed request to a `PlayerError`, treating a refused connection as a stopped server.
+fn http_error(error: ureq::Error) -> PlayerError {
+    match error {
+        ureq::Error::Status(401 | 403, _) => PlayerError::NotAuthenticated,
+        ureq::Error::Status(code, _) => {
+            PlayerError::backend(format!("Subsonic server answered with status {}", code))
+        }
+        ureq::Error::Transport(transport) => match transport.kind() {
+            ureq::ErrorKind::ConnectionFailed => PlayerError::PlayerNotRunning,
+            _ => PlayerError::backend(transport),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::player::testing::{MockRequest, MockResponse, MockServer, TempDir};
+
+    /// `getNowPlaying` answer listing songs of two users, as sent by Navidrome.
+    const NOW_PLAYING: &str = r#"{"subsonic-response": {
+        "status": "ok", "version": "1.16.1", "type": "navidrome", "openSubsonic": true,
+        "nowPlaying": {"entry": [
+            {"id": "s1", "title": "Old", "artist": "A", "duration": 200, "coverArt": "al-1",
+             "username": "alice", "minutesAgo": 3},
+            {"id": "s2", "title": "Song", "artist": "Artist", "album": "Album",
+             "displayAlbumArtist": "Album Artist", "duration": 245, "track": 3, "discNumber": 1,
+             "coverArt": "al-2", "username": "alice", "minutesAgo": 0, "playerId": 1},
+            {"id": "s3", "title": "Other", "username": "bob", "minutesAgo": 0}
+        ]}
+    }}"#;
+
+    /// Returns a backend for user `alice` on the mock server.
+    fn player(server: &MockServer) -> SubsonicPlayer {
+        SubsonicPlayer::new(&SubsonicConfig {
+            url: Some(format!("{}/", server.url)),
+            username: Some("alice".to_string()),
+            password: Some("sesame".to_string()),
+        })
+        .unwrap()
+    }
+
+    /// Returns `player` storing the covers it fetches in `dir`.
+    fn storing_covers_in(player: SubsonicPlayer, dir: &TempDir) -> SubsonicPlayer {
+        SubsonicPlayer {
+            artwork: ArtworkCache::with_dir("subsonic", dir.path.clone()),
+            ..player
+        }
+    }
+
+    /// Returns a query parameter of a request.
+    fn param(request: &MockRequest, name: &str) -> Option<String> {
+        let url = url::Url::parse(&format!("http://localhost{}", request.path)).unwrap();
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Answers `getNowPlaying` with `now_playing`, and covers with image data named
+    /// after their id.
+    fn navidrome(now_playing: &'static str) -> MockServer {
+        MockServer::start_raw(move |request| {
+            let json = |body: &str| MockResponse {
+                status: 200,
+                content_type: "application/json",
+                body: body.as_bytes().to_vec(),
+            };
+            if request.path.starts_with("/rest/getNowPlaying.view") {
+                json(now_playing)
+            } else if request.path.starts_with("/rest/getCoverArt.view") {
+                match param(request, "id").as_deref() {
+                    Some("missing") => json(
+                        r#"{"subsonic-response": {"status": "failed", "error": {"code": 70, "message": "Artwork not found"}}}"#,
+                    ),
+                    Some(id) => MockResponse {
+                        status: 200,
+                        content_type: "image/jpeg",
+                        body: format!("jpeg {}", id).into_bytes(),
+                    },
+                    None => json("{}"),
+                }
+            } else {
+                json("{}")
+            }
+        })
+    }
+
+    #[test]
+    fn requests_use_token_authentication() {
+        let server = navidrome(NOW_PLAYING);
+        let dir = TempDir::new("subsonic-token");
+        storing_covers_in(player(&server), &dir).status().unwrap();
+
+        let request = &server.requests()[0];
+        assert!(request.path.starts_with("/rest/getNowPlaying.view?"));
+        let salt = param(request, "s").unwrap();
+        let token = Md5::digest(format!("sesame{}", salt).as_bytes());
+        let token: String = token.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(param(request, "t"), Some(token));
+        assert_eq!(param(request, "u").as_deref(), Some("alice"));
+        assert_eq!(param(request, "f").as_deref(), Some("json"));
+        assert_eq!(param(request, "p"), None);
+    }
+
+    #[test]
+    fn latest_song_of_the_user_is_reported() {
+        let server = navidrome(NOW_PLAYING);
+        let dir = TempDir::new("subsonic-latest");
+        let status = storing_covers_in(player(&server), &dir).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("Artist"));
+        assert_eq!(status.metadata.track_id.as_deref(), Some("s2"));
+        assert_eq!(status.metadata.album_name.as_deref(), Some("Album"));
+        assert_eq!(
+            status.metadata.album_artist.as_deref(),
+            Some("Album Artist")
+        );
+        assert_eq!(status.metadata.track_number, Some(3));
+        assert_eq!(status.metadata.disc_number, Some(1));
+        assert_eq!(status.track_duration, Some(245.0));
+        assert_eq!(status.position, None);
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+    }
+
+    #[test]
+    fn cover_is_fetched_by_the_backend() {
+        let server = navidrome(NOW_PLAYING);
+        let dir = TempDir::new("subsonic-cover");
+        let player = storing_covers_in(player(&server), &dir);
+
+        let cover = player.status().unwrap().album_cover.unwrap();
+        // The webview gets a local file, not a URL carrying the credentials
+        assert!(cover.starts_with("asset://localhost/"));
+        assert!(!cover.contains("getCoverArt"));
+        let name = cover.rsplit("%2F").next().unwrap();
+        assert_eq!(fs::read(dir.path.join(name)).unwrap(), b"jpeg al-2");
+
+        let request = &server.requests()[1];
+        assert!(request.path.starts_with("/rest/getCoverArt.view?"));
+        assert_eq!(param(request, "size").as_deref(), Some("300"));
+
+        // Fetched once per cover
+        assert_eq!(player.status().unwrap().album_cover, Some(cover));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn missing_cover_is_none() {
+        let server = navidrome(
+            r#"{"subsonic-response": {"status": "ok", "nowPlaying": {"entry": [
+                {"id": "s1", "title": "Song", "coverArt": "missing", "username": "alice", "minutesAgo": 0}
+            ]}}}"#,
+        );
+        let dir = TempDir::new("subsonic-missing-cover");
+        let player = storing_covers_in(player(&server), &dir);
+
+        assert_eq!(player.status().unwrap().album_cover, None);
+    }
+
+    #[test]
+    fn stale_song_is_stopped() {
+        // Listed 9 minutes after a 4 minute song started
+        let server = navidrome(
+            r#"{"subsonic-response": {"status": "ok", "nowPlaying": {"entry": [
+                {"id": "s1", "title": "Song", "duration": 240, "username": "alice", "minutesAgo": 9}
+            ]}}}"#,
+        );
+        let status = player(&server).status().unwrap();
+
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.player_state.as_deref(), Some("stopped"));
+    }
+
+    #[test]
+    fn song_within_its_duration_is_playing() {
+        let server = navidrome(
+            r#"{"subsonic-response": {"status": "ok", "nowPlaying": {"entry": [
+                {"id": "s1", "title": "Song", "duration": 240, "username": "alice", "minutesAgo": 5},
+                {"id": "s2", "title": "Stream", "username": "bob", "minutesAgo": 600}
+            ]}}}"#,
+        );
+
+        assert_eq!(
+            player(&server).status().unwrap().player_state.as_deref(),
+            Some("playing")
+        );
+    }
+
+    #[test]
+    fn nothing_played_by_the_user_is_not_running() {
+        let server = navidrome(
+            r#"{"subsonic-response": {"status": "ok", "nowPlaying": {"entry": [
+                {"id": "s3", "title": "Other", "username": "bob", "minutesAgo": 0}
+            ]}}}"#,
+        );
+        assert_eq!(
+            player(&server).status().unwrap_err(),
+            PlayerError::PlayerNotRunning
+        );
+
+        let server = navidrome(r#"{"subsonic-response": {"status": "ok", "nowPlaying": {}}}"#);
+        assert_eq!(
+            player(&server).status().unwrap_err(),
+            PlayerError::PlayerNotRunning
+        );
+    }
+
+    #[test]
+    fn wrong_credentials_are_not_authenticated() {
+        let server = navidrome(
+            r#"{"subsonic-response": {"status": "failed", "error": {"code": 40, "message": "Wrong username or password"}}}"#,
+        );
+        assert_eq!(
+            player(&server).status().unwrap_err(),
+            PlayerError::NotAuthenticated
+        );
+
+        let server = navidrome(
+            r#"{"subsonic-response": {"status": "failed", "error": {"code": 0, "message": "Generic error"}}}"#,
+        );
+        assert_eq!(
+            player(&server).status().unwrap_err(),
+            PlayerError::backend("Generic error")
+        );
+    }
+
+    #[test]
+    fn incomplete_config_is_none() {
+        assert!(SubsonicPlayer::new(&SubsonicConfig {
+            url: Some("http://127.0.0.1:4533".to_string()),
+            username: Some("alice".to_string()),
+            password: None,
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn player_cannot_be_controlled() {
+        let server = navidrome(NOW_PLAYING);
+        let player = player(&server);
+
+        assert!(matches!(
+            player.toggle_playback(),
+            Err(PlayerError::Unsupported { .. })
+        ));
+        assert!(server.requests().is_empty());
+    }
+}
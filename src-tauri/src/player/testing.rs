@@ -0,0 +1,175 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+/// Request received by a `MockServer`.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,                 // e.g. `GET`
+    pub path: String,                   // Path including the query
+    pub headers: Vec<(String, String)>, // Headers, with lowercase names
+    pub body: String,                   // Body, decoded lossily
+}
+
+impl MockRequest {
+    /// Returns the value of a header, looked up by lowercase name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Response sent by a `MockServer`.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,                // Status code
+    pub content_type: &'static str, // Value of the `Content-Type` header
+    pub body: Vec<u8>,              // Body
+}
+
+/// Handler answering a request.
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+/// HTTP/1.1 server on a loopback port, standing in for the services the backends talk to.
+pub struct MockServer {
+    pub url: String,                        // Base URL, e.g. `http://127.0.0.1:1234`
+    pub port: u16,                          // Port the server listens on
+    requests: Arc<Mutex<Vec<MockRequest>>>, // Requests received so far
+}
+
+impl MockServer {
+    /// Starts a server answering every request with `handler`, with a JSON body.
+    pub fn start(handler: impl Fn(&MockRequest) -> (u16, String) + Send + Sync + 'static) -> Self {
+        Self::start_raw(move |request| {
+            let (status, body) = handler(request);
+            MockResponse {
+                status,
+                content_type: "application/json",
+                body: body.into_bytes(),
+            }
+        })
+    }
+
+    /// Starts a server answering every request with `handler`, with any body.
+    pub fn start_raw(
+        handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let received = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                let received = received.clone();
+                thread::spawn(move || serve_connection(stream, &*handler, &received));
+            }
+        });
+
+        Self {
+            url: format!("http://127.0.0.1:{}", port),
+            port,
+            requests,
+        }
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        lock(&self.requests).clone()
+    }
+
+    /// Returns the `METHOD path` lines of the requests received so far.
+    pub fn request_lines(&self) -> Vec<String> {
+        lock(&self.requests)
+            .iter()
+            .map(|request| format!("{} {}", request.method, request.path))
+            .collect()
+    }
+}
+
+/// Answers the requests of a keep-alive connection until the client closes it.
+fn serve_connection(stream: TcpStream, handler: &Handler, received: &Mutex<Vec<MockRequest>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            match line.trim_end().split_once(':') {
+                Some((name, value)) => {
+                    headers.push((name.trim().to_lowercase(), value.trim().to_string()))
+                }
+                None => break,
+            }
+        }
+        let length = headers
+            .iter()
+            .find(|(name, _)| name == "content-length")
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+
+        let request = MockRequest {
+            method,
+            path,
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        };
+        lock(received).push(request.clone());
+        let response = handler(&request);
+        let head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        );
+        if writer.write_all(head.as_bytes()).is_err() || writer.write_all(&response.body).is_err() {
+            return;
+        }
+    }
+}
+
+/// Locks a mutex, recovering from a poisoned lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Directory of its own in the temp dir for a test, removed with its content when dropped.
+pub struct TempDir {
+    pub path: PathBuf, // Path of the directory, created by the code under test
+}
+
+impl TempDir {
+    /// Returns an empty directory named after the test.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("noci-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        Self { path }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
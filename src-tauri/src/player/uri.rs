@@ -0,0 +1,246 @@
+use std::fmt;
+use std::str::FromStr;
+
+use url::Url;
+
+use super::PlayerError;
+
+/// Length of the base-62 identifiers in Spotify URIs.
+const SPOTIFY_ID_LENGTH: usize = 22;
+
+/// Kind of item a Spotify URI points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpotifyUriKind {
+    Track,    // A single track
+    Episode,  // A single podcast episode
+    Album,    // An album, played as a context
+    Playlist, // A playlist, played as a context
+    Artist,   // An artist, whose top tracks are played as a context
+    Show,     // A podcast, played as a context
+}
+
+impl SpotifyUriKind {
+    /// Returns the kind as written in URIs.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Episode => "episode",
+            Self::Album => "album",
+            Self::Playlist => "playlist",
+            Self::Artist => "artist",
+            Self::Show => "show",
+        }
+    }
+
+    /// Returns whether the item is a context (a collection of tracks) rather than a single item.
+    pub fn is_context(self) -> bool {
+        !matches!(self, Self::Track | Self::Episode)
+    }
+}
+
+impl FromStr for SpotifyUriKind {
+    type Err = PlayerError;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "track" => Ok(Self::Track),
+            "episode" => Ok(Self::Episode),
+            "album" => Ok(Self::Album),
+            "playlist" => Ok(Self::Playlist),
+            "artist" => Ok(Self::Artist),
+            "show" => Ok(Self::Show),
+            _ => Err(PlayerError::invalid_argument(format!(
+                "unsupported Spotify item kind {:?}",
+                kind
+            ))),
+        }
+    }
+}
+
+/// A validated Spotify URI (`spotify:<kind>:<id>`).
+///
+/// Only URIs made of a known kind and a 22 character base-62 id are accepted, so a
+/// parsed URI is always safe to interpolate into a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpotifyUri {
+    kind: SpotifyUriKind, // Kind of the item
+    id: String,           // Base-62 id of the item
+}
+
+impl SpotifyUri {
+    /// Parses a `spotify:` URI or an `https://open.spotify.com/` link.
+    pub fn parse(input: &str) -> Result<Self, PlayerError> {
+        let input = input.trim();
+        match input.strip_prefix("spotify:") {
+            Some(rest) => {
+                let (kind, id) = rest.split_once(':').ok_or_else(|| invalid_uri(input))?;
+                Self::new(kind.parse()?, id).ok_or_else(|| invalid_uri(input))
+            }
+            None => Self::parse_link(input),
+        }
+    }
+
+    /// Parses an `https://open.spotify.com/<kind>/<id>` link, ignoring its query.
+    fn parse_link(input: &str) -> Result<Self, PlayerError> {
+        let url = Url::parse(input).map_err(|_| invalid_uri(input))?;
+        if url.scheme() != "https" || url.host_str() != Some("open.spotify.com") {
+            return Err(invalid_uri(input));
+        }
+
+        // Localized links carry a leading segment such as `intl-fr`
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.starts_with("intl-")).collect())
+            .unwrap_or_default();
+        match segments.as_slice() {
+            [kind, id] => Self::new(kind.parse()?, id).ok_or_else(|| invalid_uri(input)),
+            _ => Err(invalid_uri(input)),
+        }
+    }
+
+    /// Creates a URI if the id is a valid base-62 Spotify id.
+    fn new(kind: SpotifyUriKind, id: &str) -> Option<Self> {
+        let valid = id.len() == SPOTIFY_ID_LENGTH && id.chars().all(|c| c.is_ascii_alphanumeric());
+        valid.then(|| Self {
+            kind,
+            id: id.to_string(),
+        })
+    }
+
+    /// Returns the kind of item the URI points to.
+    pub fn kind(&self) -> SpotifyUriKind {
+        self.kind
+    }
+
+    /// Returns the base-62 id of the item.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Display for SpotifyUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spotify:{}:{}", self.kind.as_str(), self.id)
+    }
+}
+
+/// Creates the error reported for input that is not a Spotify URI.
+fn invalid_uri(input: &str) -> PlayerError {
+    PlayerError::invalid_argument(format!("not a Spotify URI: {:?}", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "4uLU6hMCjMI75M1A2tKUQC";
+
+    const KINDS: [(&str, SpotifyUriKind); 6] = [
+        ("track", SpotifyUriKind::Track),
+        ("episode", SpotifyUriKind::Episode),
+        ("album", SpotifyUriKind::Album),
+        ("playlist", SpotifyUriKind::Playlist),
+        ("artist", SpotifyUriKind::Artist),
+        ("show", SpotifyUriKind::Show),
+    ];
+
+    #[test]
+    fn every_kind_is_accepted_as_uri() {
+        for (name, kind) in KINDS {
+            let uri = SpotifyUri::parse(&format!("spotify:{}:{}", name, ID)).unwrap();
+
+            assert_eq!(uri.kind(), kind);
+            assert_eq!(uri.id(), ID);
+        }
+    }
+
+    #[test]
+    fn every_kind_is_accepted_as_link() {
+        for (name, kind) in KINDS {
+            let uri =
+                SpotifyUri::parse(&format!("https://open.spotify.com/{}/{}", name, ID)).unwrap();
+
+            assert_eq!(uri.kind(), kind);
+            assert_eq!(uri.id(), ID);
+        }
+    }
+
+    #[test]
+    fn link_locale_and_query_are_ignored() {
+        let links = [
+            format!("https://open.spotify.com/intl-fr/track/{}", ID),
+            format!("https://open.spotify.com/track/{}?si=0123456789abcdef", ID),
+            format!(
+                "https://open.spotify.com/intl-pt/track/{}?si=x&context=y",
+                ID
+            ),
+            format!("  https://open.spotify.com/track/{}\n", ID),
+        ];
+
+        for link in links {
+            let uri = SpotifyUri::parse(&link).unwrap();
+            assert_eq!(uri.to_string(), format!("spotify:track:{}", ID), "{}", link);
+        }
+    }
+
+    #[test]
+    fn uri_round_trips_through_its_string() {
+        for (name, _) in KINDS {
+            let input = format!("spotify:{}:{}", name, ID);
+            let uri = SpotifyUri::parse(&input).unwrap();
+
+            assert_eq!(uri.to_string(), input);
+            assert_eq!(SpotifyUri::parse(&uri.to_string()).unwrap(), uri);
+        }
+    }
+
+    #[test]
+    fn contexts_are_the_collections() {
+        let contexts: Vec<_> = KINDS
+            .iter()
+            .filter(|(_, kind)| kind.is_context())
+            .map(|(name, _)| *name)
+            .collect();
+
+        assert_eq!(contexts, ["album", "playlist", "artist", "show"]);
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        let inputs = [
+            // Unknown kinds
+            format!("spotify:user:{}", ID),
+            format!("spotify:local:{}", ID),
+            format!("https://open.spotify.com/user/{}", ID),
+            // Ids of the wrong length
+            format!("spotify:track:{}", &ID[1..]),
+            format!("spotify:track:{}a", ID),
+            "spotify:track:".to_string(),
+            // Ids with other characters
+            "spotify:track:4uLU6hMCjMI75M1A2tKUQ-".to_string(),
+            "spotify:track:4uLU6hMCjMI75M1A2tKUQé".to_string(),
+            format!("spotify:track:{}:extra", ID),
+            // Script injection
+            r#"spotify:track:x" & do shell script "touch /tmp/pwned"#.to_string(),
+            format!(r#"spotify:track:{}" & do shell script "id"#, ID),
+            // Other hosts and schemes
+            format!("http://open.spotify.com/track/{}", ID),
+            format!("https://evil.example/track/{}", ID),
+            format!("https://open.spotify.com.evil.example/track/{}", ID),
+            format!("https://open.spotify.com/track/{}/extra", ID),
+            format!("track:{}", ID),
+            String::new(),
+        ];
+
+        for input in inputs {
+            assert!(
+                matches!(
+                    SpotifyUri::parse(&input),
+                    Err(PlayerError::InvalidArgument { .. })
+                ),
+                "{:?}",
+                input
+            );
+        }
+    }
+}
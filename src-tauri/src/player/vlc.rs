@@ -0,0 +1,441 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+
+use super::record::leading_number;
+use super::{MediaPlayer, PlayerCapabilities, PlayerError};
+use crate::config::VlcConfig;
+use crate::params::{self, RepeatMode, SpotifyStatus, TrackMetadata};
+
+/// Path of the status endpoint, which also runs the commands passed in its query.
+const STATUS_PATH: &str = "/requests/status.json";
+
+/// Raw volume VLC uses for 100% (its scale goes up to 512, i.e. 200%).
+const VLC_FULL_VOLUME: f64 = 256.0;
+
+/// Status returned by `/requests/status.json`.
+#[derive(Debug, Deserialize)]
+struct VlcStatus {
+    state: String, // `playing`, `paused` or `stopped`
+    volume: Option<f64>,
+    length: Option<f64>, // Seconds, 0 or -1 when unknown
+    time: Option<f64>,   // Seconds
+    random: Option<bool>,
+    #[serde(rename = "loop")]
+    loop_all: Option<bool>, // Repeat the playlist
+    repeat: Option<bool>, // Repeat the current item
+    currentplid: Option<i64>,
+    information: Option<Information>,
+}
+
+/// Information about the current item.
+#[derive(Debug, Deserialize)]
+struct Information {
+    category: Category,
+}
+
+/// Categories of the item information, of which only the metadata is used.
+#[derive(Debug, Deserialize)]
+struct Category {
+    meta: Option<Meta>,
+}
+
+/// Metadata of the current item, as read from its tags.
+#[derive(Debug, Deserialize)]
+struct Meta {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    album_artist: Option<String>,
+    filename: Option<String>,
+    track_number: Option<String>, // `3` or `3/12`
+    disc_number: Option<String>,
+    now_playing: Option<String>, // Title announced by a stream
+}
+
+/// Backend controlling VLC through its HTTP interface (`--extraintf http`).
+pub struct VlcPlayer {
+    base_url: String,      // URL of the HTTP interface
+    authorization: String, // Basic auth header value, VLC only checks the password
+    agent: ureq::Agent,    // HTTP client for the interface
+}
+
+impl VlcPlayer {
+    /// Creates a backend talking to the HTTP interface configured in `config`.
+    pub fn new(config: &VlcConfig) -> Self {
+        let credentials = format!(":{}", config.password.as_deref().unwrap_or_default());
+        Self {
+            base_url: format!("http://{}:{}", config.host, config.port),
+            authorization: format!("Basic {}", STANDARD.encode(credentials)),
+            agent: ureq::AgentBuilder::new()
+                .timeout(params::VLC_TIMEOUT)
+                .build(),
+        }
+    }
+
+    /// Requests the status, running the given command first if any.
+    fn request(&self, command: &[(&str, &str)]) -> Result<VlcStatus, PlayerError> {
+        let mut request = self
+            .agent
+            .get(&format!("{}{}", self.base_url, STATUS_PATH))
+            .set("Authorization", &self.authorization);
+        for (name, value) in command {
+            request = request.query(name, value);
+        }
+
+        request
+            .call()
+            .map_err(vlc_error)?
+            .into_json()
+            .map_err(|e| PlayerError::parse(e.to_string()))
+    }
+
+    /// Runs a command, ignoring the status VLC answers with.
+    fn command(&self, command: &str) -> Result<(), PlayerError> {
+        self.request(&[("command", command)]).map(|_| ())
+    }
+
+    /// Runs a command taking a value.
+    fn command_with(&self, command: &str, value: &str) -> Result<(), PlayerError> {
+        self.request(&[("command", command), ("val", value)])
+            .map(|_| ())
+    }
+}
+
+impl MediaPlayer for VlcPlayer {
+    fn name(&self) -> &str {
+        "VLC"
+    }
+
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            can_play_pause: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_seek: true,
+            can_set_volume: true,
+            can_shuffle: true,
+            can_repeat: true,
+            can_play_uri: false,
+            can_save: false,
+            can_queue: false,
+            can_transfer: false,
+        }
+    }
+
+    fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+        self.request(&[]).map(map_status)
+    }
+
+    fn toggle_playback(&self) -> Result<(), PlayerError> {
+        self.command("pl_pause")
+    }
+
+    fn next_track(&self) -> Result<(), PlayerError> {
+        self.command("pl_next")
+    }
+
+    fn previous_track(&self) -> Result<(), PlayerError> {
+        self.command("pl_previous")
+    }
+
+    fn set_position(&self, position: f64) -> Result<(), PlayerError> {
+        // VLC seeks to whole seconds, so round rather than truncate
+        let seconds = (position.max(0.0).round() as u64).to_string();
+        self.command_with("seek", &seconds)
+    }
+
+    fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+        let raw = (f64::from(level.min(100)) * VLC_FULL_VOLUME / 100.0).round();
+        self.command_with("volume", &raw.to_string())
+    }
+
+    fn set_shuffle(&self, enabled: bool) -> Result<(), PlayerError> {
+        // VLC only toggles shuffle, so it is left alone when already as requested
+        if self.request(&[])?.random != Some(enabled) {
+            self.command("pl_random")?;
+        }
+        Ok(())
+    }
+
+    fn set_repeat(&self, mode: RepeatMode) -> Result<(), PlayerError> {
+        let status = self.request(&[])?;
+        let (loop_all, repeat) = match mode {
+            RepeatMode::Off => (false, false),
+            RepeatMode::Track => (false, true),
+            RepeatMode::Context => (true, false),
+        };
+        if status.loop_all.unwrap_or(false) != loop_all {
+            self.command("pl_loop")?;
+        }
+        if status.repeat.unwrap_or(false) != repeat {
+            self.command("pl_repeat")?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps the VLC status onto the status model.
+fn map_status(status: VlcStatus) -> SpotifyStatus {
+    let meta = status
+        .information
+        .and_then(|information| information.category.meta);
+    let (track_name, artist_name, metadata) = match meta {
+        Some(meta) => {
+            // Untagged files are named after the file, streams after what they announce
+            let track_name = meta.title.or(meta.now_playing).or(meta.filename);
+            let metadata = TrackMetadata {
+                track_id: status.currentplid.map(|id| id.to_string()),
+                uri: None,
+                album_name: meta.album,
+                album_artist: meta.album_artist,
+                disc_number: meta.disc_number.as_deref().and_then(leading_number),
+                track_number: meta.track_number.as_deref().and_then(leading_number),
+                explicit: None,
+                popularity: None,
+            };
+            (track_name, meta.artist, metadata)
+        }
+        None => (None, None, TrackMetadata::default()),
+    };
+
+    SpotifyStatus {
+        track_name,
+        artist_name,
+        metadata,
+        track_volume: status
+            .volume
+            .map(|raw| (raw * 100.0 / VLC_FULL_VOLUME).round().clamp(0.0, 100.0) as u32),
+        position: status.time,
+        track_duration: status.length.filter(|length| *length > 0.0),
+        album_cover: None,
+        player_state: Some(status.state),
+        shuffle: status.random,
+        repeat_mode: match (status.repeat, status.loop_all) {
+            (Some(true), _) => Some(RepeatMode::Track),
+            (_, Some(true)) => Some(RepeatMode::Context),
+            (Some(false), _) | (_, Some(false)) => Some(RepeatMode::Off),
+            (None, None) => None,
+        },
+        is_saved: None,
+        device: None,
+        error: None,
+    }
+}
+
+/// Maps a failed request to a `PlayerError`, treating a refused connection as a closed VLC.
+fn vlc_error(error: ureq::Error) -> PlayerError {
+    match error {
+        ureq::Error::Status(401 | 403, _) => PlayerError::NotAuthenticated,
+        ureq::Error::Status(code, _) => {
+            PlayerError::backend(format!("VLC answered with status {}", code))
+        }
+        ureq::Error::Transport(transport) => match transport.kind() {
+            ureq::ErrorKind::ConnectionFailed => PlayerError::PlayerNotRunning,
+            _ => PlayerError::backend(transport),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::player::testing::MockServer;
+
+    /// Status of a playing file, as answered by VLC 3.0.
+    const PLAYING: &str = r#"{
+        "fullscreen": false, "apiversion": 3, "currentplid": 4, "time": 42, "volume": 128,
+        "length": 215, "random": false, "rate": 1, "state": "playing", "loop": true,
+        "version": "3.0.18 Vetinari", "position": 0.195, "repeat": false,
+        "information": {"chapter": 0, "chapters": [], "title": 0, "titles": [], "category": {
+            "meta": {"artwork_url": "file:///tmp/a.jpg", "album": "Album", "album_artist": "X",
+                     "track_number": "3/12", "disc_number": "1", "filename": "song.mp3",
+                     "artist": "X", "title": "Song"},
+            "Stream 0": {"Type": "Audio", "Codec": "MPEG Audio layer 1/2 (mpga)"}
+        }}
+    }"#;
+
+    /// Status of a stopped VLC with an empty playlist.
+    const STOPPED: &str = r#"{"state": "stopped", "volume": 256, "time": 0, "length": 0,
+        "random": true, "loop": false, "repeat": false, "currentplid": -1}"#;
+
+    /// Status of a web radio announcing the current song.
+    const STREAM: &str = r#"{
+        "state": "playing", "volume": 300, "time": 61, "length": -1, "random": false,
+        "loop": false, "repeat": true, "currentplid": 7,
+        "information": {"category": {"meta": {"filename": "stream.mp3",
+            "now_playing": "Artist - Song"}}}
+    }"#;
+
+    /// Returns a backend talking to the mock interface, with password `secret`.
+    fn player(server: &MockServer) -> VlcPlayer {
+        VlcPlayer::new(&VlcConfig {
+            host: "127.0.0.1".to_string(),
+            port: server.port,
+            password: Some("secret".to_string()),
+        })
+    }
+
+    /// Returns a mock interface answering every request with the status.
+    fn vlc(status: &'static str) -> MockServer {
+        MockServer::start(move |request| match request.header("authorization") {
+            // `:secret`, VLC ignores the user name
+            Some("Basic OnNlY3JldA==") => (200, status.to_string()),
+            _ => (401, String::new()),
+        })
+    }
+
+    #[test]
+    fn playing_status_is_parsed() {
+        let server = vlc(PLAYING);
+        let status = player(&server).status().unwrap();
+
+        assert_eq!(server.request_lines(), ["GET /requests/status.json"]);
+        assert_eq!(status.track_name.as_deref(), Some("Song"));
+        assert_eq!(status.artist_name.as_deref(), Some("X"));
+        assert_eq!(status.metadata.album_name.as_deref(), Some("Album"));
+        assert_eq!(status.metadata.album_artist.as_deref(), Some("X"));
+        assert_eq!(status.metadata.track_number, Some(3));
+        assert_eq!(status.metadata.disc_number, Some(1));
+        assert_eq!(status.track_volume, Some(50));
+        assert_eq!(status.position, Some(42.0));
+        assert_eq!(status.track_duration, Some(215.0));
+        assert_eq!(status.player_state.as_deref(), Some("playing"));
+        assert_eq!(status.shuffle, Some(false));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Context));
+    }
+
+    #[test]
+    fn stopped_status_has_no_track() {
+        let server = vlc(STOPPED);
+        let status = player(&server).status().unwrap();
+
+        assert_eq!(status.track_name, None);
+        assert_eq!(status.track_duration, None);
+        assert_eq!(status.track_volume, Some(100));
+        assert_eq!(status.player_state.as_deref(), Some("stopped"));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Off));
+    }
+
+    #[test]
+    fn stream_status_is_parsed() {
+        let server = vlc(STREAM);
+        let status = player(&server).status().unwrap();
+
+        // Streams are named after what they announce, not after the file
+        assert_eq!(status.track_name.as_deref(), Some("Artist - Song"));
+        assert_eq!(status.track_duration, None);
+        // Above 100% is reported as 100%
+        assert_eq!(status.track_volume, Some(100));
+        assert_eq!(status.repeat_mode, Some(RepeatMode::Track));
+    }
+
+    #[test]
+    fn commands_are_sent_through_the_status_endpoint() {
+        let server = vlc(PLAYING);
+        let player = player(&server);
+
+        player.toggle_playback().unwrap();
+        player.next_track().unwrap();
+        player.previous_track().unwrap();
+        player.set_volume(100).unwrap();
+        player.set_volume(33).unwrap();
+
+        assert_eq!(
+            server.request_lines(),
+            [
+                "GET /requests/status.json?command=pl_pause",
+                "GET /requests/status.json?command=pl_next",
+                "GET /requests/status.json?command=pl_previous",
+                "GET /requests/status.json?command=volume&val=256",
+                "GET /requests/status.json?command=volume&val=84",
+            ]
+        );
+    }
+
+    #[test]
+    fn seek_rounds_to_the_nearest_second() {
+        let server = vlc(PLAYING);
+        let player = player(&server);
+
+        player.set_position(61.7).unwrap();
+        player.set_position(61.4).unwrap();
+        player.set_position(-3.0).unwrap();
+
+        assert_eq!(
+            server.request_lines(),
+            [
+                "GET /requests/status.json?command=seek&val=62",
+                "GET /requests/status.json?command=seek&val=61",
+                "GET /requests/status.json?command=seek&val=0",
+            ]
+        );
+    }
+
+    #[test]
+    fn toggles_are_only_sent_when_needed() {
+        let server = vlc(PLAYING);
+        let player = player(&server);
+
+        // Shuffle is off and the playlist loops
+        player.set_shuffle(false).unwrap();
+        player.set_repeat(RepeatMode::Context).unwrap();
+        assert_eq!(
+            server.request_lines(),
+            ["GET /requests/status.json", "GET /requests/status.json"]
+        );
+
+        player.set_shuffle(true).unwrap();
+        player.set_repeat(RepeatMode::Track).unwrap();
+        assert_eq!(
+            server.request_lines()[2..],
+            [
+                "GET /requests/status.json",
+                "GET /requests/status.json?command=pl_random",
+                "GET /requests/status.json",
+                "GET /requests/status.json?command=pl_loop",
+                "GET /requests/status.json?command=pl_repeat",
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_password_is_not_authenticated() {
+        let server = vlc(PLAYING);
+        let player = VlcPlayer::new(&VlcConfig {
+            host: "127.0.0.1".to_string(),
+            port: server.port,
+            password: None,
+        });
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::NotAuthenticated);
+    }
+
+    #[test]
+    fn closed_port_is_not_running() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let player = VlcPlayer::new(&VlcConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            password: None,
+        });
+
+        assert_eq!(player.status().unwrap_err(), PlayerError::PlayerNotRunning);
+    }
+
+    #[test]
+    fn invalid_answer_is_a_parse_error() {
+        let server = MockServer::start(|_| (200, "<html>".to_string()));
+
+        assert!(matches!(
+            player(&server).status(),
+            Err(PlayerError::ParseError { .. })
+        ));
+    }
+}
@@ -0,0 +1,361 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use super::{MediaPlayer, PlayerError};
+use crate::params;
+
+/// Volume levels known to Noci, shared by the volume commands and the status thread.
+#[derive(Debug, Default)]
+struct VolumeState {
+    known: Option<u32>,             // Last level polled from or set on the player
+    target: Option<(u32, Instant)>, // Level last requested by Noci, and when
+    applied: Option<u32>,           // Level last sent to the player
+    muted_from: Option<u32>,        // Level to restore when unmuting
+}
+
+/// Changes the volume of the active player and tells external changes apart from
+/// the ones made by Noci.
+///
+/// Adjustments made in quick succession (e.g. while scrolling) build on the level
+/// requested last rather than on the polled one, which lags behind, and only the
+/// latest level is sent when several are requested while a change is in flight.
+#[derive(Debug, Default)]
+pub struct VolumeControl {
+    state: Mutex<VolumeState>, // Known and requested levels
+    apply: Mutex<()>,          // Held while a level is sent to the player
+}
+
+impl VolumeControl {
+    /// Creates a control with no known level.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the volume to the given level, clamped to 0–100, and returns it.
+    pub fn set(&self, backend: &dyn MediaPlayer, level: i32) -> Result<u32, PlayerError> {
+        let level = clamp_level(level as i64);
+        self.request(backend, level)
+    }
+
+    /// Changes the volume by the given delta, clamped to 0–100, and returns the new level.
+    pub fn adjust(&self, backend: &dyn MediaPlayer, delta: i32) -> Result<u32, PlayerError> {
+        let current = self.current_level(backend)?;
+        let level = clamp_level(current as i64 + delta as i64);
+        self.request(backend, level)
+    }
+
+    /// Mutes the player, or restores the level it had before being muted.
+    pub fn toggle_mute(&self, backend: &dyn MediaPlayer) -> Result<u32, PlayerError> {
+        let current = self.current_level(backend)?;
+        let level = {
+            let mut state = self.state();
+            match state.muted_from.take() {
+                Some(previous) if current == 0 => previous,
+                _ if current == 0 => params::UNMUTE_FALLBACK_VOLUME,
+                _ => {
+                    state.muted_from = Some(current);
+                    0
+                }
+            }
+        };
+        self.request(backend, level)
+    }
+
+    /// Records a level polled from the player and returns whether it was changed
+    /// from outside Noci.
+    pub fn observe(&self, level: Option<u32>) -> bool {
+        self.observe_at(level, Instant::now())
+    }
+
+    /// Records a level polled from the player at the given instant.
+    fn observe_at(&self, level: Option<u32>, now: Instant) -> bool {
+        let mut state = self.state();
+
+        // Polls racing a change made by Noci report stale levels until it settles
+        if let Some((target, requested_at)) = state.target {
+            if now.saturating_duration_since(requested_at) < params::VOLUME_SETTLE_WINDOW {
+                if level == Some(target) {
+                    state.known = level;
+                }
+                return false;
+            }
+            state.target = None;
+        }
+
+        let external = level != state.known;
+        if external && level.is_some_and(|level| level > 0) {
+            state.muted_from = None;
+        }
+        state.known = level;
+        state.applied = level;
+        external
+    }
+
+    /// Forgets every level, e.g. after switching to another player.
+    pub fn reset(&self) {
+        *self.state() = VolumeState::default();
+    }
+
+    /// Returns the level adjustments build on: the one requested last while it
+    /// settles, otherwise the polled one, queried from the player if unknown.
+    fn current_level(&self, backend: &dyn MediaPlayer) -> Result<u32, PlayerError> {
+        {
+            let state = self.state();
+            match state.target {
+                Some((target, requested_at))
+                    if requested_at.elapsed() < params::VOLUME_SETTLE_WINDOW =>
+                {
+                    return Ok(target)
+                }
+                _ => {}
+            }
+            if let Some(known) = state.known {
+                return Ok(known);
+            }
+        }
+
+        let level = backend
+            .status()?
+            .track_volume
+            .ok_or_else(|| PlayerError::unsupported("volume"))?;
+        self.state().known = Some(level);
+        Ok(level)
+    }
+
+    /// Records the requested level and sends it unless a newer one superseded it.
+    fn request(&self, backend: &dyn MediaPlayer, level: u32) -> Result<u32, PlayerError> {
+        self.state().target = Some((level, Instant::now()));
+
+        let _applying = self.apply.lock().unwrap_or_else(|e| e.into_inner());
+        let (target, applied) = {
+            let state = self.state();
+            (
+                state.target.map_or(level, |(target, _)| target),
+                state.applied,
+            )
+        };
+        if applied != Some(target) {
+            if let Err(error) = backend.set_volume(target) {
+                self.state().target = None;
+                return Err(error);
+            }
+            let mut state = self.state();
+            state.applied = Some(target);
+            state.known = Some(target);
+        }
+        Ok(target)
+    }
+
+    /// Locks the state, recovering from a poisoned lock.
+    fn state(&self) -> MutexGuard<'_, VolumeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Clamps a volume level to 0–100.
+fn clamp_level(level: i64) -> u32 {
+    level.clamp(0, 100) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::params::{RepeatMode, SpotifyStatus};
+    use crate::player::PlayerCapabilities;
+
+    /// Player reporting a settable volume and recording the levels it is sent.
+    ///
+    /// The reported volume does not follow the levels sent, like a poll lagging
+    /// behind a change.
+    struct FakePlayer {
+        reported: Mutex<Option<u32>>, // Volume reported by the status
+        sent: Mutex<Vec<u32>>,        // Levels sent with `set_volume`
+    }
+
+    impl FakePlayer {
+        fn new(reported: Option<u32>) -> Self {
+            Self {
+                reported: Mutex::new(reported),
+                sent: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn sent(&self) -> Vec<u32> {
+            self.sent.lock().unwrap().clone()
+        }
+    }
+
+    impl MediaPlayer for FakePlayer {
+        fn name(&self) -> &str {
+            "Fake"
+        }
+
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                can_play_pause: false,
+                can_go_next: false,
+                can_go_previous: false,
+                can_seek: false,
+                can_set_volume: true,
+                can_shuffle: false,
+                can_repeat: false,
+                can_play_uri: false,
+                can_save: false,
+                can_queue: false,
+                can_transfer: false,
+            }
+        }
+
+        fn status(&self) -> Result<SpotifyStatus, PlayerError> {
+            Ok(SpotifyStatus {
+                track_volume: *self.reported.lock().unwrap(),
+                ..SpotifyStatus::default()
+            })
+        }
+
+        fn toggle_playback(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn next_track(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn previous_track(&self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_position(&self, _position: f64) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn set_volume(&self, level: u32) -> Result<(), PlayerError> {
+            self.sent.lock().unwrap().push(level);
+            Ok(())
+        }
+
+        fn set_shuffle(&self, _enabled: bool) -> Result<(), PlayerError> {
+            Err(PlayerError::unsupported("shuffle"))
+        }
+
+        fn set_repeat(&self, _mode: RepeatMode) -> Result<(), PlayerError> {
+            Err(PlayerError::unsupported("repeat"))
+        }
+    }
+
+    /// Returns an instant after the settle window of a change requested now.
+    fn settled() -> Instant {
+        Instant::now() + params::VOLUME_SETTLE_WINDOW + Duration::from_millis(1)
+    }
+
+    #[test]
+    fn levels_are_clamped() {
+        let player = FakePlayer::new(Some(50));
+        let volume = VolumeControl::new();
+
+        assert_eq!(volume.set(&player, -5).unwrap(), 0);
+        assert_eq!(volume.set(&player, 150).unwrap(), 100);
+        assert_eq!(volume.adjust(&player, 10).unwrap(), 100);
+        assert_eq!(volume.adjust(&player, -130).unwrap(), 0);
+        assert_eq!(player.sent(), [0, 100, 0]);
+    }
+
+    #[test]
+    fn quick_adjustments_stack() {
+        let player = FakePlayer::new(Some(40));
+        let volume = VolumeControl::new();
+
+        assert_eq!(volume.adjust(&player, 5).unwrap(), 45);
+        // The player still reports 40 while the change settles
+        assert!(!volume.observe(Some(40)));
+        assert_eq!(volume.adjust(&player, 5).unwrap(), 50);
+        assert_eq!(player.sent(), [45, 50]);
+    }
+
+    #[test]
+    fn unknown_volume_is_unsupported() {
+        let player = FakePlayer::new(None);
+        let volume = VolumeControl::new();
+
+        assert!(matches!(
+            volume.adjust(&player, 5),
+            Err(PlayerError::Unsupported { .. })
+        ));
+        assert!(player.sent().is_empty());
+    }
+
+    #[test]
+    fn unmuting_restores_the_level_before_muting() {
+        let player = FakePlayer::new(Some(60));
+        let volume = VolumeControl::new();
+
+        assert_eq!(volume.toggle_mute(&player).unwrap(), 0);
+        assert_eq!(volume.toggle_mute(&player).unwrap(), 60);
+        assert_eq!(player.sent(), [0, 60]);
+    }
+
+    #[test]
+    fn unmuting_without_memory_uses_the_fallback_level() {
+        // Muted outside Noci
+        let player = FakePlayer::new(Some(0));
+        let volume = VolumeControl::new();
+
+        assert_eq!(
+            volume.toggle_mute(&player).unwrap(),
+            params::UNMUTE_FALLBACK_VOLUME
+        );
+        assert_eq!(player.sent(), [params::UNMUTE_FALLBACK_VOLUME]);
+    }
+
+    #[test]
+    fn external_unmute_forgets_the_muted_level() {
+        let player = FakePlayer::new(Some(60));
+        let volume = VolumeControl::new();
+        volume.toggle_mute(&player).unwrap();
+
+        // Unmuted to 20 in the player itself, then muted from Noci again
+        assert!(volume.observe_at(Some(20), settled()));
+        assert_eq!(volume.toggle_mute(&player).unwrap(), 0);
+        assert_eq!(volume.toggle_mute(&player).unwrap(), 20);
+    }
+
+    #[test]
+    fn own_changes_are_not_external() {
+        let player = FakePlayer::new(Some(40));
+        let volume = VolumeControl::new();
+        assert!(volume.observe(Some(40)));
+        assert!(!volume.observe(Some(40)));
+
+        volume.set(&player, 70).unwrap();
+        // Stale and settled levels within the window are both ignored
+        assert!(!volume.observe(Some(40)));
+        assert!(!volume.observe(Some(70)));
+        assert!(!volume.observe_at(Some(70), settled()));
+    }
+
+    #[test]
+    fn changes_after_the_settle_window_are_external() {
+        let player = FakePlayer::new(Some(40));
+        let volume = VolumeControl::new();
+        volume.set(&player, 70).unwrap();
+
+        assert!(!volume.observe(Some(20)));
+        assert!(volume.observe_at(Some(20), settled()));
+    }
+
+    #[test]
+    fn reset_forgets_the_levels() {
+        let player = FakePlayer::new(Some(60));
+        let volume = VolumeControl::new();
+        volume.toggle_mute(&player).unwrap();
+
+        volume.reset();
+        *player.reported.lock().unwrap() = Some(0);
+        assert_eq!(
+            volume.toggle_mute(&player).unwrap(),
+            params::UNMUTE_FALLBACK_VOLUME
+        );
+    }
+}
@@ -4,6 +4,8 @@ use cocoa::foundation::{NSPoint, NSRect, NSSize};
 use objc::declare::ClassDecl;
 use objc::runtime::{Class, Object, Sel};
 use objc::{class, msg_send, sel, sel_impl};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
 /// Exits the app.
 #[tauri::command]
@@ -20,6 +22,32 @@ impl std::ops::BitOr for crate::params::NSTrackingAreaOptions {
 
 static mut TRACK_VIEW_CLASS_REGISTERED: bool = false;
 
+/// Whether the notch is currently expanded (hovered).
+static NOTCH_EXPANDED: AtomicBool = AtomicBool::new(false);
+
+/// Listener notified when the notch expands or collapses.
+static NOTCH_EXPANSION_LISTENER: OnceLock<Box<dyn Fn(bool) + Send + Sync>> = OnceLock::new();
+
+/// Returns whether the notch is currently expanded.
+pub fn is_notch_expanded() -> bool {
+    NOTCH_EXPANDED.load(Ordering::Relaxed)
+}
+
+/// Registers the listener notified when the notch expands or collapses.
+///
+/// Only the first registered listener is kept.
+pub fn on_notch_expansion_changed(listener: impl Fn(bool) + Send + Sync + 'static) {
+    let _ = NOTCH_EXPANSION_LISTENER.set(Box::new(listener));
+}
+
+/// Records the new expansion state and notifies the listener, if any.
+fn set_notch_expanded(expanded: bool) {
+    NOTCH_EXPANDED.store(expanded, Ordering::Relaxed);
+    if let Some(listener) = NOTCH_EXPANSION_LISTENER.get() {
+        listener(expanded);
+    }
+}
+
 /// Registers the `TrackView` class.
 ///
 /// This function registers a new class called `TrackView` that inherits from `NSView`.
@@ -126,6 +154,7 @@ extern "C" fn mouse_exited(this: &Object, _: Sel, _event: id) {
             let _: () = msg_send![window, setFrame: new_frame display: YES animate: YES];
         }
     }
+    set_notch_expanded(false);
 }
 
 /// Called when the mouse exits the `TrackView` object.
@@ -140,6 +169,7 @@ extern "C" fn mouse_entered(this: &Object, _: Sel, _event: id) {
             let _: () = msg_send![animator, setFrame: new_frame display: YES];
         }
     }
+    set_notch_expanded(true);
 }
 
 /// Called when the `TrackView` object needs to update its tracking areas.